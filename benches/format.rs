@@ -0,0 +1,76 @@
+#![feature(c_variadic)]
+
+use core::ffi::{CStr, c_char, c_int};
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use printf_compat::{CompiledFormat, Config, format, output};
+
+/// A typical log line from a C library.
+const FORMAT: &CStr = c"[%s:%d] %s: read %zu bytes at offset %#010lx (%5.1f%%)\n";
+
+unsafe extern "C" fn parse_every_time(out: &mut String, str: *const c_char, args: ...) -> c_int {
+    unsafe { format(str, args, output::fmt_write(out)) }
+}
+
+unsafe extern "C" fn compiled(out: &mut String, compiled: &CompiledFormat, args: ...) -> c_int {
+    unsafe { compiled.format(args, output::fmt_write(out)) }
+}
+
+fn bench_format(c: &mut Criterion) {
+    let mut out = String::new();
+    let mut group = c.benchmark_group("log line");
+    group.bench_function("format", |b| {
+        b.iter(|| unsafe {
+            out.clear();
+            parse_every_time(
+                &mut out,
+                black_box(FORMAT).as_ptr(),
+                c"usb.c".as_ptr(),
+                123,
+                c"bulk".as_ptr(),
+                4096usize,
+                0x1f000 as core::ffi::c_long,
+                37.5,
+            )
+        })
+    });
+    let compiled_format: CompiledFormat = CompiledFormat::new(FORMAT, &Config::new()).unwrap();
+    group.bench_function("CompiledFormat", |b| {
+        b.iter(|| unsafe {
+            out.clear();
+            compiled(
+                &mut out,
+                black_box(&compiled_format),
+                c"usb.c".as_ptr(),
+                123,
+                c"bulk".as_ptr(),
+                4096usize,
+                0x1f000 as core::ffi::c_long,
+                37.5,
+            )
+        })
+    });
+    group.finish();
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (name, format) in [
+        ("log line", FORMAT),
+        (
+            "long text",
+            c"This is a long run of literal text, without any conversions in it, \
+              of the kind that a C library might log at startup.\n",
+        ),
+        ("percents", c"100%% done, 50%% left, %d%% total, %%%%\n"),
+    ] {
+        group.bench_function(name, |b| {
+            b.iter(|| CompiledFormat::<32>::new(black_box(format), &Config::new()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_format, bench_parse);
+criterion_main!(benches);
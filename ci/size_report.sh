@@ -0,0 +1,20 @@
+#!/usr/bin/env bash
+# Print the size of the `.text` section of `ensure_no_std` with each
+# combination of the optional conversion features.
+
+set -euo pipefail
+
+cd "$(dirname "$0")/ensure_no_std"
+
+target=thumbv7m-none-eabi
+# Nothing calls `printf`, so make it the entry point to keep the linker from
+# discarding it.
+export RUSTFLAGS="${RUSTFLAGS:-} -C link-arg=--entry=printf"
+
+printf '%-48s %8s\n' features .text
+for features in "" n-specifier m-specifier wide positional float float,n-specifier float,hexfloat \
+    float,hexfloat,n-specifier float,hexfloat,n-specifier,m-specifier,wide,positional; do
+    cargo build --quiet --release --target "$target" --no-default-features --features "$features"
+    size=$(size -A "target/$target/release/ensure_no_std" | awk '$1 == ".text" { print $2 }')
+    printf '%-48s %8s\n' "${features:-(none)}" "$size"
+done
@@ -0,0 +1,229 @@
+//! Decode a stream of messages written by printf-compat's
+//! [`output::binary::Encoder`] back into text.
+//!
+//! ```text
+//! printf-compat-decode (--elf FILE | --json FILE) [--follow] [--pointer-extensions] [INPUT]
+//! ```
+//!
+//! The format strings come from either the `.rodata` sections of the
+//! firmware's ELF file, if the IDs are the addresses of the strings, or a
+//! JSON string table. The messages are read from `INPUT`, or standard input
+//! if it's missing or `-`, and each is printed on its own line, byte for
+//! byte, even if its strings aren't UTF-8. A message that can't be decoded,
+//! or is still incomplete after 64 KiB, is reported, and the bytes after it
+//! are skipped until one can be.
+//!
+//! With `--follow`, it keeps waiting for more messages at the end of the
+//! input, like `tail -f`, instead of exiting. The format strings are parsed
+//! with the default [`Config`], plus the kernel's `%p` extensions with
+//! `--pointer-extensions`, which has to match the one the messages were
+//! encoded with.
+
+mod table;
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::process::ExitCode;
+use std::thread;
+use std::time::Duration;
+
+use printf_compat::output::{self, binary};
+use printf_compat::{Config, Error};
+
+use table::Table;
+
+const USAGE: &str = "usage: printf-compat-decode (--elf FILE | --json FILE) [--follow] \
+                     [--pointer-extensions] [INPUT]";
+
+/// How long to wait for more input with `--follow`.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The most input that's read while waiting for the end of a message. One
+/// that isn't complete by then is taken to be corrupt, since a damaged length
+/// or string could otherwise have the decoder wait for input that never
+/// comes.
+const MAX_MESSAGE: usize = 64 * 1024;
+
+struct Options {
+    table: Table,
+    config: Config,
+    follow: bool,
+    input: Option<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut table = None;
+    let mut config = Config::new();
+    let mut follow = false;
+    let mut input = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--elf" | "--json" => {
+                let path = args.next().ok_or_else(|| format!("{arg} needs a file"))?;
+                let file = std::fs::read(&path).map_err(|e| format!("{path}: {e}"))?;
+                let parsed = if arg == "--elf" {
+                    Table::elf(&file)
+                } else {
+                    Table::json(&file)
+                };
+                table = Some(parsed.map_err(|e| format!("{path}: {e}"))?);
+            }
+            "-f" | "--follow" => follow = true,
+            "--pointer-extensions" => config = config.pointer_extensions(true),
+            "-h" | "--help" => return Err(USAGE.into()),
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("unknown option `{arg}`\n{USAGE}"));
+            }
+            _ if input.is_some() => return Err(USAGE.into()),
+            _ => input = Some(arg),
+        }
+    }
+    Ok(Options {
+        table: table.ok_or(USAGE)?,
+        config,
+        follow,
+        input: input.filter(|input| input != "-"),
+    })
+}
+
+/// The crate's error behind an [`io::Error`] from [`output::io_write`].
+fn decode_error(e: &io::Error) -> Option<Error> {
+    e.get_ref()?.downcast_ref::<Error>().copied()
+}
+
+/// Why a message couldn't be decoded.
+fn describe(options: &Options, e: Option<Error>, id: Option<u64>) -> String {
+    match e {
+        Some(Error::MissingArgument) => "the input ends in the middle of a message".into(),
+        Some(Error::InvalidFormat) => match id {
+            Some(id) if options.table.get(id).is_some() => {
+                format!("the format string for ID {id:#x} is invalid")
+            }
+            Some(id) => format!("unknown format string ID {id:#x}"),
+            None => "the input ends in the middle of a message".into(),
+        },
+        Some(e) => format!("can't decode a message: {e}"),
+        None => "can't decode a message".into(),
+    }
+}
+
+/// Bytes that were skipped, one at a time, after a message couldn't be
+/// decoded, until one could be.
+struct Skipped {
+    reason: String,
+    len: usize,
+}
+
+impl Skipped {
+    fn report(self) {
+        eprintln!(
+            "printf-compat-decode: {}; skipped {} bytes",
+            self.reason, self.len
+        );
+    }
+}
+
+/// Decode every message from `input` to `out`, returning whether they could
+/// all be decoded.
+///
+/// A message that can't be decoded is reported, and decoding starts again
+/// from each following byte until one works, since the messages aren't
+/// framed.
+fn run(options: &Options, input: &mut dyn Read, out: &mut dyn Write) -> Result<bool, String> {
+    let mut buf = Vec::new();
+    let mut chunk = [0; 4096];
+    let mut eof = false;
+    let mut skipped: Option<Skipped> = None;
+    let mut ok = true;
+    loop {
+        let mut bytes = &buf[..];
+        while !bytes.is_empty() {
+            let mut id = None;
+            let formats = |found| {
+                id = Some(found);
+                options.table.get(found)
+            };
+            // The arguments are written as they are, even if they aren't
+            // UTF-8.
+            let mut message = Vec::new();
+            let mut rest = bytes;
+            let res = binary::decode(
+                &mut rest,
+                formats,
+                &options.config,
+                output::io_sink(&mut message),
+            );
+            let reason = match res.as_ref().map_err(decode_error) {
+                Ok(_) => None,
+                // The rest of the message hasn't been read yet.
+                Err(Some(Error::MissingArgument)) if !eof && bytes.len() < MAX_MESSAGE => break,
+                Err(Some(Error::MissingArgument)) if !eof => {
+                    Some(format!("a message is longer than {MAX_MESSAGE} bytes"))
+                }
+                Err(e @ Some(Error::MissingArgument)) => {
+                    if let Some(skipped) = skipped.take() {
+                        skipped.report();
+                    }
+                    return Err(describe(options, e, id));
+                }
+                Err(e) => Some(describe(options, e, id)),
+            };
+            if let Some(reason) = reason {
+                skipped.get_or_insert(Skipped { reason, len: 0 }).len += 1;
+                ok = false;
+                bytes = &bytes[1..];
+                continue;
+            }
+            if let Some(skipped) = skipped.take() {
+                skipped.report();
+            }
+            if !message.ends_with(b"\n") {
+                message.push(b'\n');
+            }
+            out.write_all(&message).map_err(|e| e.to_string())?;
+            bytes = rest;
+        }
+        let consumed = buf.len() - bytes.len();
+        buf.drain(..consumed);
+        if eof {
+            if let Some(skipped) = skipped.take() {
+                skipped.report();
+            }
+            return Ok(ok);
+        }
+
+        let len = match input.read(&mut chunk) {
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.to_string()),
+        };
+        if len == 0 && options.follow {
+            out.flush().map_err(|e| e.to_string())?;
+            thread::sleep(POLL_INTERVAL);
+        } else if len == 0 {
+            eof = true;
+        }
+        buf.extend_from_slice(&chunk[..len]);
+    }
+}
+
+fn main() -> ExitCode {
+    let result = parse_args(std::env::args().skip(1)).and_then(|options| {
+        let mut out = io::stdout().lock();
+        match &options.input {
+            Some(path) => {
+                let mut file = File::open(path).map_err(|e| format!("{path}: {e}"))?;
+                run(&options, &mut file, &mut out)
+            }
+            None => run(&options, &mut io::stdin().lock(), &mut out),
+        }
+    });
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("printf-compat-decode: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Looking up format strings by their ID.
+
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+
+use object::{Object, ObjectSection};
+use serde_json::Value;
+
+/// Where the format strings come from.
+pub enum Table {
+    /// The `.rodata` sections of an ELF file, where an ID is the address of
+    /// the string.
+    Elf(Vec<Section>),
+    /// A JSON string table.
+    Json(HashMap<u64, CString>),
+}
+
+/// A loaded section of an ELF file.
+pub struct Section {
+    address: u64,
+    data: Vec<u8>,
+}
+
+impl Table {
+    /// Read the read-only data sections of an ELF file.
+    pub fn elf(file: &[u8]) -> Result<Self, String> {
+        let file = object::File::parse(file).map_err(|e| format!("invalid ELF file: {e}"))?;
+        let mut sections = Vec::new();
+        for section in file.sections() {
+            if !section.name().is_ok_and(|name| name.starts_with(".rodata")) {
+                continue;
+            }
+            let data = section
+                .data()
+                .map_err(|e| format!("can't read a `.rodata` section: {e}"))?;
+            sections.push(Section {
+                address: section.address(),
+                data: data.to_vec(),
+            });
+        }
+        if sections.is_empty() {
+            return Err("the ELF file has no `.rodata` section".into());
+        }
+        Ok(Table::Elf(sections))
+    }
+
+    /// Parse a JSON string table: either an array of format strings, indexed
+    /// by their ID, or an object from IDs to format strings. The IDs are
+    /// decimal, or hexadecimal if they start with `0x`.
+    pub fn json(file: &[u8]) -> Result<Self, String> {
+        let value: Value =
+            serde_json::from_slice(file).map_err(|e| format!("invalid JSON: {e}"))?;
+        let entries: Vec<(u64, &Value)> = match &value {
+            Value::Array(formats) => (0..).zip(formats).collect(),
+            Value::Object(formats) => formats
+                .iter()
+                .map(|(id, format)| Ok((parse_id(id)?, format)))
+                .collect::<Result<_, String>>()?,
+            _ => return Err("the string table must be a JSON array or object".into()),
+        };
+        let mut formats = HashMap::new();
+        for (id, format) in entries {
+            let format = format
+                .as_str()
+                .ok_or_else(|| format!("the format string for ID {id:#x} isn't a string"))?;
+            let format = CString::new(format)
+                .map_err(|_| format!("the format string for ID {id:#x} contains a null"))?;
+            formats.insert(id, format);
+        }
+        Ok(Table::Json(formats))
+    }
+
+    /// The format string with this ID, if there is one.
+    pub fn get(&self, id: u64) -> Option<&CStr> {
+        match self {
+            Table::Elf(sections) => sections.iter().find_map(|section| {
+                let offset = usize::try_from(id.checked_sub(section.address)?).ok()?;
+                CStr::from_bytes_until_nul(section.data.get(offset..)?).ok()
+            }),
+            Table::Json(formats) => formats.get(&id).map(CString::as_c_str),
+        }
+    }
+}
+
+fn parse_id(id: &str) -> Result<u64, String> {
+    match id.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => id.parse(),
+    }
+    .map_err(|_| format!("invalid format string ID `{id}`"))
+}
@@ -0,0 +1,300 @@
+#![feature(c_variadic)]
+
+use std::ffi::{CStr, CString, c_char};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+
+use object::{Object, ObjectSection, ObjectSymbol};
+use printf_compat::output::binary::Encoder;
+use printf_compat::{Config, PrintfArg};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// The address and contents of a format string in the fixture ELF file.
+fn format(symbol: &str) -> (u64, CString) {
+    let data = std::fs::read(fixture("firmware.elf")).unwrap();
+    let file = object::File::parse(&*data).unwrap();
+    let symbol = file.symbols().find(|s| s.name() == Ok(symbol)).unwrap();
+    let section = file
+        .section_by_index(symbol.section_index().unwrap())
+        .unwrap();
+    let offset = (symbol.address() - section.address()) as usize;
+    let format = CStr::from_bytes_until_nul(&section.data().unwrap()[offset..]).unwrap();
+    (symbol.address(), format.to_owned())
+}
+
+fn encode(id: u64, format: &CStr, args: &[&dyn PrintfArg]) -> Vec<u8> {
+    let mut buf = [0; 256];
+    let mut encoder = Encoder::new(&mut buf, id);
+    printf_compat::format_slice(format, args, &mut encoder).unwrap();
+    encoder.as_bytes().to_vec()
+}
+
+/// Encode a message whose arguments can't be passed in a slice, onto `out`.
+unsafe extern "C" fn encode_va(
+    out: &mut Vec<u8>,
+    config: &Config,
+    id: u64,
+    format: *const c_char,
+    args: ...
+) {
+    let mut buf = [0; 256];
+    let mut encoder = Encoder::new(&mut buf, id);
+    let printf = printf_compat::Printf::with_config(*config);
+    unsafe { printf.try_format(format, args, &mut encoder) }.unwrap();
+    out.extend(encoder.as_bytes());
+}
+
+/// A path in the temporary directory that no other test, or test run, uses.
+fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "printf-compat-decode-{}-{name}",
+        std::process::id()
+    ))
+}
+
+/// A child process that's killed when it goes out of scope, even if a test
+/// fails.
+struct KillOnDrop(Child);
+
+impl Drop for KillOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// The lines that `child` prints, read on another thread.
+fn lines(child: &mut Child) -> Receiver<String> {
+    let (tx, rx) = mpsc::channel();
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    std::thread::spawn(move || {
+        for line in stdout.lines() {
+            if tx.send(line.unwrap()).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+fn decoder(args: &[&str]) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_printf-compat-decode"));
+    command.args(args);
+    command
+}
+
+/// Run the decoder with `input` on standard input.
+fn run(args: &[&str], input: &[u8]) -> Output {
+    let mut child = decoder(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+    std::str::from_utf8(&output.stdout).unwrap()
+}
+
+fn stderr(output: &Output) -> &str {
+    std::str::from_utf8(&output.stderr).unwrap()
+}
+
+#[test]
+fn test_elf() {
+    let elf = fixture("firmware.elf");
+    let elf = elf.to_str().unwrap();
+    let (boot, boot_format) = format("boot");
+    let (sensor, sensor_format) = format("sensor");
+    let (table, table_format) = format("table");
+    let mut input = encode(boot, &boot_format, &[&1234u32]);
+    input.extend(encode(sensor, &sensor_format, &[&c"temp", &21.456]));
+    input.extend(encode(table, &table_format, &[&c"disk", &-42, &255]));
+    input.extend(encode(sensor, &sensor_format, &[&None::<&CStr>, &-0.5]));
+
+    let output = run(&["--elf", elf], &input);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "booted in 1234 ms\nsensor temp: +21.46\ndisk    |  -42|0xff\nsensor (null): -0.50\n"
+    );
+
+    // An input file works the same way.
+    let path = temp_path("elf.bin");
+    std::fs::write(&path, &input).unwrap();
+    let file_output = decoder(&["--elf", elf, path.to_str().unwrap()])
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(file_output.stdout, output.stdout);
+}
+
+#[test]
+fn test_elf_config() {
+    let elf = fixture("firmware.elf");
+    let elf = elf.to_str().unwrap();
+    let (link_up, link_format) = format("link_up");
+    let (errno, errno_format) = format("errno_fmt");
+    let config = Config::new().pointer_extensions(true);
+    let addr = [192u8, 168, 0, 1];
+    let mut input = Vec::new();
+    unsafe {
+        encode_va(
+            &mut input,
+            &config,
+            link_up,
+            link_format.as_ptr(),
+            addr.as_ptr(),
+        );
+        encode_va(
+            &mut input,
+            &config.errno(|| 2),
+            errno,
+            errno_format.as_ptr(),
+        );
+    }
+
+    // Messages without a newline get one.
+    let output = run(&["--pointer-extensions", "--elf", elf], &input);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "link 192.168.0.1 up\nopen failed: No such file or directory\n"
+    );
+}
+
+#[test]
+fn test_json() {
+    let json = fixture("strings.json");
+    let json = json.to_str().unwrap();
+    let mut input = encode(0x10, c"%-8s|%5d|%#x", &[&c"net", &7, &16]);
+    input.extend(encode(0, c"booted in %u ms\n", &[&5u32]));
+    input.extend(encode(1, c"sensor %s: %+.2f\n", &[&c"hum", &40.0]));
+
+    let output = run(&["--json", json, "-"], &input);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "net     |    7|0x10\nbooted in 5 ms\nsensor hum: +40.00\n"
+    );
+
+    // Strings that aren't UTF-8 are copied as they are.
+    let input = encode(0x10, c"%-8s|%5d|%#x", &[&c"\xff\xfe", &1, &2]);
+    let output = run(&["--json", json], &input);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(output.stdout, b"\xff\xfe      |    1|0x2\n");
+
+    let path = temp_path("table.json");
+    std::fs::write(&path, r#"["first %d", "second %s\n"]"#).unwrap();
+    let mut input = encode(1, c"second %s\n", &[&c"x"]);
+    input.extend(encode(0, c"first %d", &[&1]));
+    let output = run(&["--json", path.to_str().unwrap()], &input);
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "second x\nfirst 1\n");
+}
+
+#[test]
+fn test_errors() {
+    let elf = fixture("firmware.elf");
+    let elf = elf.to_str().unwrap();
+    let (boot, boot_format) = format("boot");
+    let message = encode(boot, &boot_format, &[&300u32]);
+
+    // The complete messages are printed before the error.
+    let mut input = message.clone();
+    input.extend(&message[..message.len() - 1]);
+    let output = run(&["--elf", elf], &input);
+    assert!(!output.status.success());
+    assert_eq!(stdout(&output), "booted in 300 ms\n");
+    assert!(stderr(&output).contains("in the middle of a message"));
+
+    let output = run(&["--elf", elf], &encode(0x1234, c"%d", &[&1]));
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("unknown format string ID 0x1234"));
+
+    // Decoding picks up again after a message that can't be decoded.
+    let mut input = encode(0x1234, c"%d", &[&1]);
+    input.extend(&message);
+    let output = run(&["--elf", elf], &input);
+    assert!(!output.status.success());
+    assert_eq!(stdout(&output), "booted in 300 ms\n");
+    assert!(stderr(&output).contains("unknown format string ID 0x1234; skipped"));
+
+    let output = run(&["--elf", fixture("strings.json").to_str().unwrap()], &[]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("invalid ELF file"));
+
+    let output = run(&["--json", elf], &[]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("invalid JSON"));
+
+    let output = run(&[], &[]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("usage:"));
+}
+
+#[test]
+fn test_follow() {
+    let elf = fixture("firmware.elf");
+    let (boot, boot_format) = format("boot");
+    let path = temp_path("follow.bin");
+    std::fs::write(&path, encode(boot, &boot_format, &[&1u32])).unwrap();
+
+    let child = decoder(&[
+        "--follow",
+        "--elf",
+        elf.to_str().unwrap(),
+        path.to_str().unwrap(),
+    ])
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null())
+    .spawn()
+    .unwrap();
+    let mut child = KillOnDrop(child);
+    let lines = lines(&mut child.0);
+    // A decoder that stops printing fails the test rather than hanging it.
+    let next = || lines.recv_timeout(Duration::from_secs(30)).unwrap();
+    assert_eq!(next(), "booted in 1 ms");
+
+    // A message written in pieces is printed once it's complete.
+    let message = encode(boot, &boot_format, &[&200u32]);
+    let mut file = std::fs::OpenOptions::new()
+        .append(true)
+        .open(&path)
+        .unwrap();
+    file.write_all(&message[..2]).unwrap();
+    file.flush().unwrap();
+    std::thread::sleep(Duration::from_millis(300));
+    file.write_all(&message[2..]).unwrap();
+    assert_eq!(next(), "booted in 200 ms");
+
+    // A message whose length is corrupt isn't waited for forever. It has the
+    // ID of one with no conversions, then the tag of a byte string that
+    // claims to be 4 GiB long.
+    let mut corrupt = encode(boot, c"%%", &[]);
+    corrupt.extend([51, 0xff, 0xff, 0xff, 0xff, 0x0f]);
+    file.write_all(&corrupt).unwrap();
+    let message = encode(boot, &boot_format, &[&7u32]);
+    let count = 64 * 1024 / message.len() + 1;
+    file.write_all(&message.repeat(count)).unwrap();
+    for _ in 0..count {
+        assert_eq!(next(), "booted in 7 ms");
+    }
+
+    // It's still running, waiting for more.
+    assert!(child.0.try_wait().unwrap().is_none());
+    drop(child);
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,17 @@
+/*
+ * The format strings for printf-compat-decode's tests, as a device's firmware
+ * would have them. Rebuild firmware.elf with:
+ *
+ *     gcc -Os -nostdlib -static -no-pie -o firmware.elf firmware.c
+ */
+
+const char boot[] = "booted in %u ms\n";
+const char sensor[] = "sensor %s: %+.2f\n";
+const char table[] = "%-8s|%5d|%#x\n";
+const char link_up[] = "link %pI4 up";
+const char errno_fmt[] = "open failed: %m\n";
+
+void _start(void) {
+	for (;;) {
+	}
+}
@@ -0,0 +1,248 @@
+//! Procedural macros for [printf-compat](https://docs.rs/printf-compat).
+//!
+//! Don't use this crate directly: enable the `macros` feature of
+//! `printf-compat` instead, which re-exports everything here.
+
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, quote_spanned};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{Expr, Lit, LitByteStr, Token};
+
+use printf_compat_spec::{Count, Extensions, Length, Spec, parse_spec};
+
+struct Input {
+    format: Vec<u8>,
+    format_span: Span,
+    args: Vec<Expr>,
+}
+
+impl Parse for Input {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let lit: Lit = input.parse()?;
+        let format = match &lit {
+            Lit::CStr(s) => s.value().into_bytes(),
+            Lit::Str(s) => s.value().into_bytes(),
+            _ => return Err(syn::Error::new(lit.span(), "expected a string literal")),
+        };
+        let args = if input.is_empty() {
+            Vec::new()
+        } else {
+            input.parse::<Token![,]>()?;
+            Punctuated::<Expr, Token![,]>::parse_terminated(input)?
+                .into_iter()
+                .collect()
+        };
+        Ok(Input {
+            format,
+            format_span: lit.span(),
+            args,
+        })
+    }
+}
+
+/// Turns the next argument into an expression of type `ty`, so that the
+/// compiler reports a type mismatch at the argument.
+fn typed_arg(
+    args: &mut std::slice::Iter<Expr>,
+    ty: TokenStream,
+    input: &Input,
+) -> syn::Result<TokenStream> {
+    let arg = args.next().ok_or_else(|| {
+        syn::Error::new(
+            input.format_span,
+            format!(
+                "format string requires more than {} argument(s)",
+                input.args.len()
+            ),
+        )
+    })?;
+    Ok(quote_spanned! {arg.span()=> {
+        let arg: #ty = #arg;
+        arg
+    }})
+}
+
+fn count(
+    count: Count,
+    args: &mut std::slice::Iter<Expr>,
+    input: &Input,
+) -> syn::Result<TokenStream> {
+    match count {
+        Count::Value(value) => Ok(quote!(#value)),
+        Count::Arg => typed_arg(args, quote!(::core::ffi::c_int), input),
+        Count::Position(_) => Err(syn::Error::new(
+            input.format_span,
+            "numbered arguments aren't supported by `printf!`",
+        )),
+    }
+}
+
+fn double(
+    args: &mut std::slice::Iter<Expr>,
+    input: &Input,
+    format: TokenStream,
+) -> syn::Result<TokenStream> {
+    let value = typed_arg(args, quote!(f64), input)?;
+    Ok(quote! {
+        ::printf_compat::argument::Specifier::Double {
+            value: #value,
+            format: ::printf_compat::argument::DoubleFormat::#format,
+        }
+    })
+}
+
+fn specifier(
+    spec: &Spec,
+    args: &mut std::slice::Iter<Expr>,
+    input: &Input,
+) -> syn::Result<TokenStream> {
+    let error = |message: String| Err(syn::Error::new(input.format_span, message));
+    let Some(conversion) = spec.conversion else {
+        return error("incomplete conversion specification at the end of the format string".into());
+    };
+    let ch = conversion as char;
+    let signed = || match spec.length {
+        Length::Int => (quote!(Int), quote!(::core::ffi::c_int)),
+        Length::Char => (quote!(Char), quote!(::core::ffi::c_schar)),
+        Length::Short => (quote!(Short), quote!(::core::ffi::c_short)),
+        Length::Long => (quote!(Long), quote!(::core::ffi::c_long)),
+        Length::LongLong => (quote!(LongLong), quote!(::core::ffi::c_longlong)),
+        Length::Usize | Length::Isize => (quote!(Isize), quote!(isize)),
+    };
+    let unsigned = || match spec.length {
+        Length::Int => (quote!(Int), quote!(::core::ffi::c_uint)),
+        Length::Char => (quote!(Char), quote!(::core::ffi::c_uchar)),
+        Length::Short => (quote!(Short), quote!(::core::ffi::c_ushort)),
+        Length::Long => (quote!(Long), quote!(::core::ffi::c_ulong)),
+        Length::LongLong => (quote!(LongLong), quote!(::core::ffi::c_ulonglong)),
+        Length::Usize | Length::Isize => (quote!(Isize), quote!(usize)),
+    };
+    if spec.length != Length::Int && matches!(conversion, b'%' | b's' | b'c' | b'p') {
+        return error(format!(
+            "length modifiers are not supported with `%{ch}` in `printf!`"
+        ));
+    }
+
+    Ok(match conversion {
+        b'%' => quote!(::printf_compat::argument::Specifier::Percent),
+        b'd' | b'i' => {
+            let (variant, ty) = signed();
+            let value = typed_arg(args, ty, input)?;
+            quote! {
+                ::printf_compat::argument::Specifier::Int(
+                    ::printf_compat::argument::SignedInt::#variant(#value)
+                )
+            }
+        }
+        b'u' | b'o' | b'x' | b'X' | b'b' | b'B' => {
+            let specifier = match conversion {
+                b'u' => quote!(Uint),
+                b'o' => quote!(Octal),
+                b'x' => quote!(Hex),
+                b'b' => quote!(Binary),
+                b'B' => quote!(UpperBinary),
+                _ => quote!(UpperHex),
+            };
+            let (variant, ty) = unsigned();
+            let value = typed_arg(args, ty, input)?;
+            quote! {
+                ::printf_compat::argument::Specifier::#specifier(
+                    ::printf_compat::argument::UnsignedInt::#variant(#value)
+                )
+            }
+        }
+        b'f' => double(args, input, quote!(Normal))?,
+        b'F' => double(args, input, quote!(UpperNormal))?,
+        b'e' => double(args, input, quote!(Scientific))?,
+        b'E' => double(args, input, quote!(UpperScientific))?,
+        b'g' => double(args, input, quote!(Auto))?,
+        b'G' => double(args, input, quote!(UpperAuto))?,
+        b'a' => double(args, input, quote!(Hex))?,
+        b'A' => double(args, input, quote!(UpperHex))?,
+        b's' => {
+            let value = typed_arg(args, quote!(&::core::ffi::CStr), input)?;
+            quote!(::printf_compat::argument::Specifier::String(#value))
+        }
+        b'c' => {
+            let value = typed_arg(args, quote!(::core::ffi::c_char), input)?;
+            quote!(::printf_compat::argument::Specifier::Char(#value))
+        }
+        b'p' => {
+            let value = args
+                .next()
+                .ok_or_else(|| syn::Error::new(input.format_span, "missing argument for `%p`"))?;
+            quote_spanned! {value.span()=>
+                ::printf_compat::argument::Specifier::Pointer(
+                    ::printf_compat::__private::pointer(#value)
+                )
+            }
+        }
+        b'n' => return error("`%n` is not supported by `printf!`".into()),
+        _ => return error(format!("unknown conversion `%{ch}`")),
+    })
+}
+
+fn expand(input: &Input) -> syn::Result<TokenStream> {
+    let mut args = input.args.iter();
+    let mut pieces = Vec::new();
+    let mut rest = &input.format[..];
+    while !rest.is_empty() {
+        let literal_len = rest.iter().position(|&c| c == b'%').unwrap_or(rest.len());
+        let (literal, after) = rest.split_at(literal_len);
+        if !literal.is_empty() {
+            let literal = LitByteStr::new(literal, input.format_span);
+            pieces.push(quote! {
+                ::printf_compat::argument::Argument::from(
+                    ::printf_compat::argument::Specifier::Bytes(#literal)
+                )
+            });
+        }
+        let Some(after) = after.get(1..) else {
+            break;
+        };
+        let (spec, after) = parse_spec(after, Extensions::GLIBC)
+            .ok_or_else(|| syn::Error::new(input.format_span, "width or precision is too large"))?;
+        if spec.position.is_some() {
+            return Err(syn::Error::new(
+                input.format_span,
+                "numbered arguments aren't supported by `printf!`",
+            ));
+        }
+        let flags = spec.flags.bits();
+        let width = count(spec.width, &mut args, input)?;
+        let precision = match spec.precision {
+            Some(precision) => {
+                let precision = count(precision, &mut args, input)?;
+                quote!(::core::option::Option::Some(#precision))
+            }
+            None => quote!(::core::option::Option::None),
+        };
+        let specifier = specifier(&spec, &mut args, input)?;
+        pieces.push(quote! {
+            ::printf_compat::__private::argument(#flags, #width, #precision, #specifier)
+        });
+        rest = after;
+    }
+    if let Some(extra) = args.next() {
+        return Err(syn::Error::new(
+            extra.span(),
+            "argument never used by the format string",
+        ));
+    }
+    Ok(quote! {
+        ::printf_compat::Arguments::new([#(#pieces),*])
+    })
+}
+
+/// Format Rust values with a C format string, checked at compile time.
+///
+/// See the documentation of `printf-compat` for details.
+#[proc_macro]
+pub fn printf(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as Input);
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,268 @@
+//! Parsing of conversion specifications, without reading any arguments.
+//!
+//! Don't use this crate directly: it's shared by
+//! [printf-compat](https://docs.rs/printf-compat) and the
+//! `printf-compat-macros` crate, which checks format strings at compile time.
+
+#![no_std]
+
+use core::ffi::c_int;
+
+bitflags::bitflags! {
+    /// Flags field.
+    ///
+    /// Definitions from
+    /// [Wikipedia](https://en.wikipedia.org/wiki/Printf_format_string#Flags_field).
+    #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+    pub struct Flags: u8 {
+        /// Left-align the output of this placeholder. (The default is to
+        /// right-align the output.)
+        const LEFT_ALIGN = 0b00000001;
+        /// Prepends a plus for positive signed-numeric types. positive =
+        /// `+`, negative = `-`.
+        ///
+        /// (The default doesn't prepend anything in front of positive
+        /// numbers.)
+        const PREPEND_PLUS = 0b00000010;
+        /// Prepends a space for positive signed-numeric types. positive = `
+        /// `, negative = `-`. This flag is ignored if the
+        /// [`PREPEND_PLUS`][Flags::PREPEND_PLUS] flag exists.
+        ///
+        /// (The default doesn't prepend anything in front of positive
+        /// numbers.)
+        const PREPEND_SPACE = 0b00000100;
+        /// When the 'width' option is specified, prepends zeros for numeric
+        /// types. (The default prepends spaces.)
+        ///
+        /// For example, `printf("%4X",3)` produces `   3`, while
+        /// `printf("%04X",3)` produces `0003`.
+        const PREPEND_ZERO = 0b00001000;
+        /// The integer or exponent of a decimal has the thousands grouping
+        /// separator applied.
+        const THOUSANDS_GROUPING = 0b00010000;
+        /// Alternate form:
+        ///
+        /// For `g` and `G` types, trailing zeros are not removed. \
+        /// For `f`, `F`, `e`, `E`, `g`, `G` types, the output always
+        /// contains a decimal point. \ For `o`, `x`, `X` types,
+        /// the text `0`, `0x`, `0X`, respectively, is prepended
+        /// to non-zero numbers.
+        const ALTERNATE_FORM = 0b00100000;
+    }
+}
+
+bitflags::bitflags! {
+    /// Non-standard syntax to accept.
+    #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+    pub struct Extensions: u8 {
+        /// `Z` as a synonym for the `z` length modifier.
+        const GLIBC = 0b00000001;
+        /// Microsoft's `I32`, `I64` and `I` (`size_t`) length modifiers, and
+        /// `w` as a synonym for `l`.
+        const MSVC = 0b00000010;
+    }
+}
+
+/// A character of a format string: a byte, or a wide character.
+pub trait FormatChar: Copy {
+    /// The character, if it's ASCII. Anything else is returned as a byte that
+    /// isn't part of any conversion specification.
+    fn to_ascii(self) -> u8;
+}
+
+impl FormatChar for u8 {
+    fn to_ascii(self) -> u8 {
+        self
+    }
+}
+
+impl FormatChar for u16 {
+    fn to_ascii(self) -> u8 {
+        u8::try_from(self)
+            .ok()
+            .filter(u8::is_ascii)
+            .unwrap_or(u8::MAX)
+    }
+}
+
+impl FormatChar for u32 {
+    fn to_ascii(self) -> u8 {
+        u8::try_from(self)
+            .ok()
+            .filter(u8::is_ascii)
+            .unwrap_or(u8::MAX)
+    }
+}
+
+fn next_char<C>(sub: &[C]) -> &[C] {
+    sub.get(1..).unwrap_or(&[])
+}
+
+fn first<C: FormatChar>(sub: &[C]) -> Option<u8> {
+    sub.first().map(|c| c.to_ascii())
+}
+
+/// Parse the [Flags field](https://en.wikipedia.org/wiki/Printf_format_string#Flags_field).
+fn parse_flags<C: FormatChar>(mut sub: &[C]) -> (Flags, &[C]) {
+    let mut flags: Flags = Flags::empty();
+    while let Some(ch) = first(sub) {
+        flags.insert(match ch {
+            b'-' => Flags::LEFT_ALIGN,
+            b'+' => Flags::PREPEND_PLUS,
+            b' ' => Flags::PREPEND_SPACE,
+            b'0' => Flags::PREPEND_ZERO,
+            b'\'' => Flags::THOUSANDS_GROUPING,
+            b'#' => Flags::ALTERNATE_FORM,
+            _ => break,
+        });
+        sub = next_char(sub)
+    }
+    (flags, sub)
+}
+
+/// A width or precision.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Count {
+    /// Given in the format string.
+    Value(c_int),
+    /// `*`: taken from the next argument.
+    Arg,
+    /// `*n$`: taken from the `n`th argument, counting from 1.
+    Position(usize),
+}
+
+/// Parse the POSIX `n$` that numbers the argument a conversion, or a `*`
+/// width or precision, takes.
+///
+/// Returns `None` if the number doesn't fit in a `usize`, and no position if
+/// there's no `$` after the digits, or the number is 0.
+fn parse_position<C: FormatChar>(sub: &[C]) -> Option<(Option<usize>, &[C])> {
+    let digits = sub
+        .iter()
+        .take_while(|c| c.to_ascii().is_ascii_digit())
+        .count();
+    if digits == 0 || first(sub) == Some(b'0') || first(&sub[digits..]) != Some(b'$') {
+        return Some((None, sub));
+    }
+    let mut position: usize = 0;
+    for c in &sub[..digits] {
+        position = position
+            .checked_mul(10)?
+            .checked_add((c.to_ascii() & 0x0f) as usize)?;
+    }
+    Some((Some(position), &sub[digits + 1..]))
+}
+
+/// Parse the [Width field](https://en.wikipedia.org/wiki/Printf_format_string#Width_field).
+///
+/// Returns `None` if the width doesn't fit in a `c_int`.
+pub fn parse_width<C: FormatChar>(mut sub: &[C]) -> Option<(Count, &[C])> {
+    let mut width: c_int = 0;
+    if first(sub) == Some(b'*') {
+        return match parse_position(next_char(sub))? {
+            (Some(position), sub) => Some((Count::Position(position), sub)),
+            (None, sub) => Some((Count::Arg, sub)),
+        };
+    }
+    while let Some(ch) = first(sub) {
+        match ch {
+            // https://rust-malaysia.github.io/code/2020/07/11/faster-integer-parsing.html#the-bytes-solution
+            b'0'..=b'9' => width = width.checked_mul(10)?.checked_add((ch & 0x0f) as c_int)?,
+            _ => break,
+        }
+        sub = next_char(sub);
+    }
+    Some((Count::Value(width), sub))
+}
+
+/// Parse the [Precision field](https://en.wikipedia.org/wiki/Printf_format_string#Precision_field).
+fn parse_precision<C: FormatChar>(sub: &[C]) -> Option<(Option<Count>, &[C])> {
+    match first(sub) {
+        Some(b'.') => {
+            let (prec, sub) = parse_width(next_char(sub))?;
+            Some((Some(prec), sub))
+        }
+        _ => Some((None, sub)),
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Length {
+    Int,
+    /// `hh`
+    Char,
+    /// `h`
+    Short,
+    /// `l`
+    Long,
+    /// `ll`
+    LongLong,
+    /// `z`
+    Usize,
+    /// `t`
+    Isize,
+}
+
+/// Parse the [Length field](https://en.wikipedia.org/wiki/Printf_format_string#Length_field).
+pub fn parse_length<C: FormatChar>(sub: &[C], extensions: Extensions) -> (Length, &[C]) {
+    match first(sub) {
+        Some(b'h') => match first(next_char(sub)) {
+            Some(b'h') => (Length::Char, sub.get(2..).unwrap_or(&[])),
+            _ => (Length::Short, next_char(sub)),
+        },
+        Some(b'l') => match first(next_char(sub)) {
+            Some(b'l') => (Length::LongLong, sub.get(2..).unwrap_or(&[])),
+            _ => (Length::Long, next_char(sub)),
+        },
+        Some(b'z') => (Length::Usize, next_char(sub)),
+        Some(b'Z') if extensions.contains(Extensions::GLIBC) => (Length::Usize, next_char(sub)),
+        Some(b't') => (Length::Isize, next_char(sub)),
+        Some(b'I') if extensions.contains(Extensions::MSVC) => {
+            let rest = next_char(sub);
+            match (first(rest), first(next_char(rest))) {
+                (Some(b'3'), Some(b'2')) => (Length::Int, sub.get(3..).unwrap_or(&[])),
+                (Some(b'6'), Some(b'4')) => (Length::LongLong, sub.get(3..).unwrap_or(&[])),
+                _ => (Length::Usize, rest),
+            }
+        }
+        Some(b'w') if extensions.contains(Extensions::MSVC) => (Length::Long, next_char(sub)),
+        _ => (Length::Int, sub),
+    }
+}
+
+/// A conversion specification, everything after a `%` up to and including
+/// the conversion character.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Spec {
+    /// The argument's position, from `n$`, if it's numbered.
+    pub position: Option<usize>,
+    pub flags: Flags,
+    pub width: Count,
+    pub precision: Option<Count>,
+    pub length: Length,
+    /// The conversion character, or `None` if the string ended before it.
+    pub conversion: Option<u8>,
+}
+
+/// Parse the conversion specification at the start of `sub` (just after the
+/// `%`), returning it and the rest of the string after the conversion
+/// character.
+///
+/// Returns `None` if a width or precision doesn't fit in a `c_int`, or an
+/// argument position doesn't fit in a `usize`.
+pub fn parse_spec<C: FormatChar>(sub: &[C], extensions: Extensions) -> Option<(Spec, &[C])> {
+    let (position, sub) = parse_position(sub)?;
+    let (flags, sub) = parse_flags(sub);
+    let (width, sub) = parse_width(sub)?;
+    let (precision, sub) = parse_precision(sub)?;
+    let (length, sub) = parse_length(sub, extensions);
+    let spec = Spec {
+        position,
+        flags,
+        width,
+        precision,
+        length,
+        conversion: first(sub),
+    };
+    Some((spec, next_char(sub)))
+}
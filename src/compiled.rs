@@ -0,0 +1,127 @@
+//! Format strings that are parsed once, and formatted many times.
+
+use core::ffi::*;
+
+use crate::output::{Sink, Untyped};
+use crate::parser::{Piece, Pieces, run_compiled, run_compiled_slice};
+use crate::{Config, Error, PrintfArg};
+
+/// A format string that has already been parsed.
+///
+/// [`format`][crate::format()] parses the format string every time it's
+/// called. If the same one is used over and over, like in a logging
+/// callback, it can be parsed once into a `CompiledFormat`, whose
+/// [`format`][Self::format] only has to read the arguments.
+///
+/// It holds up to `N` pieces, where each conversion, and each run of text
+/// between them, is a piece. It doesn't allocate, so it works without `std`.
+///
+/// Parsing checks the syntax of the format string; errors that depend on the
+/// arguments, or on which conversions are allowed, are still returned when
+/// formatting.
+///
+/// To reuse one across calls, cache it by the format string's pointer. This
+/// is only correct if the format strings are never freed or changed, like
+/// string literals in a C library. With the default `N`, it's over a
+/// kilobyte, so keep it in place and borrow it, rather than copying it out of
+/// the cache on every call.
+///
+/// ```rust
+/// # #![feature(c_variadic)]
+/// use core::cell::RefCell;
+/// use core::ffi::{CStr, c_char, c_int};
+/// use printf_compat::{CompiledFormat, Config, output};
+///
+/// thread_local! {
+///     static LAST: RefCell<Option<CompiledFormat<'static>>> = const { RefCell::new(None) };
+/// }
+///
+/// unsafe extern "C" fn log_message(str: *const c_char, args: ...) -> c_int {
+///     LAST.with_borrow_mut(|last| {
+///         if !last.as_ref().is_some_and(|c| c.format_string().as_ptr() == str) {
+///             // SAFETY: the C library only logs string literals.
+///             let format: &'static CStr = unsafe { CStr::from_ptr(str) };
+///             match CompiledFormat::new(format, &Config::new()) {
+///                 Ok(compiled) => *last = Some(compiled),
+///                 Err(_) => return -1,
+///             }
+///         }
+///         let Some(compiled) = last else { return -1 };
+///         let mut s = String::new();
+///         let written = unsafe { compiled.format(args, output::fmt_write(&mut s)) };
+///         println!("{}", s);
+///         written
+///     })
+/// }
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct CompiledFormat<'f, const N: usize = 32> {
+    format: &'f CStr,
+    config: Config,
+    pieces: [Piece<'f, u8>; N],
+    len: usize,
+}
+
+impl<'f, const N: usize> CompiledFormat<'f, N> {
+    /// Parse `format`, to be formatted with `config`.
+    ///
+    /// Returns [`Error::InvalidFormat`] if the format string is invalid, and
+    /// [`Error::Capacity`] if it has more than `N` pieces.
+    pub fn new(format: &'f CStr, config: &Config) -> Result<Self, Error> {
+        let mut pieces = [Piece::Text(&[]); N];
+        let mut len = 0;
+        for piece in Pieces::new(format.to_bytes(), config) {
+            *pieces.get_mut(len).ok_or(Error::Capacity)? = piece?;
+            len += 1;
+        }
+        Ok(CompiledFormat {
+            format,
+            config: *config,
+            pieces,
+            len,
+        })
+    }
+
+    /// The format string that this was parsed from.
+    pub fn format_string(&self) -> &'f CStr {
+        self.format
+    }
+
+    /// The [`Config`] that it formats with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Format the arguments, and write them somewhere. Returns -1 on error.
+    ///
+    /// # Safety
+    ///
+    /// [`VaList`]s are *very* unsafe. The passed `args` must match the format
+    /// string, as for [`format`][crate::format()].
+    pub unsafe fn format(&self, args: VaList, sink: impl Sink) -> c_int {
+        unsafe { self.try_format(args, Untyped(sink)) }.unwrap_or(-1)
+    }
+
+    /// Format the arguments, and write them somewhere, returning why it
+    /// failed instead of -1.
+    ///
+    /// # Safety
+    ///
+    /// [`VaList`]s are *very* unsafe. The passed `args` must match the format
+    /// string, as for [`format`][crate::format()].
+    pub unsafe fn try_format<S: Sink>(&self, args: VaList, sink: S) -> Result<c_int, S::Error>
+    where
+        S::Error: From<Error>,
+    {
+        unsafe { run_compiled(&self.pieces[..self.len], args, &self.config, sink) }
+    }
+
+    /// Format the arguments, taking them from a slice. See
+    /// [`format_slice`][crate::format_slice].
+    pub fn format_slice<S: Sink>(&self, args: &[&dyn PrintfArg], sink: S) -> Result<c_int, S::Error>
+    where
+        S::Error: From<Error>,
+    {
+        run_compiled_slice(&self.pieces[..self.len], args, &self.config, sink)
+    }
+}
@@ -0,0 +1,341 @@
+//! Options that change how format strings are interpreted.
+
+use core::ffi::c_int;
+use core::hash::{Hash, Hasher};
+
+use printf_compat_spec::Extensions;
+
+use crate::{Conversion, Locale};
+
+/// What to do when a format string contains `%n`
+/// ([`Specifier::WriteBytesWritten`][crate::Specifier::WriteBytesWritten]).
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum BytesWrittenPolicy {
+    /// Stop formatting and return an error.
+    #[default]
+    Reject,
+    /// Consume the pointer argument, but don't write anything to it.
+    Ignore,
+    /// Store the number of bytes written so far through the pointer argument,
+    /// like C does. The length modifier is honoured, so `%hhn` writes a
+    /// `signed char`, `%ln` writes a `long`, and so on. A null pointer is an
+    /// error.
+    ///
+    /// # Safety
+    ///
+    /// `%n` is the classic way to turn a format string vulnerability into an
+    /// arbitrary memory write: if a user-provided string is ever passed as the
+    /// format string, it can make this crate write to any address that it
+    /// puts on the argument list. Only use this policy if every format string
+    /// is trusted, for example because they are all string literals in the C
+    /// library being wrapped.
+    Write,
+    /// Pass [`Specifier::WriteBytesWritten`][crate::Specifier::WriteBytesWritten]
+    /// to the handler, and let it decide. The built-in handlers in
+    /// [`output`][crate::output] treat it as an error.
+    Handler,
+}
+
+/// The C library whose `printf` to imitate, where they disagree.
+///
+/// The dialect changes which conversions the parser accepts, so it's set on
+/// the [`Config`]. It also changes how some values are printed, so it has to
+/// be passed to the output adapter too, with
+/// [`output::fmt_write_dialect`][crate::output::fmt_write_dialect] or
+/// [`output::io_write_dialect`][crate::output::io_write_dialect].
+///
+/// | | glibc | musl | MSVC | newlib-nano | C11 |
+/// |-|-|-|-|-|-|
+/// | `%p` of null | `(nil)` | `0` | `0000000000000000` | `0x0` | `0x0` |
+/// | `%p` of `0x1a` | `0x1a` | `0x1a` | `000000000000001A` | `0x1a` | `0x1a` |
+/// | `%s` of null | `(null)` | `(null)` | `(null)` | `(null)` | [`Error::NullPointer`][crate::Error::NullPointer] |
+/// | negative NaN | `-nan` | `-nan` | `-nan(ind)` | `nan` | `-nan` |
+/// | `%#o` of 0 | `0` | `0` | `0` | `00` | `0` |
+/// | `%e` of 1 | `1.000000e+00` | `1.000000e+00` | `1.000000e+000` | `1.000000e+00` | `1.000000e+00` |
+/// | `%Zu` | `size_t` | invalid | `%Z`, then `u` | invalid | invalid |
+/// | `%I64u`, `%I32u`, `%Iu` | invalid | invalid | `unsigned long long`, `unsigned int`, `size_t` | invalid | invalid |
+/// | `%S`, `%C`, `%ws`, `%wc` | invalid | invalid | `%ls`, `%lc` | invalid | invalid |
+/// | `%b`, `%B` | binary | binary | invalid | binary | invalid |
+/// | `%m` | `strerror(errno)` | `strerror(errno)` | invalid | invalid | invalid |
+/// | `%Z` | invalid | invalid | `ANSI_STRING *` | invalid | invalid |
+///
+/// MSVC pointers have as many digits as a pointer has nibbles, so the table
+/// shows a 64-bit target.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Dialect {
+    /// The GNU C library, used by most Linux distributions.
+    #[default]
+    Glibc,
+    /// musl libc, used by Alpine Linux and static Linux binaries.
+    Musl,
+    /// The Microsoft C runtime.
+    ///
+    /// `%S` and `%C` take wide strings and characters, the opposite width of
+    /// `%s` and `%c`, and `%hs` and `%hc` take narrow ones. `%Z` prints the
+    /// counted string of an `ANSI_STRING`; `%wZ` (a `UNICODE_STRING`) isn't
+    /// supported.
+    Msvc,
+    /// newlib's reduced `printf`, used on many embedded targets.
+    NewlibNano,
+    /// The ISO C11 standard, without any extensions.
+    C11,
+}
+
+impl Dialect {
+    /// The non-standard syntax that this dialect accepts.
+    pub(crate) fn extensions(self) -> Extensions {
+        match self {
+            Dialect::Glibc => Extensions::GLIBC,
+            Dialect::Msvc => Extensions::MSVC,
+            _ => Extensions::empty(),
+        }
+    }
+
+    /// Whether C23's `%b` and `%B` are accepted.
+    pub(crate) fn binary(self) -> bool {
+        matches!(self, Dialect::Glibc | Dialect::Musl | Dialect::NewlibNano)
+    }
+}
+
+/// Options for [`format_with_config`][crate::format_with_config].
+///
+/// [`format`][crate::format()] uses the default configuration.
+///
+/// ```rust
+/// use printf_compat::{BytesWrittenPolicy, Config};
+///
+/// const CONFIG: Config = Config::new().bytes_written(BytesWrittenPolicy::Write);
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Config {
+    pub(crate) bytes_written: BytesWrittenPolicy,
+    pub(crate) limits: Option<Limits>,
+    pub(crate) dialect: Dialect,
+    pub(crate) errno: Option<ErrnoSource>,
+    pub(crate) conversions: &'static [Conversion],
+    pub(crate) locale: Locale,
+    #[cfg(feature = "pointer-ext")]
+    pub(crate) pointer_extensions: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where `%m` reads `errno` from.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ErrnoSource(pub(crate) fn() -> c_int);
+
+impl PartialEq for ErrnoSource {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::fn_addr_eq(self.0, other.0)
+    }
+}
+
+impl Eq for ErrnoSource {}
+
+impl Hash for ErrnoSource {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.0 as usize).hash(state);
+    }
+}
+
+/// The current thread's `errno`, as [`std`] sees it.
+#[cfg(all(feature = "std", not(windows)))]
+fn current_errno() -> c_int {
+    std::io::Error::last_os_error().raw_os_error().unwrap_or(0)
+}
+
+/// The current thread's `errno`. On Windows, [`std`]'s last OS error is
+/// `GetLastError`, so the C runtime's is read instead.
+#[cfg(all(feature = "std", windows))]
+fn current_errno() -> c_int {
+    unsafe extern "C" {
+        fn _errno() -> *mut c_int;
+    }
+    // SAFETY: `_errno` returns a pointer to the current thread's `errno`.
+    unsafe { *_errno() }
+}
+
+impl Config {
+    /// The default configuration.
+    pub const fn new() -> Self {
+        Config {
+            bytes_written: BytesWrittenPolicy::Reject,
+            limits: None,
+            dialect: Dialect::Glibc,
+            #[cfg(feature = "std")]
+            errno: Some(ErrnoSource(current_errno)),
+            #[cfg(not(feature = "std"))]
+            errno: None,
+            conversions: &[],
+            locale: Locale::C,
+            #[cfg(feature = "pointer-ext")]
+            pointer_extensions: false,
+        }
+    }
+
+    /// Set what to do with `%n`. Defaults to [`BytesWrittenPolicy::Reject`].
+    pub const fn bytes_written(mut self, policy: BytesWrittenPolicy) -> Self {
+        self.bytes_written = policy;
+        self
+    }
+
+    /// Set the C library to imitate. Defaults to [`Dialect::Glibc`].
+    pub const fn dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Set where `%m` reads `errno` from. It's read once, before anything is
+    /// written, so the handler can't change it.
+    ///
+    /// With the `std` feature, this defaults to the C library's `errno`,
+    /// which on Windows is the C runtime's rather than `GetLastError`.
+    /// Without it, there's no default, and `%m` returns
+    /// [`Error::Forbidden`][crate::Error::Forbidden] until one is set.
+    ///
+    /// ```rust
+    /// use core::ffi::c_int;
+    /// use printf_compat::Config;
+    ///
+    /// fn errno() -> c_int {
+    ///     2
+    /// }
+    ///
+    /// const CONFIG: Config = Config::new().errno(errno);
+    /// ```
+    pub const fn errno(mut self, errno: fn() -> c_int) -> Self {
+        self.errno = Some(ErrnoSource(errno));
+        self
+    }
+
+    /// Add conversions to the parser. They're looked up before the built-in
+    /// ones, so they can also replace them. Any other unknown conversion is
+    /// still an error. See [`Conversion`] for an example.
+    pub const fn conversions(mut self, conversions: &'static [Conversion]) -> Self {
+        self.conversions = conversions;
+        self
+    }
+
+    /// Set the locale that numbers are written in. Defaults to [`Locale::C`].
+    ///
+    /// The parser doesn't use this: it's for the output adapters that
+    /// [`Printf`][crate::Printf] and [`output::display`][crate::output::display]
+    /// make. Pass it to [`output::fmt_write_locale`][crate::output::fmt_write_locale]
+    /// to use it with [`format_with_config`][crate::format_with_config].
+    pub const fn locale(mut self, locale: Locale) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Accept the Linux kernel's `%p` extensions, like `%pI4` and `%pM`,
+    /// which print what the pointer points to. They're passed to the handler
+    /// as [`Specifier::PointerExt`][crate::Specifier::PointerExt]. Defaults
+    /// to `false`.
+    ///
+    /// As in the kernel, all the letters and digits after `%p` are part of
+    /// the conversion, and an unknown suffix prints the plain pointer. A null
+    /// pointer prints `(null)`. The extensions that read memory are forbidden
+    /// by [`Limits::allow_dereference`].
+    ///
+    /// ```rust
+    /// # #![feature(c_variadic)]
+    /// # use core::ffi::{c_char, c_int};
+    /// use printf_compat::{Config, format_with_config, output};
+    ///
+    /// unsafe extern "C" fn printk(str: *const c_char, args: ...) -> c_int {
+    ///     const CONFIG: Config = Config::new().pointer_extensions(true);
+    ///     let mut s = String::new();
+    ///     let written = unsafe { format_with_config(str, args, &CONFIG, output::fmt_write(&mut s)) };
+    ///     println!("{}", s);
+    ///     written
+    /// }
+    /// ```
+    #[cfg(feature = "pointer-ext")]
+    pub const fn pointer_extensions(mut self, enabled: bool) -> Self {
+        self.pointer_extensions = enabled;
+        self
+    }
+
+    /// Treat the format string as untrusted, and enforce `limits` on it.
+    ///
+    /// This also forbids `%n`, regardless of [`bytes_written`][Self::bytes_written].
+    pub const fn limits(mut self, limits: Limits) -> Self {
+        self.limits = Some(limits);
+        self
+    }
+}
+
+/// Caps for formatting untrusted format strings, as used by
+/// [`format_untrusted`][crate::format_untrusted].
+///
+/// A format string that a user controls can ask for a huge amount of output
+/// (`%999999999d`), or read through pointers that it makes up (`%s`). Each
+/// limit makes formatting stop with a distinct [`Error`][crate::Error] as soon
+/// as it's exceeded, before anything is written for that conversion.
+///
+/// ```rust
+/// use printf_compat::Limits;
+///
+/// const LIMITS: Limits = Limits::new().max_output(256).allow_dereference(false);
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Limits {
+    pub(crate) max_width: c_int,
+    pub(crate) max_precision: c_int,
+    pub(crate) max_output: usize,
+    pub(crate) dereference: bool,
+}
+
+impl Limits {
+    /// The default limits: a width or precision of at most 1024, at most
+    /// 64 KiB of output, and dereferencing allowed.
+    pub const fn new() -> Self {
+        Limits {
+            max_width: 1024,
+            max_precision: 1024,
+            max_output: 64 * 1024,
+            dereference: true,
+        }
+    }
+
+    /// Set the largest allowed width, whether it's given in the format string
+    /// or as an argument with `*`.
+    pub const fn max_width(mut self, max: c_int) -> Self {
+        self.max_width = max;
+        self
+    }
+
+    /// Set the largest allowed precision, whether it's given in the format
+    /// string or as an argument with `*`.
+    pub const fn max_precision(mut self, max: c_int) -> Self {
+        self.max_precision = max;
+        self
+    }
+
+    /// Set the largest number of bytes that may be written in total.
+    pub const fn max_output(mut self, max: usize) -> Self {
+        self.max_output = max;
+        self
+    }
+
+    /// Set whether conversions that read through a pointer argument, like
+    /// `%s`, are allowed. A pointer argument can be anything if the format
+    /// string is untrusted, since it can make the argument list be read out
+    /// of step.
+    pub const fn allow_dereference(mut self, allow: bool) -> Self {
+        self.dereference = allow;
+        self
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
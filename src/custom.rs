@@ -0,0 +1,116 @@
+//! Conversions added by the user, like glibc's `register_printf_specifier`.
+
+use core::ffi::c_int;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+
+use crate::{Flags, Promoted};
+
+/// The C type that a [`Conversion`] reads from the arguments.
+///
+/// The integer types are read with the conversion's length modifier, like
+/// `%d` and `%u`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum ArgType {
+    /// A signed integer, passed as [`Promoted::Int`].
+    Int,
+    /// An unsigned integer, passed as [`Promoted::Uint`].
+    Uint,
+    /// A `double`, passed as [`Promoted::Double`].
+    Double,
+    /// A `char *` string, passed as [`Promoted::String`].
+    String,
+    /// A pointer, passed as [`Promoted::Pointer`].
+    Pointer,
+}
+
+/// Renders the value of a [`Conversion`], given its flags and precision. The
+/// built-in output adapters pad the result to the width.
+pub type Render = fn(&mut dyn fmt::Write, Promoted<'_>, Flags, Option<c_int>) -> fmt::Result;
+
+/// A conversion character added to the parser with
+/// [`Config::conversions`][crate::Config::conversions].
+///
+/// ```rust
+/// # #![feature(c_variadic)]
+/// use core::ffi::{c_char, c_int};
+/// use core::fmt;
+/// use printf_compat::argument::Flags;
+/// use printf_compat::{ArgType, Config, Conversion, Promoted, format_with_config, output};
+///
+/// /// `%Q`: a quoted string.
+/// fn quote(w: &mut dyn fmt::Write, value: Promoted, _: Flags, _: Option<c_int>) -> fmt::Result {
+///     match value {
+///         Promoted::String(Some(s)) => write!(w, "{:?}", s),
+///         _ => w.write_str("NULL"),
+///     }
+/// }
+///
+/// const CONFIG: Config = Config::new().conversions(&[Conversion::new(b'Q', ArgType::String, quote)]);
+///
+/// unsafe extern "C" fn log(str: *const c_char, args: ...) -> c_int {
+///     let mut s = String::new();
+///     let written = unsafe { format_with_config(str, args, &CONFIG, output::fmt_write(&mut s)) };
+///     println!("{}", s);
+///     written
+/// }
+/// ```
+#[derive(Debug, Copy, Clone)]
+pub struct Conversion {
+    conversion: u8,
+    arg: ArgType,
+    render: Render,
+}
+
+impl Conversion {
+    /// A conversion for the character `conversion` that reads an argument of
+    /// type `arg`, and prints it with `render`.
+    pub const fn new(conversion: u8, arg: ArgType, render: Render) -> Self {
+        Conversion {
+            conversion,
+            arg,
+            render,
+        }
+    }
+
+    /// The conversion character.
+    pub fn conversion(&self) -> u8 {
+        self.conversion
+    }
+
+    /// The type of argument that it reads.
+    pub fn arg_type(&self) -> ArgType {
+        self.arg
+    }
+
+    /// Render `value`, for handlers that don't use the built-in output
+    /// adapters.
+    pub fn render(
+        &self,
+        w: &mut dyn fmt::Write,
+        value: Promoted<'_>,
+        flags: Flags,
+        precision: Option<c_int>,
+    ) -> fmt::Result {
+        (self.render)(w, value, flags, precision)
+    }
+}
+
+impl PartialEq for Conversion {
+    fn eq(&self, other: &Self) -> bool {
+        self.conversion == other.conversion
+            && self.arg == other.arg
+            && core::ptr::fn_addr_eq(self.render, other.render)
+    }
+}
+
+impl Eq for Conversion {}
+
+impl Hash for Conversion {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.conversion.hash(state);
+        self.arg.hash(state);
+        (self.render as usize).hash(state);
+    }
+}
@@ -0,0 +1,69 @@
+use core::fmt;
+
+/// The reason formatting failed, as returned by
+/// [`format_untrusted`][crate::format_untrusted].
+///
+/// The functions that return a C-style `c_int` report all of these as `-1`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Error {
+    /// The format string is invalid, or uses an unknown conversion.
+    InvalidFormat,
+    /// The handler returned an error.
+    Handler,
+    /// The conversion is not allowed by the [`Config`][crate::Config], like
+    /// `%n` by default, or `%m` without an errno source. Contains the
+    /// conversion character.
+    Forbidden(u8),
+    /// `%n` was given a null pointer, or `%s` was with
+    /// [`Dialect::C11`][crate::Dialect::C11].
+    NullPointer,
+    /// A width was larger than [`Limits::max_width`][crate::Limits::max_width].
+    WidthLimit,
+    /// A precision was larger than
+    /// [`Limits::max_precision`][crate::Limits::max_precision].
+    PrecisionLimit,
+    /// More bytes would be written than
+    /// [`Limits::max_output`][crate::Limits::max_output] allows.
+    OutputLimit,
+    /// There were fewer arguments than the format string needs. Only returned
+    /// when the arguments are a slice.
+    MissingArgument,
+    /// An argument's type doesn't match its conversion. Only returned when the
+    /// arguments are a slice.
+    ArgumentType,
+    /// The format string has more pieces than a
+    /// [`CompiledFormat`][crate::CompiledFormat] can hold.
+    Capacity,
+    /// A fixed-size output buffer is full, like a
+    /// [`binary::Encoder`][crate::output::binary::Encoder]'s.
+    BufferFull,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidFormat => f.write_str("invalid format string"),
+            Error::Handler => f.write_str("the output handler returned an error"),
+            Error::Forbidden(ch) => write!(f, "the conversion `%{}` is forbidden", *ch as char),
+            Error::NullPointer => f.write_str("null pointer passed to `%n` or `%s`"),
+            Error::WidthLimit => f.write_str("width limit exceeded"),
+            Error::PrecisionLimit => f.write_str("precision limit exceeded"),
+            Error::OutputLimit => f.write_str("output limit exceeded"),
+            Error::MissingArgument => f.write_str("not enough arguments for the format string"),
+            Error::ArgumentType => f.write_str("an argument doesn't match its conversion"),
+            Error::Capacity => f.write_str("the format string has too many pieces to compile"),
+            Error::BufferFull => f.write_str("the output buffer is full"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {}
+
+#[cfg(feature = "std")]
+impl From<Error> for std::io::Error {
+    fn from(e: Error) -> Self {
+        std::io::Error::other(e)
+    }
+}
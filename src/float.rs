@@ -0,0 +1,392 @@
+//! Exact decimal formatting of floating point numbers.
+//!
+//! glibc prints the exact decimal expansion of a `double`, no matter how many
+//! digits are requested: `printf("%.20f", 0.1)` prints
+//! `0.10000000000000000555`. The functions in this module do the same, using
+//! a small fixed-size big integer so that they work without `alloc`. Exact
+//! ties are rounded to even, like glibc in its default rounding mode.
+//!
+//! Only the magnitude of the number is written: the sign, padding, and
+//! non-finite values are up to the caller. The output is always ASCII, so any
+//! byte-level sink can be used by wrapping it in a [`fmt::Write`]
+//! implementation.
+
+use core::fmt;
+
+/// Enough 32-bit words to hold `f64::MAX` as an integer (1024 bits), or the
+/// fractional part of the smallest subnormal (1074 bits) multiplied by 10.
+const WORDS: usize = 36;
+
+/// The maximum number of decimal digits in the integer part of an `f64`.
+const MAX_INT_DIGITS: usize = 309;
+
+/// A fixed-capacity unsigned big integer, stored as little-endian words.
+#[derive(Clone, Copy)]
+struct Big {
+    words: [u32; WORDS],
+    len: usize,
+}
+
+impl Big {
+    fn from_u64(value: u64) -> Self {
+        let mut big = Big {
+            words: [0; WORDS],
+            len: 2,
+        };
+        big.words[0] = value as u32;
+        big.words[1] = (value >> 32) as u32;
+        big.trim();
+        big
+    }
+
+    fn trim(&mut self) {
+        while self.len > 0 && self.words[self.len - 1] == 0 {
+            self.len -= 1;
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.len == 0
+    }
+
+    fn shl(&mut self, bits: usize) {
+        let (words, bits) = (bits / 32, bits % 32);
+        if self.is_zero() {
+            return;
+        }
+        let mut i = self.len + words + 1;
+        self.len = i.min(WORDS);
+        while i > words {
+            i -= 1;
+            let hi = self.get(i - words);
+            let lo = if i > words {
+                self.get(i - words - 1)
+            } else {
+                0
+            };
+            let word = if bits == 0 {
+                hi
+            } else {
+                (hi << bits) | (lo >> (32 - bits))
+            };
+            if let Some(w) = self.words.get_mut(i) {
+                *w = word;
+            }
+        }
+        for w in self.words.iter_mut().take(words) {
+            *w = 0;
+        }
+        self.trim();
+    }
+
+    fn get(&self, i: usize) -> u32 {
+        self.words.get(i).copied().unwrap_or(0)
+    }
+
+    fn mul_small(&mut self, factor: u32) {
+        let mut carry = 0u64;
+        for w in self.words.iter_mut().take(self.len) {
+            let v = *w as u64 * factor as u64 + carry;
+            *w = v as u32;
+            carry = v >> 32;
+        }
+        if carry != 0
+            && let Some(w) = self.words.get_mut(self.len)
+        {
+            *w = carry as u32;
+            self.len += 1;
+        }
+    }
+
+    /// Divide in place, returning the remainder.
+    fn div_small(&mut self, divisor: u32) -> u32 {
+        let mut rem = 0u64;
+        for w in self.words.iter_mut().take(self.len).rev() {
+            let v = (rem << 32) | *w as u64;
+            *w = (v / divisor as u64) as u32;
+            rem = v % divisor as u64;
+        }
+        self.trim();
+        rem as u32
+    }
+
+    fn bit(&self, i: usize) -> bool {
+        self.get(i / 32) & (1 << (i % 32)) != 0
+    }
+
+    /// Whether any bit below `i` is set.
+    fn any_below(&self, i: usize) -> bool {
+        let (words, bits) = (i / 32, i % 32);
+        self.words.iter().take(words).any(|&w| w != 0) || self.get(words) & ((1 << bits) - 1) != 0
+    }
+
+    /// Remove and return the bits at and above `i`, which must fit in a `u32`.
+    fn split_high(&mut self, i: usize) -> u32 {
+        let (words, bits) = (i / 32, i % 32);
+        let joined = (self.get(words + 1) as u64) << 32 | self.get(words) as u64;
+        let high = (joined >> bits) as u32;
+        if let Some(w) = self.words.get_mut(words) {
+            *w &= (1 << bits) - 1;
+        }
+        for w in self.words.iter_mut().skip(words + 1) {
+            *w = 0;
+        }
+        self.len = self.len.min(words + 1);
+        self.trim();
+        high
+    }
+}
+
+/// Produces the exact decimal digits of a finite, non-negative `f64`: first
+/// the digits of the integer part (without leading zeros), then an endless
+/// stream of fractional digits.
+#[derive(Clone, Copy)]
+struct Digits {
+    int: [u8; MAX_INT_DIGITS],
+    int_len: usize,
+    int_pos: usize,
+    /// The fractional part is `frac / 2^frac_bits`.
+    frac: Big,
+    frac_bits: usize,
+}
+
+impl Digits {
+    fn new(value: f64) -> Self {
+        let bits = value.to_bits();
+        let exp = ((bits >> 52) & 0x7ff) as i32;
+        let mut mantissa = bits & ((1 << 52) - 1);
+        let mut exp = if exp == 0 {
+            -1074
+        } else {
+            mantissa |= 1 << 52;
+            exp - 1075
+        };
+        while mantissa != 0 && mantissa & 1 == 0 {
+            mantissa >>= 1;
+            exp += 1;
+        }
+
+        let (mut int, frac, frac_bits) = if exp >= 0 {
+            let mut int = Big::from_u64(mantissa);
+            int.shl(exp as usize);
+            (int, Big::from_u64(0), 0)
+        } else {
+            let frac_bits = -exp as usize;
+            if frac_bits >= 64 {
+                (Big::from_u64(0), Big::from_u64(mantissa), frac_bits)
+            } else {
+                (
+                    Big::from_u64(mantissa >> frac_bits),
+                    Big::from_u64(mantissa & ((1 << frac_bits) - 1)),
+                    frac_bits,
+                )
+            }
+        };
+
+        let mut digits = [0; MAX_INT_DIGITS];
+        let mut start = MAX_INT_DIGITS;
+        while !int.is_zero() {
+            let mut chunk = int.div_small(1_000_000_000);
+            for _ in 0..9 {
+                if start == 0 {
+                    break;
+                }
+                start -= 1;
+                digits[start] = (chunk % 10) as u8;
+                chunk /= 10;
+            }
+        }
+        while start < MAX_INT_DIGITS && digits[start] == 0 {
+            start += 1;
+        }
+        digits.copy_within(start.., 0);
+
+        Digits {
+            int: digits,
+            int_len: MAX_INT_DIGITS - start,
+            int_pos: 0,
+            frac,
+            frac_bits,
+        }
+    }
+
+    fn next_digit(&mut self) -> u8 {
+        if self.int_pos < self.int_len {
+            self.int_pos += 1;
+            return self.int.get(self.int_pos - 1).copied().unwrap_or(0);
+        }
+        if self.frac.is_zero() {
+            return 0;
+        }
+        self.frac.mul_small(10);
+        self.frac.split_high(self.frac_bits) as u8
+    }
+
+    /// Whether the digits produced so far should be rounded up, given whether
+    /// the last of them was odd.
+    fn round_up(&self, odd: bool) -> bool {
+        let (next, rest) = if self.int_pos < self.int_len {
+            let rest = self.int.get(self.int_pos + 1..self.int_len).unwrap_or(&[]);
+            let rest = rest.iter().any(|&d| d != 0);
+            let next = self.int.get(self.int_pos).copied().unwrap_or(0);
+            (next, rest || !self.frac.is_zero())
+        } else if self.frac.is_zero() {
+            return false;
+        } else {
+            let half = self.frac_bits - 1;
+            match (self.frac.bit(half), self.frac.any_below(half)) {
+                (false, _) => return false,
+                (true, rest) => (5, rest),
+            }
+        };
+        next > 5 || (next == 5 && (rest || odd))
+    }
+}
+
+/// The result of rounding a digit stream to a fixed number of digits.
+struct Rounded {
+    digits: Digits,
+    count: usize,
+    round_up: bool,
+    /// The index of the digit that is incremented when rounding up. All
+    /// following digits become zero. If `None`, every digit was a 9 and a
+    /// leading 1 has to be inserted.
+    last_non_nine: Option<usize>,
+}
+
+impl Rounded {
+    fn new(digits: Digits, count: usize) -> Self {
+        let mut scan = digits;
+        let mut last_non_nine = None;
+        let mut last = 0;
+        for i in 0..count {
+            last = scan.next_digit();
+            if last != 9 {
+                last_non_nine = Some(i);
+            }
+        }
+        Rounded {
+            digits,
+            count,
+            round_up: scan.round_up(last % 2 == 1),
+            last_non_nine,
+        }
+    }
+
+    /// Whether rounding up carries into a new leading digit.
+    fn carries_out(&self) -> bool {
+        self.round_up && self.last_non_nine.is_none()
+    }
+
+    /// Write the rounded digits, with a decimal point after the digit at
+    /// index `point` (if any).
+    fn write(&self, w: &mut (impl fmt::Write + ?Sized), point: Option<usize>) -> fmt::Result {
+        let mut digits = self.digits;
+        for i in 0..self.count {
+            let mut d = digits.next_digit();
+            if self.round_up {
+                match self.last_non_nine {
+                    Some(j) if i == j => d += 1,
+                    Some(j) if i < j => {}
+                    _ => d = 0,
+                }
+            }
+            w.write_char((b'0' + d) as char)?;
+            if point == Some(i) {
+                w.write_char('.')?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write `value` as `[d]ddd.ddd`, with `precision` digits after the decimal
+/// point, like `%f`.
+///
+/// If `alternate` is set, the decimal point is written even if `precision` is
+/// 0. Only the magnitude of `value` is written, and an error is returned if it
+/// isn't finite.
+pub fn write_fixed(
+    w: &mut (impl fmt::Write + ?Sized),
+    value: f64,
+    precision: usize,
+    alternate: bool,
+) -> fmt::Result {
+    if !value.is_finite() {
+        return Err(fmt::Error);
+    }
+    let digits = Digits::new(value.abs());
+    let int_len = digits.int_len;
+    let rounded = Rounded::new(digits, int_len + precision);
+
+    if rounded.carries_out() {
+        w.write_char('1')?;
+    } else if int_len == 0 {
+        w.write_char('0')?;
+    }
+    if int_len == 0 && (precision > 0 || alternate) {
+        w.write_char('.')?;
+    }
+    let point = match int_len {
+        0 => None,
+        _ if precision > 0 || alternate => Some(int_len - 1),
+        _ => None,
+    };
+    rounded.write(w, point)
+}
+
+/// Write `value` as `d.ddde±dd`, with `precision` digits after the decimal
+/// point, like `%e`.
+///
+/// If `alternate` is set, the decimal point is written even if `precision` is
+/// 0. If `upper` is set, the exponent is introduced by `E` instead of `e`. The
+/// exponent is padded with zeros to `exponent_digits` digits; C uses 2.
+/// Only the magnitude of `value` is written, and an error is returned if it
+/// isn't finite.
+pub fn write_scientific(
+    w: &mut (impl fmt::Write + ?Sized),
+    value: f64,
+    precision: usize,
+    upper: bool,
+    alternate: bool,
+    exponent_digits: usize,
+) -> fmt::Result {
+    if !value.is_finite() {
+        return Err(fmt::Error);
+    }
+    let mut digits = Digits::new(value.abs());
+    let mut exponent = digits.int_len as i32 - 1;
+    if value != 0.0 && digits.int_len == 0 {
+        // Skip the leading zeros of the fractional part.
+        loop {
+            let mut peek = digits;
+            if peek.next_digit() != 0 {
+                break;
+            }
+            digits = peek;
+            exponent -= 1;
+        }
+    } else if value == 0.0 {
+        exponent = 0;
+    }
+
+    let rounded = Rounded::new(digits, precision + 1);
+    let point = (precision > 0 || alternate).then_some(0);
+    if rounded.carries_out() {
+        // All digits were 9s, so the result is 1.000...
+        exponent += 1;
+        w.write_char('1')?;
+        if point.is_some() {
+            w.write_char('.')?;
+        }
+        for _ in 0..precision {
+            w.write_char('0')?;
+        }
+    } else {
+        rounded.write(w, point)?;
+    }
+
+    w.write_char(if upper { 'E' } else { 'e' })?;
+    w.write_char(if exponent < 0 { '-' } else { '+' })?;
+    write!(w, "{:01$}", exponent.unsigned_abs(), exponent_digits)
+}
@@ -26,13 +26,24 @@
 //! printf-compat lets you pick how you want to output a message. Use
 //! pre-written adapters for [`fmt::Write`][output::fmt_write] (like a
 //! [`String`]) or [`io::Write`][output::io_write] (like
-//! [`io::stdout()`][std::io::stdout]), or implement your own.
+//! [`io::stdout()`][std::io::stdout]), or implement your own
+//! [`Sink`][output::Sink], which is told when a message is finished.
 //!
 //! ## 🔬 Small
 //!
 //! This crate is `no_std` compatible (with `default-features = false`).
 //! The main machinery doesn't require the use of [`core::fmt`], and it can't panic.
 //!
+//! Conversions that you don't need can be compiled out: disable the `float`,
+//! `hexfloat`, `n-specifier`, `m-specifier` or `wide` cargo features to
+//! remove support for `%f` (and friends), `%a`, `%n`, `%m` or `%ls` and `%lc`.
+//! Those conversions are then passed to your handler as
+//! [`Specifier::Disabled`].
+//!
+//! Numbered arguments, like `%2$s %1$d`, need the `positional` feature, which
+//! handles up to 32 of them. Without it, a format string that numbers its
+//! arguments is invalid.
+//!
 //! ## 🔒 Safe (as can be)
 //!
 //! Of course, `printf` is *completely* unsafe, as it requires the use of
@@ -41,9 +52,14 @@
 //!
 //! The `n` format specifier, which writes to a user-provided pointer, is
 //! considered a serious security vulnerability if a user-provided string is
-//! ever passed to `printf`. It *is* supported by this crate; however, it
-//! doesn't do anything by default, and you'll have to explicitly do the writing
-//! yourself.
+//! ever passed to `printf`. It *is* supported by this crate; however, it is
+//! rejected by default, and you'll have to explicitly opt in with
+//! [`BytesWrittenPolicy::Write`].
+//!
+//! If you're calling into Rust code that formats C-style strings, the
+//! `printf!` macro (behind the `macros` cargo feature) parses the format
+//! string at compile time and checks each argument's type against its
+//! conversion, so there's no `va_list` involved at all.
 //!
 //! ## 🧹 Tested
 //!
@@ -51,6 +67,14 @@
 //! identical to glibc's `printf`. [Differences are
 //! documented][output::fmt_write#differences].
 //!
+//! Other C libraries differ from glibc in small ways, like how `%p` prints a null
+//! pointer; pick one with [`Dialect`] to match its output instead. A [`Locale`]
+//! sets the decimal point, and the thousands separator that the `'` flag, as
+//! in `%'d`, groups digits with.
+//! Code ported from the Linux kernel can turn on the `pointer-ext` cargo
+//! feature and enable `pointer_extensions` on a [`Config`] to print `printk`'s
+//! `%pI4`, `%pM`, `%pU`, `%ph` and `%pe`.
+//!
 //! # Getting Started
 //!
 //! Start by adding the unstable feature:
@@ -98,11 +122,29 @@
 //! ```
 //!
 //! Of course, replace [`output::fmt_write`] with whatever you like—some are
-//! provided for you in [`output`]. If you'd like to write your own, follow
-//! their function signature: you need to provide a function to [`format()`]
-//! that takes an [`Argument`] and returns the number of bytes written (although
-//! you don't *need* to if your C library doesn't use it) or -1 if there was an
-//! error.
+//! provided for you in [`output`]. If you'd like to write your own, you can pass
+//! [`format()`] a closure that takes an [`Argument`] and returns the number of
+//! bytes written (although you don't *need* to if your C library doesn't use
+//! it) or -1 if there was an error. For typed errors, or to know when a message
+//! is finished, implement [`output::Sink`] and use [`format_sink`] instead.
+//! To forward a C library's log callback to the `log` crate, turn on the `log`
+//! cargo feature and use a `log::Logger`.
+//!
+//! You can also format Rust values with a C format string, without a
+//! `va_list`: [`sprintf`] and [`format_slice`] take a slice of [`PrintfArg`]s,
+//! and check each one against its conversion.
+//!
+//! If you always format with the same options, set up a [`Printf`] once,
+//! possibly as a `const`, and call its methods instead of the free functions.
+//! If you format the same format string many times, parse it once into a
+//! [`CompiledFormat`]. To format a message later, possibly on another thread,
+//! [`capture`] its arguments into a [`Record`].
+//! To send it somewhere else to be formatted, like over a slow serial link,
+//! [`output::binary`] encodes just the arguments and an ID for the format string,
+//! and the `printf-compat-decode` command turns those back into text.
+//!
+//! To go the other way, [`scan::vsscanf`] parses input like C's `sscanf`. Wide
+//! format strings, as used by `swprintf`, are handled by [`format_wide`].
 //!
 //! [sigrok-log]: https://sigrok.org/api/libsigrok/unstable/a00063.html#ga4240b8fe79be72ef758f40f9acbd4316
 //! [libusb-log]: http://libusb.sourceforge.net/api-1.0/group__libusb__lib.html#ga2efb66b8f16ffb0851f3907794c06e20
@@ -115,55 +157,63 @@
 
 use core::{ffi::*, fmt};
 
+mod compiled;
+mod config;
+mod custom;
+#[cfg(feature = "m-specifier")]
+mod errno;
+mod error;
+#[cfg(feature = "float")]
+pub mod float;
+mod locale;
+#[cfg(feature = "log")]
+pub mod log;
+#[cfg(feature = "macros")]
+mod macros;
 pub mod output;
 mod parser;
+#[cfg(feature = "pointer-ext")]
+mod pointer_ext;
+mod printf;
+mod printf_arg;
+#[cfg(feature = "std")]
+mod record;
+pub mod scan;
 use argument::*;
-pub use parser::format;
+pub use compiled::CompiledFormat;
+pub use config::{BytesWrittenPolicy, Config, Dialect, Limits};
+pub use custom::{ArgType, Conversion, Render};
+#[cfg(feature = "m-specifier")]
+pub use errno::strerror;
+pub use error::Error;
+pub use locale::Locale;
+#[cfg(feature = "macros")]
+#[doc(hidden)]
+pub use macros::__private;
+#[cfg(feature = "macros")]
+pub use macros::{Arguments, printf};
+#[cfg(feature = "wide")]
+pub use parser::format_wide;
+#[cfg(feature = "std")]
+pub use parser::sprintf;
+pub use parser::{format, format_sink, format_slice, format_untrusted, format_with_config};
+pub use printf::Printf;
+pub use printf_arg::{PrintfArg, Promoted};
+#[cfg(feature = "std")]
+pub use record::{Record, capture};
 pub mod argument {
     use super::*;
 
-    bitflags::bitflags! {
-        /// Flags field.
-        ///
-        /// Definitions from
-        /// [Wikipedia](https://en.wikipedia.org/wiki/Printf_format_string#Flags_field).
-        #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
-        pub struct Flags: u8 {
-            /// Left-align the output of this placeholder. (The default is to
-            /// right-align the output.)
-            const LEFT_ALIGN = 0b00000001;
-            /// Prepends a plus for positive signed-numeric types. positive =
-            /// `+`, negative = `-`.
-            ///
-            /// (The default doesn't prepend anything in front of positive
-            /// numbers.)
-            const PREPEND_PLUS = 0b00000010;
-            /// Prepends a space for positive signed-numeric types. positive = `
-            /// `, negative = `-`. This flag is ignored if the
-            /// [`PREPEND_PLUS`][Flags::PREPEND_PLUS] flag exists.
-            ///
-            /// (The default doesn't prepend anything in front of positive
-            /// numbers.)
-            const PREPEND_SPACE = 0b00000100;
-            /// When the 'width' option is specified, prepends zeros for numeric
-            /// types. (The default prepends spaces.)
-            ///
-            /// For example, `printf("%4X",3)` produces `   3`, while
-            /// `printf("%04X",3)` produces `0003`.
-            const PREPEND_ZERO = 0b00001000;
-            /// The integer or exponent of a decimal has the thousands grouping
-            /// separator applied.
-            const THOUSANDS_GROUPING = 0b00010000;
-            /// Alternate form:
-            ///
-            /// For `g` and `G` types, trailing zeros are not removed. \
-            /// For `f`, `F`, `e`, `E`, `g`, `G` types, the output always
-            /// contains a decimal point. \ For `o`, `x`, `X` types,
-            /// the text `0`, `0x`, `0X`, respectively, is prepended
-            /// to non-zero numbers.
-            const ALTERNATE_FORM = 0b00100000;
-        }
-    }
+    #[cfg(feature = "pointer-ext")]
+    pub use crate::pointer_ext::{Ipv6Format, PointerExt};
+    pub use printf_compat_spec::Flags;
+
+    /// C's `wchar_t`: UTF-16 on Windows, and UTF-32 everywhere else.
+    #[cfg(windows)]
+    pub type WChar = u16;
+    /// C's `wchar_t`: UTF-16 on Windows, and UTF-32 everywhere else.
+    #[cfg(not(windows))]
+    pub type WChar = u32;
 
     #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
     pub enum DoubleFormat {
@@ -381,7 +431,8 @@ pub mod argument {
     #[derive(Debug, Copy, Clone, PartialEq)]
     #[non_exhaustive]
     pub enum Specifier<'a> {
-        /// `%`
+        /// `%`, when it has flags or a width, like `%-5%`. A plain `%%` is
+        /// passed to the handler as part of the text around it.
         Percent,
         /// `d`, `i`
         Int(SignedInt),
@@ -401,19 +452,58 @@ pub mod argument {
         String(&'a CStr),
         /// `c`
         Char(c_char),
+        /// string outside of formatting, in a wide format string
+        WideText(&'a [WChar]),
+        /// `ls`, without the null terminator
+        WideString(&'a [WChar]),
+        /// `lc`
+        WideChar(WChar),
         /// `x`
         Hex(UnsignedInt),
         /// `X`
         UpperHex(UnsignedInt),
+        /// `b`
+        Binary(UnsignedInt),
+        /// `B`
+        UpperBinary(UnsignedInt),
         /// `p`
         Pointer(*const ()),
+        /// A conversion added with
+        /// [`Config::conversions`][crate::Config::conversions], and the
+        /// argument that it read.
+        Custom {
+            conversion: Conversion,
+            value: Promoted<'a>,
+        },
+        /// `p` followed by one of the Linux kernel's extensions, like `pI4`
+        /// or `pM`. Only produced with
+        /// [`Config::pointer_extensions`][crate::Config::pointer_extensions].
+        #[cfg(feature = "pointer-ext")]
+        PointerExt(PointerExt<'a>),
+        /// `m`: the value of `errno` when formatting started, which is printed
+        /// as `strerror` does.
+        Errno(c_int),
         /// `n`
         ///
+        /// This is only passed to the handler with
+        /// [`BytesWrittenPolicy::Handler`]. The pointer is always cast to
+        /// `*const c_int`, regardless of the length modifier.
+        ///
         /// # Safety
         ///
         /// This can be a serious security vulnerability if the format specifier
         /// of `printf` is allowed to be user-specified. This shouldn't ever
         /// happen, but poorly-written software may do so.
         WriteBytesWritten(c_int, *const c_int),
+        /// A conversion that was recognized, but whose support was removed by
+        /// disabling the `float`, `hexfloat`, `n-specifier`, `m-specifier` or
+        /// `wide` cargo feature.
+        /// Contains the conversion character.
+        ///
+        /// The argument of the conversion has already been consumed, so the
+        /// rest of the format string can still be printed. Use
+        /// [`output::placeholder`] to print something in its place instead of
+        /// returning an error.
+        Disabled(u8),
     }
 }
@@ -0,0 +1,100 @@
+//! How numbers are written in different languages.
+
+use core::ffi::c_char;
+
+/// The numeric conventions of a locale, like C's `LC_NUMERIC`.
+///
+/// The output adapters only use a locale if they're given one, with
+/// [`output::fmt_write_locale`][crate::output::fmt_write_locale] or
+/// [`Config::locale`][crate::Config::locale]. The floating point
+/// conversions then write its decimal point, and conversions with the `'`
+/// flag, like `%'d`, group digits with its thousands separator. The
+/// default, [`Locale::C`], writes `.` and doesn't group digits at all.
+///
+/// ```rust
+/// use printf_compat::{Printf, Locale};
+///
+/// const PRINTF: Printf = Printf::new().locale(Locale::EN_US);
+///
+/// assert_eq!(PRINTF.sprintf(c"%'d", &[&1234567]).unwrap(), "1,234,567");
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Locale {
+    decimal_point: &'static str,
+    thousands_sep: &'static str,
+    grouping: &'static [u8],
+}
+
+impl Locale {
+    /// The `C` and `POSIX` locales, which have no thousands separator.
+    pub const C: Locale = Locale::new(".", "", &[]);
+    /// American English, `en_US`: `1,234,567.89`.
+    pub const EN_US: Locale = Locale::new(".", ",", &[3]);
+    /// German, `de_DE`: `1.234.567,89`.
+    pub const DE_DE: Locale = Locale::new(",", ".", &[3]);
+    /// French, `fr_FR`, which separates thousands with a narrow no-break
+    /// space (U+202F), like glibc: `1 234 567,89`.
+    pub const FR_FR: Locale = Locale::new(",", "\u{202f}", &[3]);
+
+    /// Describe a locale.
+    ///
+    /// `grouping` works like `grouping` in C's `struct lconv`: it's the
+    /// number of digits in each group, starting from the decimal point, and
+    /// the last one repeats. A size of 0, or of [`c_char::MAX`] or more,
+    /// leaves the rest of the digits in one group. `&[3, 2]` groups
+    /// 123456789 as `12,34,56,789`.
+    pub const fn new(
+        decimal_point: &'static str,
+        thousands_sep: &'static str,
+        grouping: &'static [u8],
+    ) -> Self {
+        Locale {
+            decimal_point,
+            thousands_sep,
+            grouping,
+        }
+    }
+
+    /// The character between the integer and fractional parts of a number.
+    pub const fn decimal_point(&self) -> &'static str {
+        self.decimal_point
+    }
+
+    /// The separator between groups of digits.
+    pub const fn thousands_sep(&self) -> &'static str {
+        self.thousands_sep
+    }
+
+    /// The sizes of the groups of digits. See [`new`][Self::new].
+    pub const fn grouping(&self) -> &'static [u8] {
+        self.grouping
+    }
+
+    /// Whether a separator goes before the last `digits` digits of the
+    /// integer part of a number.
+    pub(crate) fn separates(&self, digits: usize) -> bool {
+        let Some(&last) = self.grouping.last() else {
+            return false;
+        };
+        if self.thousands_sep.is_empty() {
+            return false;
+        }
+        let mut total = 0;
+        for &size in self.grouping.iter().chain(core::iter::repeat(&last)) {
+            if size == 0 || size >= c_char::MAX as u8 {
+                return false;
+            }
+            total += usize::from(size);
+            if total >= digits {
+                return total == digits;
+            }
+        }
+        false
+    }
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self::C
+    }
+}
@@ -0,0 +1,170 @@
+//! Forwarding a C library's log messages to the [`log`] crate.
+//!
+//! C libraries usually take a callback for their log messages, with a
+//! level, a format string and its arguments. A [`Logger`] formats them
+//! straight into a [`log::Record`], without allocating.
+//!
+//! ```rust
+//! #![feature(c_variadic)]
+//!
+//! use core::ffi::{c_char, c_int};
+//! use printf_compat::log::Logger;
+//!
+//! const LOGGER: Logger = Logger::new("libfoo");
+//!
+//! #[unsafe(no_mangle)]
+//! unsafe extern "C" fn foo_log_callback(level: c_int, str: *const c_char, args: ...) {
+//!     unsafe { LOGGER.log(level, str, args) };
+//! }
+//! ```
+
+use core::ffi::*;
+use core::fmt;
+
+use ::log::{Level, Metadata, Record};
+
+use crate::Config;
+use crate::output::display_with_config;
+
+/// syslog's levels, from `LOG_EMERG` to `LOG_DEBUG`, which many C libraries
+/// use.
+pub const SYSLOG_LEVELS: &[(c_int, Level)] = &[
+    (0, Level::Error),
+    (1, Level::Error),
+    (2, Level::Error),
+    (3, Level::Error),
+    (4, Level::Warn),
+    (5, Level::Info),
+    (6, Level::Info),
+    (7, Level::Debug),
+];
+
+/// Logs a C library's messages with the [`log`] crate.
+///
+/// Each message is logged with the library's name as its target, at the
+/// [`Level`] that its C level maps to, and without its trailing newline.
+#[derive(Debug, Copy, Clone)]
+pub struct Logger {
+    target: &'static str,
+    levels: &'static [(c_int, Level)],
+    default_level: Level,
+    config: Config,
+}
+
+impl Logger {
+    /// Log messages from the library called `target`, with
+    /// [`SYSLOG_LEVELS`].
+    pub const fn new(target: &'static str) -> Self {
+        Logger {
+            target,
+            levels: SYSLOG_LEVELS,
+            default_level: Level::Info,
+            config: Config::new(),
+        }
+    }
+
+    /// Map the library's levels to [`Level`]s with a table of pairs.
+    pub const fn levels(mut self, levels: &'static [(c_int, Level)]) -> Self {
+        self.levels = levels;
+        self
+    }
+
+    /// The [`Level`] of a level that isn't in the table. The default is
+    /// [`Level::Info`].
+    pub const fn default_level(mut self, level: Level) -> Self {
+        self.default_level = level;
+        self
+    }
+
+    /// Format messages with a [`Config`], whose dialect and locale are also
+    /// used for the output.
+    pub const fn config(mut self, config: Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// The [`Level`] that a C level maps to.
+    pub fn level(&self, level: c_int) -> Level {
+        self.levels
+            .iter()
+            .find(|(c, _)| *c == level)
+            .map_or(self.default_level, |(_, level)| *level)
+    }
+
+    /// Format a message and log it. Nothing is formatted if the level is
+    /// disabled.
+    ///
+    /// If the message can't be formatted, the part that was formatted before
+    /// the error is logged.
+    ///
+    /// # Safety
+    ///
+    /// [`VaList`]s are *very* unsafe. The passed `format` and `args` parameter must be a valid [`printf` format string](http://www.cplusplus.com/reference/cstdio/printf/).
+    pub unsafe fn log(&self, level: c_int, format: *const c_char, args: VaList) {
+        let level = self.level(level);
+        let metadata = Metadata::builder().level(level).target(self.target).build();
+        let logger = ::log::logger();
+        if level > ::log::max_level() || !logger.enabled(&metadata) {
+            return;
+        }
+        let message = unsafe { display_with_config(format, args, self.config) };
+        logger.log(
+            &Record::builder()
+                .metadata(metadata)
+                .args(format_args!("{}", TrimNewline(&message)))
+                .build(),
+        );
+    }
+}
+
+/// Writes a message without one trailing newline.
+struct TrimNewline<T>(T);
+
+impl<T: fmt::Display> fmt::Display for TrimNewline<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut w = Trimmer {
+            f,
+            newline: false,
+            failed: false,
+        };
+        // A formatting error is ignored, since a logger can't handle it, but
+        // an error from the logger's output isn't.
+        let _ = fmt::write(&mut w, format_args!("{}", self.0));
+        if w.failed { Err(fmt::Error) } else { Ok(()) }
+    }
+}
+
+struct Trimmer<'a, 'b> {
+    f: &'a mut fmt::Formatter<'b>,
+    /// Whether the last piece ended with a newline, which hasn't been written
+    /// yet.
+    newline: bool,
+    failed: bool,
+}
+
+impl Trimmer<'_, '_> {
+    fn write(&mut self, s: &str) -> fmt::Result {
+        let res = self.f.write_str(s);
+        self.failed |= res.is_err();
+        res
+    }
+}
+
+impl fmt::Write for Trimmer<'_, '_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if s.is_empty() {
+            return Ok(());
+        }
+        if self.newline {
+            self.newline = false;
+            self.write("\n")?;
+        }
+        match s.strip_suffix('\n') {
+            Some(s) => {
+                self.newline = true;
+                self.write(s)
+            }
+            None => self.write(s),
+        }
+    }
+}
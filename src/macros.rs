@@ -0,0 +1,129 @@
+//! Runtime support for the [`printf!`] macro.
+
+use core::ffi::c_int;
+use core::fmt;
+
+use crate::output::{self, Sink};
+use crate::{Argument, Flags, Specifier};
+
+/// Format Rust values with a C format string, checked at compile time.
+///
+/// The format string must be a string literal, usually a C string literal.
+/// It's parsed at compile time, and the type of each argument is checked
+/// against its conversion and length modifier, using the types that C would
+/// read from a `va_list`:
+///
+/// | Conversion               | Type                                          |
+/// |--------------------------|-----------------------------------------------|
+/// | `%d`, `%i`               | `c_int` (`c_schar` for `hh`, `c_long` for `l`, `isize` for `z`/`t`, …) |
+/// | `%u`, `%o`, `%x`, `%X`, `%b`, `%B` | `c_uint` (`c_uchar` for `hh`, `c_ulong` for `l`, `usize` for `z`/`t`, …) |
+/// | `%f`, `%e`, `%g`, `%a`   | `f64`                                         |
+/// | `%s`                     | `&CStr`                                       |
+/// | `%c`                     | `c_char`                                      |
+/// | `%p`                     | any raw pointer                               |
+/// | `*` width or precision   | `c_int`                                       |
+///
+/// `%n` is not supported. The result is an [`Arguments`], which can be
+/// printed with [`Display`][fmt::Display] or passed to any handler.
+///
+/// ```rust
+/// use printf_compat::printf;
+///
+/// let name = c"world";
+/// assert_eq!(printf!(c"%5d %s", 42, name).to_string(), "   42 world");
+/// assert_eq!(printf!(c"%-*.*f|", 8, 2, 1.5).to_string(), "1.50    |");
+/// ```
+///
+/// Mismatched arguments are compile errors:
+///
+/// ```rust,compile_fail
+/// # use printf_compat::printf;
+/// printf!(c"%s", 42);
+/// ```
+///
+/// ```rust,compile_fail
+/// # use printf_compat::printf;
+/// printf!(c"%lu", 42u32);
+/// ```
+///
+/// ```rust,compile_fail
+/// # use printf_compat::printf;
+/// printf!(c"%d %d", 42);
+/// ```
+pub use printf_compat_macros::printf;
+
+/// Arguments that were already converted by [`printf!`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Arguments<'a, const N: usize>([Argument<'a>; N]);
+
+impl<'a, const N: usize> Arguments<'a, N> {
+    #[doc(hidden)]
+    pub const fn new(args: [Argument<'a>; N]) -> Self {
+        Arguments(args)
+    }
+
+    /// The literal text and conversions, in order.
+    pub fn as_slice(&self) -> &[Argument<'a>] {
+        &self.0
+    }
+
+    /// Write the arguments to `sink`, like [`format()`][crate::format()].
+    /// Returns the number of bytes written, or -1 if there was an error.
+    pub fn format(&self, mut sink: impl Sink) -> c_int {
+        let mut written: c_int = 0;
+        for &arg in &self.0 {
+            // Literal text is the only thing that the macro passes as bytes.
+            let res = match arg.specifier {
+                Specifier::Bytes(text) => sink.write_literal(text),
+                _ => sink.write_argument(arg),
+            };
+            match res {
+                Ok(res) => written += res as c_int,
+                Err(_) => return -1,
+            }
+        }
+        if sink.finish().is_err() {
+            return -1;
+        }
+        written
+    }
+}
+
+impl<const N: usize> fmt::Display for Arguments<'_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.format(output::fmt_write(f)) < 0 {
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[doc(hidden)]
+pub mod __private {
+    use super::*;
+
+    /// Build an argument, handling negative widths and precisions like the
+    /// runtime parser.
+    pub fn argument(
+        flags: u8,
+        width: c_int,
+        precision: Option<c_int>,
+        specifier: Specifier,
+    ) -> Argument {
+        let mut flags = Flags::from_bits_retain(flags);
+        if width < 0 {
+            flags.insert(Flags::LEFT_ALIGN);
+        }
+        Argument {
+            flags,
+            width: width.saturating_abs(),
+            precision: precision.filter(|&p| p >= 0),
+            specifier,
+        }
+    }
+
+    pub fn pointer<T: ?Sized>(ptr: *const T) -> *const () {
+        ptr.cast()
+    }
+}
@@ -8,7 +8,14 @@ use core::str::from_utf8;
 #[cfg(feature = "std")]
 pub use yes_std::*;
 
-use crate::{Argument, DoubleFormat, Flags, Specifier};
+pub mod binary;
+
+use crate::{
+    Argument, Config, Conversion, Dialect, Error, Flags, Locale, Promoted, Specifier, UnsignedInt,
+    WChar,
+};
+#[cfg(feature = "float")]
+use crate::{DoubleFormat, float};
 
 struct DummyWriter(usize);
 
@@ -19,6 +26,18 @@ impl fmt::Write for DummyWriter {
     }
 }
 
+/// Counts characters rather than bytes.
+#[cfg(feature = "float")]
+struct CharCounter(usize);
+
+#[cfg(feature = "float")]
+impl fmt::Write for CharCounter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.0 += s.chars().count();
+        Ok(())
+    }
+}
+
 struct WriteCounter<'a, T: fmt::Write>(&'a mut T, usize);
 
 impl<'a, T: fmt::Write> fmt::Write for WriteCounter<'a, T> {
@@ -28,6 +47,17 @@ impl<'a, T: fmt::Write> fmt::Write for WriteCounter<'a, T> {
     }
 }
 
+/// Write `n` spaces. This doesn't use `std`'s width, which panics when it's
+/// over [`u16::MAX`].
+fn write_padding(w: &mut impl fmt::Write, n: usize) -> fmt::Result {
+    (0..n).try_for_each(|_| w.write_char(' '))
+}
+
+/// Write `n` zeros, like [`write_padding`].
+fn write_zeros(w: &mut impl fmt::Write, n: usize) -> fmt::Result {
+    (0..n).try_for_each(|_| w.write_char('0'))
+}
+
 fn write_str(
     w: &mut impl fmt::Write,
     flags: Flags,
@@ -36,23 +66,370 @@ fn write_str(
     b: &[u8],
 ) -> fmt::Result {
     let string = from_utf8(b).map_err(|_| fmt::Error)?;
-    let precision = precision.unwrap_or(string.len() as c_int);
+    let string = match precision.and_then(|p| string.char_indices().nth(p as usize)) {
+        Some((end, _)) => &string[..end],
+        None => string,
+    };
+    let padding = (width as usize).saturating_sub(string.chars().count());
+    if !flags.contains(Flags::LEFT_ALIGN) {
+        write_padding(w, padding)?;
+    }
+    w.write_str(string)?;
+    if flags.contains(Flags::LEFT_ALIGN) {
+        write_padding(w, padding)?;
+    }
+    Ok(())
+}
+
+/// Decode a wide string, giving `None` for invalid characters.
+fn decode_wide(s: &[WChar]) -> impl Iterator<Item = Option<char>> + Clone + '_ {
+    #[cfg(windows)]
+    return char::decode_utf16(s.iter().copied()).map(Result::ok);
+    #[cfg(not(windows))]
+    return s.iter().map(|&c| char::from_u32(c));
+}
+
+fn write_wide(
+    w: &mut impl fmt::Write,
+    flags: Flags,
+    width: c_int,
+    precision: Option<c_int>,
+    s: &[WChar],
+) -> fmt::Result {
+    let chars = decode_wide(s).take(precision.map_or(usize::MAX, |p| p as usize));
+    let padding = (width as usize).saturating_sub(chars.clone().count());
+    if !flags.contains(Flags::LEFT_ALIGN) {
+        write_padding(w, padding)?;
+    }
+    for c in chars {
+        w.write_char(c.ok_or(fmt::Error)?)?;
+    }
+    if flags.contains(Flags::LEFT_ALIGN) {
+        write_padding(w, padding)?;
+    }
+    Ok(())
+}
+
+#[cfg(feature = "float")]
+#[allow(clippy::too_many_arguments)]
+fn write_double(
+    w: &mut impl fmt::Write,
+    flags: Flags,
+    width: c_int,
+    precision: Option<c_int>,
+    value: f64,
+    format: DoubleFormat,
+    dialect: Dialect,
+    locale: &Locale,
+) -> fmt::Result {
+    // A negative precision is taken as if the precision were omitted.
+    let precision = precision.and_then(|p| usize::try_from(p).ok()).unwrap_or(6);
+    let upper = format.is_upper();
+    let alternate = flags.contains(Flags::ALTERNATE_FORM);
+    let exponent_digits = if dialect == Dialect::Msvc { 3 } else { 2 };
+    let body = |w: &mut dyn fmt::Write| match format {
+        // MSVC's default NaN, from operations like 0.0 / 0.0, is negative.
+        _ if value.is_nan() && value.is_sign_negative() && dialect == Dialect::Msvc => {
+            w.write_str(if upper { "NAN(IND)" } else { "nan(ind)" })
+        }
+        _ if value.is_nan() => w.write_str(if upper { "NAN" } else { "nan" }),
+        _ if value.is_infinite() => w.write_str(if upper { "INF" } else { "inf" }),
+        DoubleFormat::Scientific | DoubleFormat::UpperScientific => {
+            float::write_scientific(w, value, precision, upper, alternate, exponent_digits)
+        }
+        DoubleFormat::Normal
+        | DoubleFormat::UpperNormal
+        | DoubleFormat::Auto
+        | DoubleFormat::UpperAuto
+        | DoubleFormat::Hex
+        | DoubleFormat::UpperHex => float::write_fixed(w, value, precision, alternate),
+    };
+    // Only `%f` can have more than one digit before the decimal point, so
+    // this doesn't group anything else. The decimal point is replaced first,
+    // so that a thousands separator of `.` is left alone.
+    let body = |w: &mut dyn fmt::Write| {
+        let body = |w: &mut dyn fmt::Write| body(&mut PointWriter(w, locale.decimal_point()));
+        if flags.contains(Flags::THOUSANDS_GROUPING) {
+            write!(w, "{}", Grouped(fmt::from_fn(|f| body(f)), locale))
+        } else {
+            body(w)
+        }
+    };
+
+    let sign = if value.is_nan() && dialect == Dialect::NewlibNano {
+        ""
+    } else if value.is_sign_negative() {
+        "-"
+    } else if flags.contains(Flags::PREPEND_PLUS) {
+        "+"
+    } else if flags.contains(Flags::PREPEND_SPACE) {
+        " "
+    } else {
+        ""
+    };
+    // Like glibc, the width counts a multibyte decimal point or thousands
+    // separator as one character here, unlike for integers.
+    let mut d = CharCounter(0);
+    body(&mut d)?;
+    let padding = (width as usize).saturating_sub(sign.len() + d.0);
+
+    if flags.contains(Flags::LEFT_ALIGN) {
+        w.write_str(sign)?;
+        body(w)?;
+        (0..padding).try_for_each(|_| w.write_char(' '))
+    } else if flags.contains(Flags::PREPEND_ZERO) && value.is_finite() {
+        w.write_str(sign)?;
+        (0..padding).try_for_each(|_| w.write_char('0'))?;
+        body(w)
+    } else {
+        (0..padding).try_for_each(|_| w.write_char(' '))?;
+        w.write_str(sign)?;
+        body(w)
+    }
+}
+
+/// Writes the locale's decimal point instead of `.`.
+#[cfg(feature = "float")]
+struct PointWriter<'a, W: ?Sized>(&'a mut W, &'a str);
+
+#[cfg(feature = "float")]
+impl<W: fmt::Write + ?Sized> fmt::Write for PointWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.1 == "." {
+            return self.0.write_str(s);
+        }
+        let mut parts = s.split('.');
+        if let Some(first) = parts.next() {
+            self.0.write_str(first)?;
+        }
+        parts.try_for_each(|part| {
+            self.0.write_str(self.1)?;
+            self.0.write_str(part)
+        })
+    }
+}
+
+/// Counts the digits at the start of what's written to it.
+struct DigitCounter(usize, bool);
+
+impl fmt::Write for DigitCounter {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for b in s.bytes() {
+            self.1 |= !b.is_ascii_digit();
+            if !self.1 {
+                self.0 += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes the locale's thousands separator between the groups of `digits`
+/// digits that it's given first, then passes the rest through.
+struct GroupWriter<'a, W: ?Sized> {
+    w: &'a mut W,
+    locale: &'a Locale,
+    digits: usize,
+}
+
+impl<W: fmt::Write + ?Sized> fmt::Write for GroupWriter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut rest = s;
+        while self.digits > 0 && !rest.is_empty() {
+            let (digit, tail) = rest.split_at(1);
+            self.w.write_str(digit)?;
+            self.digits -= 1;
+            if self.locale.separates(self.digits) {
+                self.w.write_str(self.locale.thousands_sep())?;
+            }
+            rest = tail;
+        }
+        self.w.write_str(rest)
+    }
+}
+
+/// `text`, which starts with the integer part of a number, with its digits
+/// grouped as the `'` flag asks.
+struct Grouped<'a, T>(T, &'a Locale);
+
+impl<T: fmt::Display> fmt::Display for Grouped<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use fmt::Write;
+        let mut count = DigitCounter(0, false);
+        write!(count, "{}", self.0)?;
+        let mut w = GroupWriter {
+            w: f,
+            locale: self.1,
+            digits: count.0,
+        };
+        write!(w, "{}", self.0)
+    }
+}
+
+/// `%'d`, `%'i` and `%'u`, which group the digits. As for the other integer
+/// conversions, the precision is ignored.
+fn write_grouped(
+    w: &mut impl fmt::Write,
+    flags: Flags,
+    width: c_int,
+    negative: bool,
+    magnitude: u64,
+    locale: &Locale,
+) -> fmt::Result {
+    let sign = if negative {
+        "-"
+    } else if flags.contains(Flags::PREPEND_PLUS) {
+        "+"
+    } else if flags.contains(Flags::PREPEND_SPACE) {
+        " "
+    } else {
+        ""
+    };
+    let zero = flags.contains(Flags::PREPEND_ZERO);
+    pad(w, flags, width, zero, sign, Grouped(magnitude, locale))
+}
+
+/// Write `text`, padded to `width` with spaces, or zeros after `prefix` if
+/// `zero` is set.
+fn pad(
+    w: &mut impl fmt::Write,
+    flags: Flags,
+    width: c_int,
+    zero: bool,
+    prefix: &str,
+    text: impl fmt::Display,
+) -> fmt::Result {
+    use fmt::Write;
+    let mut d = DummyWriter(0);
+    write!(d, "{}", text)?;
+    let padding = (width as usize).saturating_sub(prefix.len() + d.0);
     if flags.contains(Flags::LEFT_ALIGN) {
-        write!(
+        write!(w, "{prefix}{text}")?;
+        write_padding(w, padding)
+    } else if zero {
+        w.write_str(prefix)?;
+        write_zeros(w, padding)?;
+        write!(w, "{text}")
+    } else {
+        write_padding(w, padding)?;
+        write!(w, "{prefix}{text}")
+    }
+}
+
+/// A custom conversion's output, rendered for each [`fmt::Display::fmt`].
+struct Custom<'a>(Conversion, Promoted<'a>, Flags, Option<c_int>);
+
+impl fmt::Display for Custom<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.render(f, self.1, self.2, self.3)
+    }
+}
+
+/// `%m`, which prints an unknown error number like glibc.
+#[cfg(feature = "m-specifier")]
+fn write_errno(
+    w: &mut impl fmt::Write,
+    flags: Flags,
+    width: c_int,
+    precision: Option<c_int>,
+    errno: c_int,
+) -> fmt::Result {
+    match crate::strerror(errno) {
+        Some(message) => write_str(w, flags, width, precision, message.as_bytes()),
+        None => pad(
             w,
-            "{:1$.prec$}",
-            string,
-            width as usize,
-            prec = precision as usize
-        )
+            flags,
+            width,
+            false,
+            "",
+            format_args!("Unknown error {errno}"),
+        ),
+    }
+}
+
+/// `%b` and `%B`, which, unlike the other integer conversions, honour the
+/// precision.
+fn write_binary(
+    w: &mut impl fmt::Write,
+    flags: Flags,
+    width: c_int,
+    precision: Option<c_int>,
+    data: UnsignedInt,
+    upper: bool,
+) -> fmt::Result {
+    let data = u64::from(data);
+    let prefix = match (flags.contains(Flags::ALTERNATE_FORM) && data != 0, upper) {
+        (false, _) => "",
+        (true, false) => "0b",
+        (true, true) => "0B",
+    };
+    // As in C, a precision turns off zero padding, and a precision of 0
+    // prints nothing for 0.
+    let zero = flags.contains(Flags::PREPEND_ZERO) && precision.is_none();
+    let digits = precision.unwrap_or(1) as usize;
+    if data == 0 && digits == 0 {
+        pad(w, flags, width, false, "", "")
+    } else {
+        let len = (u64::BITS - data.leading_zeros()).max(1) as usize;
+        let text = LeadingZeros(digits.saturating_sub(len), data);
+        pad(w, flags, width, zero, prefix, text)
+    }
+}
+
+/// A number in binary after some zeros, written one at a time since `std`'s
+/// precision can't go over [`u16::MAX`].
+struct LeadingZeros(usize, u64);
+
+impl fmt::Display for LeadingZeros {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_zeros(f, self.0)?;
+        write!(f, "{:b}", self.1)
+    }
+}
+
+/// `%#o`, which prefixes the number with a 0 unless it already starts with
+/// one.
+fn write_alternate_octal(
+    w: &mut impl fmt::Write,
+    flags: Flags,
+    width: c_int,
+    data: UnsignedInt,
+    dialect: Dialect,
+) -> fmt::Result {
+    // newlib-nano always adds the prefix, so 0 is printed as `00`.
+    let prefix = if u64::from(data) != 0 || dialect == Dialect::NewlibNano {
+        "0"
     } else {
-        write!(
+        ""
+    };
+    let zero = flags.contains(Flags::PREPEND_ZERO);
+    pad(w, flags, width, zero, prefix, format_args!("{:o}", data))
+}
+
+fn write_pointer(
+    w: &mut impl fmt::Write,
+    flags: Flags,
+    width: c_int,
+    data: *const (),
+    dialect: Dialect,
+) -> fmt::Result {
+    let zero = flags.contains(Flags::PREPEND_ZERO);
+    match dialect {
+        Dialect::Glibc if data.is_null() => pad(w, flags, width, false, "", "(nil)"),
+        Dialect::Musl if data.is_null() => pad(w, flags, width, zero, "", "0"),
+        Dialect::Msvc => {
+            let digits = 2 * core::mem::size_of::<*const ()>();
+            let data = data as usize;
+            pad(w, flags, width, false, "", format_args!("{data:0digits$X}"))
+        }
+        _ => pad(
             w,
-            "{:>1$.prec$}",
-            string,
-            width as usize,
-            prec = precision as usize
-        )
+            flags,
+            width,
+            zero,
+            "0x",
+            format_args!("{:x}", data as usize),
+        ),
     }
 }
 
@@ -213,6 +590,229 @@ macro_rules! define_unumeric {
     }};
 }
 
+/// Where formatted output goes.
+///
+/// The formatting functions pass each run of literal text in the format
+/// string to [`write_literal`][Self::write_literal], each conversion to
+/// [`write_argument`][Self::write_argument], and then call
+/// [`finish`][Self::finish] once the whole string has been written, so
+/// buffered sinks, like a syslog connection or a ring buffer, know when a
+/// message is complete. If formatting fails, `finish` isn't called.
+///
+/// Every closure that takes an [`Argument`] and returns a `c_int`, the number
+/// of bytes written or -1 on error, is a sink whose errors are
+/// [`Error::Handler`]. [`format()`][crate::format] takes one directly; wrap
+/// one in [`handler`] to pass it straight to the other formatting functions,
+/// as in `output::handler(|arg| ...)`, so that Rust infers its argument's
+/// type. Use [`format_sink`][crate::format_sink] for other sinks.
+///
+/// The functions that return a [`Result`] report a failed sink with its own
+/// error, so it has to be able to hold the crate's [`Error`] too; the ones
+/// that return a C-style `c_int` report it as -1.
+///
+/// ```rust
+/// use printf_compat::{Error, argument::Argument, format_slice, output::{self, Sink}};
+///
+/// /// Collects lines, only adding a line once it has been fully formatted.
+/// struct Lines<'a> {
+///     partial: String,
+///     lines: &'a mut Vec<String>,
+/// }
+///
+/// impl Sink for Lines<'_> {
+///     type Error = Error;
+///
+///     fn write_argument(&mut self, arg: Argument) -> Result<usize, Error> {
+///         output::fmt_write(&mut self.partial).write_argument(arg)
+///     }
+///
+///     fn finish(&mut self) -> Result<(), Error> {
+///         self.lines.push(core::mem::take(&mut self.partial));
+///         Ok(())
+///     }
+/// }
+///
+/// let mut lines = Vec::new();
+/// for n in [99, 98] {
+///     let sink = Lines { partial: String::new(), lines: &mut lines };
+///     format_slice(c"%d bottles", &[&n], sink).unwrap();
+/// }
+/// let sink = Lines { partial: String::new(), lines: &mut lines };
+/// assert_eq!(format_slice(c"%d %d", &[&97], sink), Err(Error::MissingArgument));
+/// assert_eq!(lines, ["99 bottles", "98 bottles"]);
+/// ```
+pub trait Sink {
+    /// Why writing failed.
+    type Error;
+
+    /// Write literal text from a format string, returning the number of
+    /// bytes written.
+    ///
+    /// The text of wide format strings is passed to
+    /// [`write_argument`][Self::write_argument] as
+    /// [`Specifier::WideText`] instead. By default, this passes it there as
+    /// [`Specifier::Bytes`] too.
+    fn write_literal(&mut self, text: &[u8]) -> Result<usize, Self::Error> {
+        self.write_argument(Specifier::Bytes(text).into())
+    }
+
+    /// Write a conversion, returning the number of bytes written.
+    fn write_argument(&mut self, arg: Argument) -> Result<usize, Self::Error>;
+
+    /// Called once everything has been written.
+    fn finish(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl<F: FnMut(Argument) -> c_int> Sink for F {
+    type Error = Error;
+
+    fn write_argument(&mut self, arg: Argument) -> Result<usize, Error> {
+        usize::try_from(self(arg)).map_err(|_| Error::Handler)
+    }
+}
+
+/// Use a closure as a [`Sink`].
+///
+/// [`format()`][crate::format] takes a closure, so its argument's type is
+/// inferred. The functions that take a generic `impl Sink` don't tell Rust
+/// what a closure takes: write `|arg: Argument|`, or pass the closure through
+/// this function, which does.
+///
+/// ```rust
+/// use printf_compat::{argument::Specifier, format_slice, output};
+///
+/// let mut ints = 0;
+/// let res = format_slice(
+///     c"%d and %d",
+///     &[&1, &2],
+///     output::handler(|arg| {
+///         if let Specifier::Int(_) = arg.specifier {
+///             ints += 1;
+///         }
+///         0
+///     }),
+/// );
+/// assert_eq!((res, ints), (Ok(0), 2));
+/// ```
+pub fn handler<F: FnMut(Argument) -> c_int>(f: F) -> F {
+    f
+}
+
+/// A sink whose errors are all reported as [`Error::Handler`], for the
+/// functions that return -1 on any error.
+pub(crate) struct Untyped<S>(pub(crate) S);
+
+impl<S: Sink> Sink for Untyped<S> {
+    type Error = Error;
+
+    fn write_literal(&mut self, text: &[u8]) -> Result<usize, Error> {
+        self.0.write_literal(text).map_err(|_| Error::Handler)
+    }
+
+    fn write_argument(&mut self, arg: Argument) -> Result<usize, Error> {
+        self.0.write_argument(arg).map_err(|_| Error::Handler)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.0.finish().map_err(|_| Error::Handler)
+    }
+}
+
+/// A sink that stops with [`Error::OutputLimit`] before it's passed anything
+/// that would take the output past [`Limits::max_output`].
+///
+/// Each argument is measured by formatting it like [`fmt_write_locale`]
+/// first, so that the sink never sees one that doesn't fit.
+///
+/// [`Limits::max_output`]: crate::Limits::max_output
+pub(crate) struct Limited<S> {
+    sink: S,
+    /// How many more bytes may be written, or `None` if there's no limit.
+    remaining: Option<usize>,
+    dialect: Dialect,
+    locale: Locale,
+}
+
+impl<S: Sink> Limited<S>
+where
+    S::Error: From<Error>,
+{
+    pub(crate) fn new(sink: S, config: &Config) -> Self {
+        Limited {
+            sink,
+            remaining: config.limits.map(|l| l.max_output),
+            dialect: config.dialect,
+            locale: config.locale,
+        }
+    }
+
+    /// Fail unless `len` more bytes fit.
+    fn check(&self, len: usize) -> Result<(), Error> {
+        match self.remaining {
+            Some(remaining) if len > remaining => Err(Error::OutputLimit),
+            _ => Ok(()),
+        }
+    }
+
+    /// Take `len` bytes that were written by the sink out of the budget.
+    fn consume(&mut self, len: usize) -> Result<usize, Error> {
+        if let Some(remaining) = &mut self.remaining {
+            *remaining = remaining.checked_sub(len).ok_or(Error::OutputLimit)?;
+        }
+        Ok(len)
+    }
+
+    /// How many bytes `arg` takes up. Anything that `fmt_write` can't
+    /// format counts as nothing here, and is only checked once the sink has
+    /// written it, except for strings that aren't UTF-8, which other sinks
+    /// copy byte for byte.
+    fn measure(&self, arg: Argument) -> usize {
+        let mut d = DummyWriter(0);
+        if fmt_write_locale(&mut d, self.dialect, self.locale)
+            .write_argument(arg)
+            .is_ok()
+        {
+            return d.0;
+        }
+        let bytes = match arg.specifier {
+            Specifier::Bytes(b) => b,
+            Specifier::String(s) => s.to_bytes(),
+            _ => return 0,
+        };
+        let len = arg
+            .precision
+            .map_or(bytes.len(), |p| bytes.len().min(p as usize));
+        len.max(arg.width as usize)
+    }
+}
+
+impl<S: Sink> Sink for Limited<S>
+where
+    S::Error: From<Error>,
+{
+    type Error = S::Error;
+
+    fn write_literal(&mut self, text: &[u8]) -> Result<usize, S::Error> {
+        self.check(text.len())?;
+        let len = self.sink.write_literal(text)?;
+        Ok(self.consume(len)?)
+    }
+
+    fn write_argument(&mut self, arg: Argument) -> Result<usize, S::Error> {
+        if self.remaining.is_some() {
+            self.check(self.measure(arg))?;
+        }
+        let len = self.sink.write_argument(arg)?;
+        Ok(self.consume(len)?)
+    }
+
+    fn finish(&mut self) -> Result<(), S::Error> {
+        self.sink.finish()
+    }
+}
+
 /// Write to a struct that implements [`fmt::Write`].
 ///
 /// # Differences
@@ -222,24 +822,94 @@ macro_rules! define_unumeric {
 /// - only valid UTF-8 data can be printed.
 /// - an `X` format specifier with a `#` flag prints the hex data in uppercase,
 ///   but the leading `0x` is still lowercase.
-/// - an `o` format specifier with a `#` flag precedes the number with an `o`
-///   instead of `0`.
 /// - `g`/`G` (shorted floating point) is aliased to `f`/`F`` (decimal floating
 ///   point).
 /// - same for `a`/`A` (hex floating point).
 /// - the `n` format specifier, [`Specifier::WriteBytesWritten`], is not
 ///   implemented and will cause an error if encountered.
-/// - precision is ignored for integral types, instead of specifying the
-///   minimum number of digits.
-pub fn fmt_write(w: &mut impl fmt::Write) -> impl FnMut(Argument) -> c_int + '_ {
-    use fmt::Write;
-    move |Argument {
-              flags,
-              mut width,
-              precision,
-              specifier,
-          }| {
-        let mut w = WriteCounter(w, 0);
+/// - conversions disabled with a cargo feature, [`Specifier::Disabled`], cause
+///   an error. Wrap the handler in [`placeholder`] to print them differently.
+/// - precision is ignored for integral types other than `b`/`B`, instead of
+///   specifying the minimum number of digits.
+/// - wide strings and characters are converted to UTF-8, regardless of the
+///   locale. An invalid wide character causes an error.
+/// - the `'` flag only groups the digits of `d`, `i`, `u` and the floating
+///   point conversions, as POSIX says; glibc groups `x` and `o` too.
+///
+/// This imitates glibc's output in the `C` locale; use [`fmt_write_dialect`]
+/// to pick another [`Dialect`], or [`fmt_write_locale`] to pick another
+/// [`Locale`] too.
+///
+/// The returned closure is a handler for [`format()`][crate::format]; each
+/// call returns the number of bytes written, or -1 on error. Use
+/// [`fmt_sink`] for the [`Sink`] it wraps.
+pub fn fmt_write<W: fmt::Write>(w: &mut W) -> impl FnMut(Argument) -> c_int + '_ {
+    let mut sink = fmt_sink(w);
+    move |arg| match sink.write_argument(arg) {
+        Ok(written) => written as c_int,
+        Err(_) => -1,
+    }
+}
+
+/// Write to a struct that implements [`fmt::Write`], as a [`Sink`].
+///
+/// This shares the same caveats as [`fmt_write`].
+pub fn fmt_sink<W: fmt::Write>(w: &mut W) -> FmtWrite<'_, W> {
+    fmt_write_dialect(w, Dialect::Glibc)
+}
+
+/// Write to a struct that implements [`fmt::Write`], imitating the output of
+/// the C library given by `dialect`.
+///
+/// This shares the same caveats as [`fmt_write`].
+pub fn fmt_write_dialect<W: fmt::Write>(w: &mut W, dialect: Dialect) -> FmtWrite<'_, W> {
+    fmt_write_locale(w, dialect, Locale::C)
+}
+
+/// Write to a struct that implements [`fmt::Write`], imitating the output of
+/// the C library given by `dialect`, with numbers written as in `locale`.
+///
+/// This shares the same caveats as [`fmt_write`].
+pub fn fmt_write_locale<W: fmt::Write>(
+    w: &mut W,
+    dialect: Dialect,
+    locale: Locale,
+) -> FmtWrite<'_, W> {
+    FmtWrite { w, dialect, locale }
+}
+
+/// A [`Sink`] that writes to a struct that implements [`fmt::Write`],
+/// created by [`fmt_sink`].
+///
+/// A failed write, or output that can't be written, is an
+/// [`Error::Handler`].
+#[derive(Debug)]
+pub struct FmtWrite<'a, W> {
+    w: &'a mut W,
+    dialect: Dialect,
+    locale: Locale,
+}
+
+impl<W: fmt::Write> Sink for FmtWrite<'_, W> {
+    type Error = Error;
+
+    fn write_literal(&mut self, text: &[u8]) -> Result<usize, Error> {
+        let text = from_utf8(text).map_err(|_| Error::Handler)?;
+        self.w.write_str(text).map_err(|_| Error::Handler)?;
+        Ok(text.len())
+    }
+
+    fn write_argument(
+        &mut self,
+        Argument {
+            flags,
+            mut width,
+            precision,
+            specifier,
+        }: Argument,
+    ) -> Result<usize, Error> {
+        use fmt::Write;
+        let mut w = WriteCounter(&mut *self.w, 0);
         let w = &mut w;
         let res = match specifier {
             Specifier::Percent => w.write_char('%'),
@@ -251,29 +921,38 @@ pub fn fmt_write(w: &mut impl fmt::Write) -> impl FnMut(Argument) -> c_int + '_
             Specifier::UpperHex(data) => {
                 define_unumeric!(w, data, flags, width, precision.unwrap_or(0), "X")
             }
+            Specifier::Binary(data) => write_binary(w, flags, width, precision, data, false),
+            Specifier::UpperBinary(data) => write_binary(w, flags, width, precision, data, true),
+            Specifier::Octal(data) if flags.contains(Flags::ALTERNATE_FORM) => {
+                write_alternate_octal(w, flags, width, data, self.dialect)
+            }
             Specifier::Octal(data) => {
                 define_unumeric!(w, data, flags, width, precision.unwrap_or(0), "o")
             }
+            Specifier::Uint(data) if flags.contains(Flags::THOUSANDS_GROUPING) => {
+                write_grouped(w, flags, width, false, u64::from(data), &self.locale)
+            }
             Specifier::Uint(data) => {
                 define_unumeric!(w, data, flags, width, precision.unwrap_or(0))
             }
+            Specifier::Int(data) if flags.contains(Flags::THOUSANDS_GROUPING) => {
+                let data = i64::from(data);
+                write_grouped(w, flags, width, data < 0, data.unsigned_abs(), &self.locale)
+            }
             Specifier::Int(data) => define_numeric!(w, data, flags, width, precision.unwrap_or(0)),
-            Specifier::Double { value, format } => match format {
-                DoubleFormat::Normal
-                | DoubleFormat::UpperNormal
-                | DoubleFormat::Auto
-                | DoubleFormat::UpperAuto
-                | DoubleFormat::Hex
-                | DoubleFormat::UpperHex => {
-                    define_numeric!(w, value, flags, width, precision.unwrap_or(6))
-                }
-                DoubleFormat::Scientific => {
-                    define_numeric!(w, value, flags, width, precision.unwrap_or(6), "e")
-                }
-                DoubleFormat::UpperScientific => {
-                    define_numeric!(w, value, flags, width, precision.unwrap_or(6), "E")
-                }
-            },
+            #[cfg(feature = "float")]
+            Specifier::Double { value, format } => write_double(
+                w,
+                flags,
+                width,
+                precision,
+                value,
+                format,
+                self.dialect,
+                &self.locale,
+            ),
+            #[cfg(not(feature = "float"))]
+            Specifier::Double { .. } => Err(Default::default()),
             Specifier::Char(data) => {
                 if flags.contains(Flags::LEFT_ALIGN) {
                     write!(w, "{:width$}", data as u8 as char, width = width as usize)
@@ -281,21 +960,148 @@ pub fn fmt_write(w: &mut impl fmt::Write) -> impl FnMut(Argument) -> c_int + '_
                     write!(w, "{:>width$}", data as u8 as char, width = width as usize)
                 }
             }
-            Specifier::Pointer(data) => {
-                if flags.contains(Flags::LEFT_ALIGN) {
-                    write!(w, "{:<width$p}", data, width = width as usize)
-                } else if flags.contains(Flags::PREPEND_ZERO) {
-                    write!(w, "{:0width$p}", data, width = width as usize)
-                } else {
-                    write!(w, "{:width$p}", data, width = width as usize)
-                }
+            Specifier::WideText(data) | Specifier::WideString(data) => {
+                write_wide(w, flags, width, precision, data)
+            }
+            Specifier::WideChar(data) => write_wide(w, flags, width, None, &[data]),
+            Specifier::Pointer(data) => write_pointer(w, flags, width, data, self.dialect),
+            Specifier::Custom { conversion, value } => {
+                let text = Custom(conversion, value, flags, precision);
+                pad(w, flags, width, false, "", text)
             }
-            Specifier::WriteBytesWritten(_, _) => Err(Default::default()),
+            #[cfg(feature = "pointer-ext")]
+            Specifier::PointerExt(data) => pad(w, flags, width, false, "", data),
+            #[cfg(feature = "m-specifier")]
+            Specifier::Errno(errno) => write_errno(w, flags, width, precision, errno),
+            #[cfg(not(feature = "m-specifier"))]
+            Specifier::Errno(_) => Err(Default::default()),
+            Specifier::WriteBytesWritten(_, _) | Specifier::Disabled(_) => Err(Default::default()),
         };
         match res {
-            Ok(_) => w.1 as c_int,
-            Err(_) => -1,
+            Ok(_) => Ok(w.1),
+            Err(_) => Err(Error::Handler),
+        }
+    }
+}
+
+/// Writes wide characters to a buffer, failing when it's full.
+#[cfg(feature = "wide")]
+struct WideWriter<'a> {
+    buf: &'a mut [WChar],
+    len: usize,
+}
+
+#[cfg(feature = "wide")]
+impl fmt::Write for WideWriter<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            #[cfg(windows)]
+            let mut buf = [0; 2];
+            #[cfg(windows)]
+            let units = c.encode_utf16(&mut buf);
+            #[cfg(not(windows))]
+            let units = [WChar::from(c)];
+            for &unit in units.iter() {
+                *self.buf.get_mut(self.len).ok_or(fmt::Error)? = unit;
+                self.len += 1;
+            }
         }
+        Ok(())
+    }
+}
+
+/// Write wide characters to a buffer, like `swprintf`. This is usually used
+/// with [`format_wide`][crate::format_wide].
+///
+/// Each call returns the number of wide characters written, or -1 if `buf` is
+/// full. Narrow text has to be UTF-8. No null terminator is written, but the
+/// total returned by [`format_wide`][crate::format_wide] is where it goes.
+///
+/// This shares the same caveats as [`fmt_write`].
+///
+/// ```rust
+/// # #![feature(c_variadic)]
+/// use core::ffi::c_int;
+/// use printf_compat::{argument::WChar, format_wide, output};
+///
+/// #[unsafe(no_mangle)]
+/// unsafe extern "C" fn swprintf(
+///     buf: *mut WChar,
+///     len: usize,
+///     format: *const WChar,
+///     args: ...
+/// ) -> c_int {
+///     if len == 0 {
+///         return -1;
+///     }
+///     let buf = unsafe { core::slice::from_raw_parts_mut(buf, len) };
+///     let written = unsafe { format_wide(format, args, output::wide_buffer(&mut buf[..len - 1])) };
+///     buf[written.max(0) as usize] = 0;
+///     written
+/// }
+/// ```
+#[cfg(feature = "wide")]
+pub fn wide_buffer(buf: &mut [WChar]) -> impl FnMut(Argument) -> c_int + '_ {
+    let mut w = WideWriter { buf, len: 0 };
+    move |arg| {
+        let start = w.len;
+        if fmt_sink(&mut w).write_argument(arg).is_err() {
+            return -1;
+        }
+        (w.len - start) as c_int
+    }
+}
+
+/// Print `text` in place of conversions that were disabled with a cargo
+/// feature ([`Specifier::Disabled`]), instead of passing them to `sink`.
+///
+/// The width and left-align flag of the conversion are still applied to
+/// `text`.
+///
+/// ```rust
+/// # #![feature(c_variadic)]
+/// # use core::ffi::{c_char, c_int};
+/// use printf_compat::{format_sink, output};
+///
+/// unsafe extern "C" fn c_library_print(str: *const c_char, args: ...) -> c_int {
+///     let mut s = String::new();
+///     let sink = output::placeholder(b"?", output::fmt_sink(&mut s));
+///     unsafe { format_sink(str, args, sink) }
+/// }
+/// ```
+pub fn placeholder<S: Sink>(text: &[u8], sink: S) -> Placeholder<'_, S> {
+    Placeholder { text, sink }
+}
+
+/// A [`Sink`] that prints text in place of disabled conversions, created by
+/// [`placeholder`].
+#[derive(Debug)]
+pub struct Placeholder<'a, S> {
+    text: &'a [u8],
+    sink: S,
+}
+
+impl<S: Sink> Sink for Placeholder<'_, S> {
+    type Error = S::Error;
+
+    fn write_literal(&mut self, text: &[u8]) -> Result<usize, S::Error> {
+        self.sink.write_literal(text)
+    }
+
+    fn write_argument(&mut self, arg: Argument) -> Result<usize, S::Error> {
+        match arg.specifier {
+            Specifier::Disabled(_) => self.sink.write_argument(Argument {
+                flags: arg.flags & Flags::LEFT_ALIGN,
+                width: arg.width,
+                precision: None,
+                specifier: Specifier::Bytes(self.text),
+            }),
+            _ => self.sink.write_argument(arg),
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), S::Error> {
+        self.sink.finish()
     }
 }
 
@@ -309,9 +1115,20 @@ pub fn fmt_write(w: &mut impl fmt::Write) -> impl FnMut(Argument) -> c_int + '_
 ///
 /// [`VaList`]s are *very* unsafe. The passed `format` and `args` parameter must be a valid [`printf` format string](http://www.cplusplus.com/reference/cstdio/printf/).
 pub unsafe fn display<'a>(format: *const c_char, va_list: VaList<'a>) -> VaListDisplay<'a> {
+    unsafe { display_with_config(format, va_list, Config::new()) }
+}
+
+/// [`display`], with the given [`Config`], whose dialect and locale are also
+/// used for the output.
+pub(crate) unsafe fn display_with_config<'a>(
+    format: *const c_char,
+    va_list: VaList<'a>,
+    config: Config,
+) -> VaListDisplay<'a> {
     VaListDisplay {
         format,
         va_list,
+        config,
         written: Cell::new(0),
     }
 }
@@ -335,6 +1152,7 @@ pub unsafe fn display<'a>(format: *const c_char, va_list: VaList<'a>) -> VaListD
 pub struct VaListDisplay<'a> {
     format: *const c_char,
     va_list: VaList<'a>,
+    config: Config,
     written: Cell<c_int>,
 }
 
@@ -348,7 +1166,9 @@ impl VaListDisplay<'_> {
 impl<'a> fmt::Display for VaListDisplay<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         unsafe {
-            let bytes = crate::format(self.format, self.va_list.clone(), fmt_write(f));
+            let sink = fmt_write_locale(f, self.config.dialect, self.config.locale);
+            let va_list = self.va_list.clone();
+            let bytes = crate::format_with_config(self.format, va_list, &self.config, sink);
             self.written.set(bytes);
             if bytes < 0 { Err(fmt::Error) } else { Ok(()) }
         }
@@ -416,36 +1236,101 @@ mod yes_std {
     /// Write to a struct that implements [`io::Write`].
     ///
     /// This shares the same caveats as [`fmt_write`], except that non-UTF-8
-    /// data is supported.
-    pub fn io_write(w: &mut impl io::Write) -> impl FnMut(Argument) -> c_int + '_ {
-        use io::Write;
-        move |Argument {
-                  flags,
-                  width,
-                  precision,
-                  specifier,
-              }| {
-            let mut w = IoWriteCounter(w, 0);
-            let mut w = &mut w;
-            let res = match specifier {
-                Specifier::Percent => w.write_all(b"%"),
-                Specifier::Bytes(data) => write_bytes(w, flags, width, precision, data),
-                Specifier::String(data) => write_bytes(w, flags, width, precision, data.to_bytes()),
+    /// data is supported. Use [`io_sink`] to have the writer flushed once
+    /// everything has been written, and a failed write returned as its
+    /// [`io::Error`].
+    pub fn io_write<W: io::Write>(w: &mut W) -> impl FnMut(Argument) -> c_int + '_ {
+        let mut sink = io_sink(w);
+        move |arg| match sink.write_argument(arg) {
+            Ok(written) => written as c_int,
+            Err(_) => -1,
+        }
+    }
+
+    /// Write to a struct that implements [`io::Write`], as a [`Sink`].
+    ///
+    /// This shares the same caveats as [`io_write`]. The writer is flushed
+    /// once everything has been written, and a failed write is returned as
+    /// its [`io::Error`].
+    pub fn io_sink<W: io::Write>(w: &mut W) -> IoWrite<'_, W> {
+        io_write_dialect(w, Dialect::Glibc)
+    }
+
+    /// Write to a struct that implements [`io::Write`], imitating the output
+    /// of the C library given by `dialect`.
+    ///
+    /// This shares the same caveats as [`io_write`].
+    pub fn io_write_dialect<W: io::Write>(w: &mut W, dialect: Dialect) -> IoWrite<'_, W> {
+        io_write_locale(w, dialect, Locale::C)
+    }
+
+    /// Write to a struct that implements [`io::Write`], imitating the output
+    /// of the C library given by `dialect`, with numbers written as in
+    /// `locale`.
+    ///
+    /// This shares the same caveats as [`io_write`].
+    pub fn io_write_locale<W: io::Write>(
+        w: &mut W,
+        dialect: Dialect,
+        locale: Locale,
+    ) -> IoWrite<'_, W> {
+        IoWrite { w, dialect, locale }
+    }
+
+    /// A [`Sink`] that writes to a struct that implements [`io::Write`],
+    /// created by [`io_sink`].
+    ///
+    /// Formatting errors are returned as an [`io::Error`] holding the
+    /// [`Error`].
+    #[derive(Debug)]
+    pub struct IoWrite<'a, W> {
+        w: &'a mut W,
+        dialect: Dialect,
+        locale: Locale,
+    }
+
+    impl<W: io::Write> Sink for IoWrite<'_, W> {
+        type Error = io::Error;
+
+        fn write_literal(&mut self, text: &[u8]) -> io::Result<usize> {
+            self.w.write_all(text)?;
+            Ok(text.len())
+        }
+
+        fn write_argument(
+            &mut self,
+            Argument {
+                flags,
+                width,
+                precision,
+                specifier,
+            }: Argument,
+        ) -> io::Result<usize> {
+            let mut w = IoWriteCounter(&mut *self.w, 0);
+            match specifier {
+                Specifier::Percent => io::Write::write_all(&mut w, b"%")?,
+                Specifier::Bytes(data) => write_bytes(&mut w, flags, width, precision, data)?,
+                Specifier::String(data) => {
+                    write_bytes(&mut w, flags, width, precision, data.to_bytes())?
+                }
                 _ => {
                     let mut writer = FmtWriter(&mut w, Ok(()));
-                    fmt_write(&mut writer)(Argument {
-                        flags,
-                        width,
-                        precision,
-                        specifier,
-                    });
-                    writer.1
+                    let res = fmt_write_locale(&mut writer, self.dialect, self.locale)
+                        .write_argument(Argument {
+                            flags,
+                            width,
+                            precision,
+                            specifier,
+                        });
+                    writer.1?;
+                    res?;
                 }
-            };
-            match res {
-                Ok(_) => w.1 as c_int,
-                Err(_) => -1,
             }
+            Ok(w.1)
+        }
+
+        fn finish(&mut self) -> io::Result<()> {
+            self.w.flush()
         }
     }
 }
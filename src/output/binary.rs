@@ -0,0 +1,637 @@
+//! A compact binary encoding of formatting arguments, to be decoded into
+//! text somewhere else.
+//!
+//! Sending the text of every log message over a slow link, like a UART, is
+//! wasteful when the receiving end could have the format strings already. An
+//! [`Encoder`] writes only an ID for the format string, like an index into a
+//! table or the address of a string literal, and the values of the
+//! arguments. [`decode`] looks up the format string by its ID, and writes the
+//! same text that [`fmt_write`][super::fmt_write] would have, to any
+//! [`Sink`].
+//!
+//! ```rust
+//! use printf_compat::{Config, format_slice, output::{self, binary}};
+//!
+//! const FORMATS: &[&core::ffi::CStr] = &[c"boot %d", c"%s: %#x"];
+//!
+//! let mut buf = [0; 64];
+//! let mut encoder = binary::Encoder::new(&mut buf, 1);
+//! format_slice(FORMATS[1], &[&c"status", &0x2a], &mut encoder).unwrap();
+//! let mut bytes = encoder.as_bytes();
+//! // The ID, the string, and the number with its `#` flag, but no text.
+//! assert_eq!(bytes.len(), 14);
+//!
+//! let mut s = String::new();
+//! let formats = |id| FORMATS.get(id as usize).copied();
+//! binary::decode(&mut bytes, formats, &Config::new(), output::fmt_write(&mut s)).unwrap();
+//! assert_eq!(s, "status: 0x2a");
+//! ```
+//!
+//! Literal text is never encoded, since the decoder reads it from the format
+//! string. Each conversion is a tag byte, its value, and its flags, width and
+//! precision if it has any. Integers are LEB128 varints, zigzag-encoded if
+//! signed, floating point numbers are their 8 little-endian bytes, and
+//! strings run up to their null terminator. Everything works without `std`
+//! or an allocator.
+//!
+//! The format string has to be decoded with the same [`Config`] that it was
+//! encoded with, and `%n` isn't supported.
+//!
+//! To decode a log on a computer, the `printf-compat-decode` command in this
+//! crate's repository reads the format strings from the firmware's ELF file
+//! or a JSON table.
+
+use core::ffi::*;
+
+use super::{Limited, Sink};
+#[cfg(feature = "pointer-ext")]
+use crate::argument::{Ipv6Format, PointerExt};
+use crate::parser::{Piece, Pieces};
+use crate::{
+    Argument, Config, DoubleFormat, Error, Flags, Promoted, SignedInt, Specifier, UnsignedInt,
+    WChar,
+};
+
+/// The longest wide string, in [`WChar`]s, that can be encoded. [`decode`]
+/// copies wide strings to a buffer of this size on the stack.
+pub const MAX_WIDE_STRING: usize = 256;
+
+/// Set in a tag byte if the flags, width and precision follow it.
+const MODIFIERS: u8 = 0x80;
+
+// Tags. The integer tags are followed by one for each kind of integer, in
+// the order of `SignedInt` and `UnsignedInt`, and `DOUBLE` by one for each
+// `DoubleFormat`.
+const INT: u8 = 0;
+const UINT: u8 = 6;
+const OCTAL: u8 = 12;
+const HEX: u8 = 18;
+const UPPER_HEX: u8 = 24;
+const BINARY: u8 = 30;
+const UPPER_BINARY: u8 = 36;
+const DOUBLE: u8 = 42;
+const PERCENT: u8 = 50;
+const BYTES: u8 = 51;
+const STRING: u8 = 52;
+const CHAR: u8 = 53;
+const WIDE_STRING: u8 = 54;
+const WIDE_CHAR: u8 = 55;
+const POINTER: u8 = 56;
+const ERRNO: u8 = 57;
+const DISABLED: u8 = 58;
+const CUSTOM_INT: u8 = 59;
+const CUSTOM_UINT: u8 = 65;
+const CUSTOM_DOUBLE: u8 = 71;
+const CUSTOM_STRING: u8 = 72;
+const CUSTOM_NULL: u8 = 73;
+const CUSTOM_POINTER: u8 = 74;
+#[cfg(feature = "pointer-ext")]
+const IPV4: u8 = 75;
+#[cfg(feature = "pointer-ext")]
+const IPV6: u8 = 76;
+#[cfg(feature = "pointer-ext")]
+const MAC: u8 = 77;
+#[cfg(feature = "pointer-ext")]
+const UUID: u8 = 78;
+#[cfg(feature = "pointer-ext")]
+const HEX_DUMP: u8 = 79;
+#[cfg(feature = "pointer-ext")]
+const ERROR_POINTER: u8 = 80;
+
+const DOUBLE_FORMATS: [DoubleFormat; 8] = [
+    DoubleFormat::Normal,
+    DoubleFormat::UpperNormal,
+    DoubleFormat::Scientific,
+    DoubleFormat::UpperScientific,
+    DoubleFormat::Auto,
+    DoubleFormat::UpperAuto,
+    DoubleFormat::Hex,
+    DoubleFormat::UpperHex,
+];
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+fn signed_kind(value: SignedInt) -> (u8, i64) {
+    let kind = match value {
+        SignedInt::Int(_) => 0,
+        SignedInt::Char(_) => 1,
+        SignedInt::Short(_) => 2,
+        SignedInt::Long(_) => 3,
+        SignedInt::LongLong(_) => 4,
+        SignedInt::Isize(_) => 5,
+    };
+    (kind, value.into())
+}
+
+fn unsigned_kind(value: UnsignedInt) -> (u8, u64) {
+    let kind = match value {
+        UnsignedInt::Int(_) => 0,
+        UnsignedInt::Char(_) => 1,
+        UnsignedInt::Short(_) => 2,
+        UnsignedInt::Long(_) => 3,
+        UnsignedInt::LongLong(_) => 4,
+        UnsignedInt::Isize(_) => 5,
+    };
+    (kind, value.into())
+}
+
+/// A [`Sink`] that encodes the arguments into a buffer, for [`decode`].
+///
+/// Each call returns the number of bytes that it encoded, so the total
+/// returned by the formatting functions doesn't include the ID, or anything
+/// for literal text. Encoding fails with [`Error::BufferFull`] if the buffer
+/// is too small, or a wide string is longer than [`MAX_WIDE_STRING`].
+#[derive(Debug)]
+pub struct Encoder<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    /// Whether the ID didn't fit.
+    full: bool,
+}
+
+impl<'a> Encoder<'a> {
+    /// Encode a message to `buf`, starting with the format string's `id`.
+    pub fn new(buf: &'a mut [u8], id: u64) -> Self {
+        let mut encoder = Encoder {
+            buf,
+            len: 0,
+            full: false,
+        };
+        encoder.full = encoder.varint(id).is_err();
+        encoder
+    }
+
+    /// The message encoded so far.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf[..self.len]
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        let end = self.len + bytes.len();
+        self.buf
+            .get_mut(self.len..end)
+            .ok_or(Error::BufferFull)?
+            .copy_from_slice(bytes);
+        self.len = end;
+        Ok(())
+    }
+
+    fn byte(&mut self, byte: u8) -> Result<(), Error> {
+        self.bytes(&[byte])
+    }
+
+    fn varint(&mut self, mut value: u64) -> Result<(), Error> {
+        while value >= 0x80 {
+            self.byte(value as u8 | 0x80)?;
+            value >>= 7;
+        }
+        self.byte(value as u8)
+    }
+
+    fn signed(&mut self, tag: u8, value: SignedInt) -> Result<(), Error> {
+        let (kind, value) = signed_kind(value);
+        self.byte(tag + kind)?;
+        self.varint(zigzag(value))
+    }
+
+    fn unsigned(&mut self, tag: u8, value: UnsignedInt) -> Result<(), Error> {
+        let (kind, value) = unsigned_kind(value);
+        self.byte(tag + kind)?;
+        self.varint(value)
+    }
+
+    fn c_str(&mut self, s: &CStr) -> Result<(), Error> {
+        self.bytes(s.to_bytes_with_nul())
+    }
+
+    fn encode(&mut self, specifier: Specifier) -> Result<(), Error> {
+        match specifier {
+            Specifier::Percent => self.byte(PERCENT),
+            Specifier::Int(data) => self.signed(INT, data),
+            Specifier::Uint(data) => self.unsigned(UINT, data),
+            Specifier::Octal(data) => self.unsigned(OCTAL, data),
+            Specifier::Hex(data) => self.unsigned(HEX, data),
+            Specifier::UpperHex(data) => self.unsigned(UPPER_HEX, data),
+            Specifier::Binary(data) => self.unsigned(BINARY, data),
+            Specifier::UpperBinary(data) => self.unsigned(UPPER_BINARY, data),
+            Specifier::Double { value, format } => {
+                let format = DOUBLE_FORMATS
+                    .iter()
+                    .position(|&f| f == format)
+                    .unwrap_or(0);
+                self.byte(DOUBLE + format as u8)?;
+                self.bytes(&value.to_le_bytes())
+            }
+            Specifier::Bytes(data) => {
+                self.byte(BYTES)?;
+                self.varint(data.len() as u64)?;
+                self.bytes(data)
+            }
+            Specifier::String(data) => {
+                self.byte(STRING)?;
+                self.c_str(data)
+            }
+            Specifier::Char(data) => self.bytes(&[CHAR, data as u8]),
+            // Like the rest of the format string, the decoder has this.
+            Specifier::WideText(_) => Ok(()),
+            Specifier::WideString(data) => {
+                if data.len() > MAX_WIDE_STRING {
+                    return Err(Error::BufferFull);
+                }
+                self.byte(WIDE_STRING)?;
+                self.varint(data.len() as u64)?;
+                data.iter().try_for_each(|&c| self.varint(c.into()))
+            }
+            Specifier::WideChar(data) => {
+                self.byte(WIDE_CHAR)?;
+                self.varint(data.into())
+            }
+            Specifier::Pointer(data) => {
+                self.byte(POINTER)?;
+                self.varint(data.addr() as u64)
+            }
+            Specifier::Custom { conversion, value } => {
+                match value {
+                    Promoted::Int(data) => self.signed(CUSTOM_INT, data)?,
+                    Promoted::Uint(data) => self.unsigned(CUSTOM_UINT, data)?,
+                    Promoted::Double(data) => {
+                        self.byte(CUSTOM_DOUBLE)?;
+                        self.bytes(&data.to_le_bytes())?;
+                    }
+                    Promoted::String(Some(data)) => {
+                        self.byte(CUSTOM_STRING)?;
+                        self.c_str(data)?;
+                    }
+                    Promoted::String(None) => self.byte(CUSTOM_NULL)?,
+                    Promoted::Pointer(data) => {
+                        self.byte(CUSTOM_POINTER)?;
+                        self.varint(data.addr() as u64)?;
+                    }
+                }
+                self.byte(conversion.conversion())
+            }
+            #[cfg(feature = "pointer-ext")]
+            Specifier::PointerExt(data) => match data {
+                PointerExt::Ipv4 {
+                    addr,
+                    leading_zeros,
+                } => {
+                    self.bytes(&[IPV4, leading_zeros as u8])?;
+                    self.bytes(addr)
+                }
+                PointerExt::Ipv6 { addr, format } => {
+                    let format = match format {
+                        Ipv6Format::Full => 0,
+                        Ipv6Format::NoColons => 1,
+                        Ipv6Format::Compressed => 2,
+                    };
+                    self.bytes(&[IPV6, format])?;
+                    self.bytes(addr)
+                }
+                PointerExt::Mac {
+                    addr,
+                    separator,
+                    reversed,
+                } => {
+                    self.bytes(&[MAC, reversed as u8])?;
+                    self.varint(separator.map_or(0, |c| u64::from(c) + 1))?;
+                    self.bytes(addr)
+                }
+                PointerExt::Uuid {
+                    bytes,
+                    upper,
+                    little_endian,
+                } => {
+                    self.bytes(&[UUID, upper as u8 | (little_endian as u8) << 1])?;
+                    self.bytes(bytes)
+                }
+                PointerExt::Hex { bytes, separator } => {
+                    self.byte(HEX_DUMP)?;
+                    self.varint(separator.map_or(0, |c| u64::from(c) + 1))?;
+                    self.varint(bytes.len() as u64)?;
+                    self.bytes(bytes)
+                }
+                PointerExt::Error(errno) => {
+                    self.byte(ERROR_POINTER)?;
+                    self.varint(zigzag(errno.into()))
+                }
+            },
+            Specifier::Errno(errno) => {
+                self.byte(ERRNO)?;
+                self.varint(zigzag(errno.into()))
+            }
+            Specifier::Disabled(ch) => self.bytes(&[DISABLED, ch]),
+            Specifier::WriteBytesWritten(_, _) => Err(Error::Forbidden(b'n')),
+        }
+    }
+}
+
+/// Implemented for a reference, so the encoded message can be read after
+/// formatting.
+impl Sink for &mut Encoder<'_> {
+    type Error = Error;
+
+    fn write_literal(&mut self, _text: &[u8]) -> Result<usize, Error> {
+        Ok(0)
+    }
+
+    fn write_argument(&mut self, arg: Argument) -> Result<usize, Error> {
+        if self.full {
+            return Err(Error::BufferFull);
+        }
+        let start = self.len;
+        let modifiers = !arg.flags.is_empty() || arg.width != 0 || arg.precision.is_some();
+        self.encode(arg.specifier)?;
+        if self.len > start && modifiers {
+            self.buf[start] |= MODIFIERS;
+            self.byte(arg.flags.bits())?;
+            self.varint(zigzag(arg.width.into()))?;
+            self.varint(arg.precision.map_or(0, |p| zigzag(p.into()) + 1))?;
+        }
+        Ok(self.len - start)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        if self.full {
+            return Err(Error::BufferFull);
+        }
+        Ok(())
+    }
+}
+
+/// Reads an encoded message.
+struct Reader<'b>(&'b [u8]);
+
+impl<'b> Reader<'b> {
+    fn bytes(&mut self, len: usize) -> Result<&'b [u8], Error> {
+        if len > self.0.len() {
+            return Err(Error::MissingArgument);
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<&'b [u8; N], Error> {
+        let (bytes, rest) = self.0.split_first_chunk().ok_or(Error::MissingArgument)?;
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn varint(&mut self) -> Result<u64, Error> {
+        let mut value = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte < 0x80 {
+                return Ok(value);
+            }
+        }
+        Err(Error::ArgumentType)
+    }
+
+    fn number<T: TryFrom<u64>>(&mut self) -> Result<T, Error> {
+        self.varint()?.try_into().map_err(|_| Error::ArgumentType)
+    }
+
+    fn signed_number<T: TryFrom<i64>>(&mut self) -> Result<T, Error> {
+        unzigzag(self.varint()?)
+            .try_into()
+            .map_err(|_| Error::ArgumentType)
+    }
+
+    fn signed(&mut self, kind: u8) -> Result<SignedInt, Error> {
+        Ok(match kind {
+            0 => SignedInt::Int(self.signed_number()?),
+            1 => SignedInt::Char(self.signed_number()?),
+            2 => SignedInt::Short(self.signed_number()?),
+            3 => SignedInt::Long(self.signed_number()?),
+            4 => SignedInt::LongLong(self.signed_number()?),
+            _ => SignedInt::Isize(self.signed_number()?),
+        })
+    }
+
+    fn unsigned(&mut self, kind: u8) -> Result<UnsignedInt, Error> {
+        Ok(match kind {
+            0 => UnsignedInt::Int(self.number()?),
+            1 => UnsignedInt::Char(self.number()?),
+            2 => UnsignedInt::Short(self.number()?),
+            3 => UnsignedInt::Long(self.number()?),
+            4 => UnsignedInt::LongLong(self.number()?),
+            _ => UnsignedInt::Isize(self.number()?),
+        })
+    }
+
+    fn double(&mut self) -> Result<f64, Error> {
+        Ok(f64::from_le_bytes(*self.array()?))
+    }
+
+    fn c_str(&mut self) -> Result<&'b CStr, Error> {
+        let s = CStr::from_bytes_until_nul(self.0).map_err(|_| Error::MissingArgument)?;
+        self.0 = &self.0[s.count_bytes() + 1..];
+        Ok(s)
+    }
+
+    fn pointer(&mut self) -> Result<*const (), Error> {
+        Ok(core::ptr::without_provenance(self.number()?))
+    }
+
+    #[cfg(feature = "pointer-ext")]
+    fn separator(&mut self) -> Result<Option<char>, Error> {
+        match self.varint()? {
+            0 => Ok(None),
+            c => char::from_u32((c - 1) as u32)
+                .map(Some)
+                .ok_or(Error::ArgumentType),
+        }
+    }
+
+    /// Whether the next argument is a `%n` that was disabled with a cargo
+    /// feature.
+    fn next_is_disabled_n(&self) -> bool {
+        matches!(self.0, [tag, b'n', ..] if tag & !MODIFIERS == DISABLED)
+    }
+
+    fn argument<'w>(
+        &mut self,
+        config: &Config,
+        wide: &'w mut [WChar; MAX_WIDE_STRING],
+    ) -> Result<Argument<'w>, Error>
+    where
+        'b: 'w,
+    {
+        let tag = self.byte()?;
+        let specifier = match tag & !MODIFIERS {
+            tag @ INT..UINT => Specifier::Int(self.signed(tag - INT)?),
+            tag @ UINT..OCTAL => Specifier::Uint(self.unsigned(tag - UINT)?),
+            tag @ OCTAL..HEX => Specifier::Octal(self.unsigned(tag - OCTAL)?),
+            tag @ HEX..UPPER_HEX => Specifier::Hex(self.unsigned(tag - HEX)?),
+            tag @ UPPER_HEX..BINARY => Specifier::UpperHex(self.unsigned(tag - UPPER_HEX)?),
+            tag @ BINARY..UPPER_BINARY => Specifier::Binary(self.unsigned(tag - BINARY)?),
+            tag @ UPPER_BINARY..DOUBLE => {
+                Specifier::UpperBinary(self.unsigned(tag - UPPER_BINARY)?)
+            }
+            tag @ DOUBLE..PERCENT => Specifier::Double {
+                value: self.double()?,
+                format: DOUBLE_FORMATS[usize::from(tag - DOUBLE)],
+            },
+            PERCENT => Specifier::Percent,
+            BYTES => {
+                let len = self.number()?;
+                Specifier::Bytes(self.bytes(len)?)
+            }
+            STRING => Specifier::String(self.c_str()?),
+            CHAR => Specifier::Char(self.byte()? as c_char),
+            WIDE_STRING => {
+                let len = self.number()?;
+                let data = wide.get_mut(..len).ok_or(Error::ArgumentType)?;
+                for c in data.iter_mut() {
+                    *c = self.number()?;
+                }
+                Specifier::WideString(data)
+            }
+            WIDE_CHAR => Specifier::WideChar(self.number()?),
+            POINTER => Specifier::Pointer(self.pointer()?),
+            ERRNO => Specifier::Errno(self.signed_number()?),
+            DISABLED => Specifier::Disabled(self.byte()?),
+            tag @ CUSTOM_INT..IPV4_OR_END => {
+                let value = match tag {
+                    CUSTOM_INT..CUSTOM_UINT => Promoted::Int(self.signed(tag - CUSTOM_INT)?),
+                    CUSTOM_UINT..CUSTOM_DOUBLE => Promoted::Uint(self.unsigned(tag - CUSTOM_UINT)?),
+                    CUSTOM_DOUBLE => Promoted::Double(self.double()?),
+                    CUSTOM_STRING => Promoted::String(Some(self.c_str()?)),
+                    CUSTOM_NULL => Promoted::String(None),
+                    _ => Promoted::Pointer(self.pointer()?),
+                };
+                let ch = self.byte()?;
+                let conversion = config
+                    .conversions
+                    .iter()
+                    .find(|c| c.conversion() == ch)
+                    .ok_or(Error::ArgumentType)?;
+                Specifier::Custom {
+                    conversion: *conversion,
+                    value,
+                }
+            }
+            #[cfg(feature = "pointer-ext")]
+            IPV4 => Specifier::PointerExt(PointerExt::Ipv4 {
+                leading_zeros: self.byte()? != 0,
+                addr: self.array()?,
+            }),
+            #[cfg(feature = "pointer-ext")]
+            IPV6 => Specifier::PointerExt(PointerExt::Ipv6 {
+                format: match self.byte()? {
+                    0 => Ipv6Format::Full,
+                    1 => Ipv6Format::NoColons,
+                    _ => Ipv6Format::Compressed,
+                },
+                addr: self.array()?,
+            }),
+            #[cfg(feature = "pointer-ext")]
+            MAC => Specifier::PointerExt(PointerExt::Mac {
+                reversed: self.byte()? != 0,
+                separator: self.separator()?,
+                addr: self.array()?,
+            }),
+            #[cfg(feature = "pointer-ext")]
+            UUID => {
+                let options = self.byte()?;
+                Specifier::PointerExt(PointerExt::Uuid {
+                    upper: options & 1 != 0,
+                    little_endian: options & 2 != 0,
+                    bytes: self.array()?,
+                })
+            }
+            #[cfg(feature = "pointer-ext")]
+            HEX_DUMP => {
+                let separator = self.separator()?;
+                let len = self.number()?;
+                Specifier::PointerExt(PointerExt::Hex {
+                    separator,
+                    bytes: self.bytes(len)?,
+                })
+            }
+            #[cfg(feature = "pointer-ext")]
+            ERROR_POINTER => Specifier::PointerExt(PointerExt::Error(self.signed_number()?)),
+            _ => return Err(Error::ArgumentType),
+        };
+        let mut arg = Argument::from(specifier);
+        if tag & MODIFIERS != 0 {
+            arg.flags = Flags::from_bits(self.byte()?).ok_or(Error::ArgumentType)?;
+            arg.width = self.signed_number()?;
+            arg.precision = match self.varint()? {
+                0 => None,
+                p => Some(
+                    unzigzag(p - 1)
+                        .try_into()
+                        .map_err(|_| Error::ArgumentType)?,
+                ),
+            };
+        }
+        Ok(arg)
+    }
+}
+
+/// The first tag after the custom conversions.
+const IPV4_OR_END: u8 = CUSTOM_POINTER + 1;
+
+/// Decode a message written by an [`Encoder`], and write it to `sink`.
+///
+/// `formats` looks up the format string for the message's ID, and the
+/// format string is parsed with `config`, which has to match the one that
+/// the message was encoded with. `bytes` is advanced past the message, so a
+/// stream of messages can be decoded by calling this until it's empty.
+///
+/// Returns [`Error::InvalidFormat`] if `formats` doesn't know the ID,
+/// [`Error::MissingArgument`] if the message is cut off, and
+/// [`Error::ArgumentType`] if it's malformed. Arguments aren't checked
+/// against their conversions, so a stale format table decodes to the wrong
+/// text rather than an error, but they are checked against
+/// [`Config::limits`], like arguments that are read from a `VaList`.
+pub fn decode<'f, S: Sink>(
+    bytes: &mut &[u8],
+    formats: impl FnOnce(u64) -> Option<&'f CStr>,
+    config: &Config,
+    sink: S,
+) -> Result<c_int, S::Error>
+where
+    S::Error: From<Error>,
+{
+    let mut reader = Reader(bytes);
+    let format = formats(reader.varint()?).ok_or(Error::InvalidFormat)?;
+    let mut wide = [0; MAX_WIDE_STRING];
+    let mut written = 0;
+    let mut sink = Limited::new(sink, config);
+    for piece in Pieces::new(format.to_bytes(), config) {
+        let res = match piece? {
+            Piece::Text(text) => sink.write_literal(text)?,
+            Piece::Conversion(conv) if conv.is_bytes_written() && config.limits.is_some() => {
+                return Err(Error::Forbidden(b'n').into());
+            }
+            // `%n` is only encoded if it was disabled.
+            Piece::Conversion(conv) if conv.is_bytes_written() && !reader.next_is_disabled_n() => {
+                continue;
+            }
+            Piece::Conversion(conv) => {
+                let arg = reader.argument(config, &mut wide)?;
+                conv.check_decoded(&arg, config, written)?;
+                sink.write_argument(arg)?
+            }
+        };
+        written += res as c_int;
+    }
+    sink.finish()?;
+    *bytes = reader.0;
+    Ok(written)
+}
@@ -1,225 +1,1015 @@
+#[cfg(feature = "positional")]
+mod positional;
+
 use core::ffi::*;
 
-use crate::{Argument, DoubleFormat, Flags, SignedInt, Specifier, UnsignedInt};
-use itertools::Itertools;
-
-fn next_char(sub: &[u8]) -> &[u8] {
-    sub.get(1..).unwrap_or(&[])
-}
-
-/// Parse the [Flags field](https://en.wikipedia.org/wiki/Printf_format_string#Flags_field).
-fn parse_flags(mut sub: &[u8]) -> (Flags, &[u8]) {
-    let mut flags: Flags = Flags::empty();
-    while let Some(&ch) = sub.first() {
-        flags.insert(match ch {
-            b'-' => Flags::LEFT_ALIGN,
-            b'+' => Flags::PREPEND_PLUS,
-            b' ' => Flags::PREPEND_SPACE,
-            b'0' => Flags::PREPEND_ZERO,
-            b'\'' => Flags::THOUSANDS_GROUPING,
-            b'#' => Flags::ALTERNATE_FORM,
-            _ => break,
-        });
-        sub = next_char(sub)
-    }
-    (flags, sub)
-}
-
-/// Parse the [Width field](https://en.wikipedia.org/wiki/Printf_format_string#Width_field).
-unsafe fn parse_width<'a>(mut sub: &'a [u8], args: &mut VaList) -> (c_int, &'a [u8]) {
-    let mut width: c_int = 0;
-    if sub.first() == Some(&b'*') {
-        return (unsafe { args.next_arg() }, next_char(sub));
-    }
-    while let Some(&ch) = sub.first() {
-        match ch {
-            // https://rust-malaysia.github.io/code/2020/07/11/faster-integer-parsing.html#the-bytes-solution
-            b'0'..=b'9' => width = width * 10 + (ch & 0x0f) as c_int,
-            _ => break,
+use printf_compat_spec::{Count, FormatChar, Length, parse_spec};
+
+#[cfg(feature = "n-specifier")]
+use crate::BytesWrittenPolicy;
+#[cfg(feature = "float")]
+use crate::DoubleFormat;
+#[cfg(feature = "wide")]
+use crate::WChar;
+use crate::output::{Limited, Sink, Untyped};
+#[cfg(feature = "pointer-ext")]
+use crate::pointer_ext::{PointerExt, Suffix};
+use crate::{
+    ArgType, Argument, Config, Conversion, Dialect, Error, Flags, Limits, PrintfArg, Promoted,
+    SignedInt, Specifier, UnsignedInt,
+};
+
+#[cfg(feature = "positional")]
+use positional::Positional;
+
+/// A character type that format strings can be made of.
+trait FormatText: FormatChar {
+    /// Write literal text from the format string.
+    fn write_text<S: Sink>(sink: &mut S, text: &[Self]) -> Result<usize, S::Error>;
+}
+
+impl FormatText for u8 {
+    fn write_text<S: Sink>(sink: &mut S, text: &[Self]) -> Result<usize, S::Error> {
+        sink.write_literal(text)
+    }
+}
+
+#[cfg(feature = "wide")]
+impl FormatText for WChar {
+    fn write_text<S: Sink>(sink: &mut S, text: &[Self]) -> Result<usize, S::Error> {
+        sink.write_argument(Specifier::WideText(text).into())
+    }
+}
+
+/// Where a format string's arguments come from.
+///
+/// The methods are unsafe because reading from a [`VaList`] is: the caller
+/// must make sure that the format string matches the arguments.
+trait ArgSource<'a> {
+    /// `*`, `%c`
+    unsafe fn int(&mut self) -> Result<c_int, Error>;
+    /// `%d`, `%i`
+    unsafe fn signed(&mut self, length: Length) -> Result<SignedInt, Error>;
+    /// `%u`, `%o`, `%x`, `%X`
+    unsafe fn unsigned(&mut self, length: Length) -> Result<UnsignedInt, Error>;
+    /// `%f`, `%e`, `%g`, `%a`
+    unsafe fn double(&mut self) -> Result<f64, Error>;
+    /// `%s`
+    unsafe fn string(&mut self) -> Result<Option<&'a CStr>, Error>;
+    /// `%ls`
+    #[cfg(feature = "wide")]
+    unsafe fn wide_string(&mut self) -> Result<Option<&'a [WChar]>, Error>;
+    /// `%lc`
+    #[cfg(feature = "wide")]
+    unsafe fn wide_char(&mut self) -> Result<WChar, Error>;
+    /// `%Z` with [`Dialect::Msvc`]
+    unsafe fn ansi_string(&mut self) -> Result<Option<&'a [u8]>, Error>;
+    /// `%p`, and `%n` when it's not written to
+    unsafe fn pointer(&mut self) -> Result<*const (), Error>;
+    /// The next pointer argument, for `%p` extensions to read through.
+    #[cfg(feature = "pointer-ext")]
+    unsafe fn bytes_pointer(&mut self) -> Result<*const u8, Error>;
+    /// The `len` bytes that the next pointer argument points to, for `%p`
+    /// extensions.
+    #[cfg(feature = "pointer-ext")]
+    unsafe fn bytes(&mut self, len: usize) -> Result<Option<&'a [u8]>, Error> {
+        let arg = unsafe { self.bytes_pointer()? };
+        Ok((!arg.is_null()).then(|| unsafe { core::slice::from_raw_parts(arg, len) }))
+    }
+    /// The next pointer argument, for `%n` to write through.
+    #[cfg(feature = "n-specifier")]
+    unsafe fn count_pointer(&mut self) -> Result<*mut (), Error>;
+    /// Store `count` through the next pointer argument, with the type given
+    /// by the length modifier.
+    #[cfg(feature = "n-specifier")]
+    unsafe fn write_count(&mut self, length: Length, count: c_int) -> Result<(), Error> {
+        unsafe { write_count(self.count_pointer()?, length, count) }
+    }
+    /// Called before a conversion's arguments are read. Arguments are read in
+    /// order, so numbered ones are invalid unless this is overridden.
+    fn start(&mut self, conv: &Conv) -> Result<(), Error> {
+        if conv.is_positional() {
+            return Err(Error::InvalidFormat);
         }
-        sub = next_char(sub);
+        Ok(())
     }
-    (width, sub)
 }
 
-/// Parse the [Precision field](https://en.wikipedia.org/wiki/Printf_format_string#Precision_field).
-unsafe fn parse_precision<'a>(sub: &'a [u8], args: &mut VaList) -> (Option<c_int>, &'a [u8]) {
-    match sub.first() {
-        Some(&b'.') => {
-            let (prec, sub) = unsafe { parse_width(next_char(sub), args) };
-            (Some(prec), sub)
+/// Store `count` through `ptr`, with the type given by the length modifier.
+#[cfg(feature = "n-specifier")]
+unsafe fn write_count(ptr: *mut (), length: Length, count: c_int) -> Result<(), Error> {
+    unsafe fn write<T>(ptr: *mut (), value: T) -> Result<(), Error> {
+        if ptr.is_null() {
+            return Err(Error::NullPointer);
+        }
+        unsafe { ptr.cast::<T>().write_unaligned(value) };
+        Ok(())
+    }
+    unsafe {
+        match length {
+            Length::Int => write(ptr, count),
+            Length::Char => write(ptr, count as c_schar),
+            Length::Short => write(ptr, count as c_short),
+            Length::Long => write(ptr, count as c_long),
+            Length::LongLong => write(ptr, count as c_longlong),
+            Length::Usize | Length::Isize => write(ptr, count as isize),
         }
-        _ => (None, sub),
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-enum Length {
-    Int,
-    /// `hh`
-    Char,
-    /// `h`
-    Short,
-    /// `l`
-    Long,
-    /// `ll`
-    LongLong,
-    /// `z`
-    Usize,
-    /// `t`
-    Isize,
-}
-
-impl Length {
-    unsafe fn parse_signed(self, args: &mut VaList) -> SignedInt {
-        match self {
-            Length::Int => SignedInt::Int(unsafe { args.next_arg() }),
-            Length::Char => SignedInt::Char(unsafe { args.next_arg::<c_int>() } as c_schar),
-            Length::Short => SignedInt::Short(unsafe { args.next_arg::<c_int>() } as c_short),
-            Length::Long => SignedInt::Long(unsafe { args.next_arg() }),
-            Length::LongLong => SignedInt::LongLong(unsafe { args.next_arg() }),
+impl<'a> ArgSource<'a> for VaList<'_> {
+    unsafe fn int(&mut self) -> Result<c_int, Error> {
+        Ok(unsafe { self.next_arg() })
+    }
+    unsafe fn signed(&mut self, length: Length) -> Result<SignedInt, Error> {
+        Ok(match length {
+            Length::Int => SignedInt::Int(unsafe { self.next_arg() }),
+            Length::Char => SignedInt::Char(unsafe { self.next_arg::<c_int>() } as c_schar),
+            Length::Short => SignedInt::Short(unsafe { self.next_arg::<c_int>() } as c_short),
+            Length::Long => SignedInt::Long(unsafe { self.next_arg() }),
+            Length::LongLong => SignedInt::LongLong(unsafe { self.next_arg() }),
             // for some reason, these exist as different options, yet produce the same output
-            Length::Usize | Length::Isize => SignedInt::Isize(unsafe { args.next_arg() }),
-        }
+            Length::Usize | Length::Isize => SignedInt::Isize(unsafe { self.next_arg() }),
+        })
     }
-    unsafe fn parse_unsigned(self, args: &mut VaList) -> UnsignedInt {
-        match self {
-            Length::Int => UnsignedInt::Int(unsafe { args.next_arg() }),
-            Length::Char => UnsignedInt::Char(unsafe { args.next_arg::<c_uint>() } as c_uchar),
-            Length::Short => UnsignedInt::Short(unsafe { args.next_arg::<c_uint>() } as c_ushort),
-            Length::Long => UnsignedInt::Long(unsafe { args.next_arg() }),
-            Length::LongLong => UnsignedInt::LongLong(unsafe { args.next_arg() }),
+    unsafe fn unsigned(&mut self, length: Length) -> Result<UnsignedInt, Error> {
+        Ok(match length {
+            Length::Int => UnsignedInt::Int(unsafe { self.next_arg() }),
+            Length::Char => UnsignedInt::Char(unsafe { self.next_arg::<c_uint>() } as c_uchar),
+            Length::Short => UnsignedInt::Short(unsafe { self.next_arg::<c_uint>() } as c_ushort),
+            Length::Long => UnsignedInt::Long(unsafe { self.next_arg() }),
+            Length::LongLong => UnsignedInt::LongLong(unsafe { self.next_arg() }),
             // for some reason, these exist as different options, yet produce the same output
-            Length::Usize | Length::Isize => UnsignedInt::Isize(unsafe { args.next_arg() }),
+            Length::Usize | Length::Isize => UnsignedInt::Isize(unsafe { self.next_arg() }),
+        })
+    }
+    unsafe fn double(&mut self) -> Result<f64, Error> {
+        Ok(unsafe { self.next_arg() })
+    }
+    unsafe fn string(&mut self) -> Result<Option<&'a CStr>, Error> {
+        let arg: *const c_char = unsafe { self.next_arg() };
+        Ok((!arg.is_null()).then(|| unsafe { CStr::from_ptr(arg) }))
+    }
+    #[cfg(feature = "wide")]
+    unsafe fn wide_string(&mut self) -> Result<Option<&'a [WChar]>, Error> {
+        let arg: *const WChar = unsafe { self.next_arg() };
+        Ok((!arg.is_null()).then(|| unsafe { wide_str(arg) }))
+    }
+    #[cfg(feature = "wide")]
+    unsafe fn wide_char(&mut self) -> Result<WChar, Error> {
+        // `wint_t` is at least as large as `int`, or promoted to it.
+        Ok(unsafe { self.next_arg::<c_uint>() } as WChar)
+    }
+    unsafe fn ansi_string(&mut self) -> Result<Option<&'a [u8]>, Error> {
+        /// `ANSI_STRING` from the Windows headers.
+        #[repr(C)]
+        struct AnsiString {
+            length: c_ushort,
+            maximum_length: c_ushort,
+            buffer: *const c_char,
         }
+        let arg: *const AnsiString = unsafe { self.next_arg() };
+        Ok(unsafe { arg.as_ref() }
+            .filter(|s| !s.buffer.is_null())
+            .map(|s| unsafe { core::slice::from_raw_parts(s.buffer.cast(), s.length.into()) }))
+    }
+    unsafe fn pointer(&mut self) -> Result<*const (), Error> {
+        Ok(unsafe { self.next_arg() })
+    }
+    #[cfg(feature = "pointer-ext")]
+    unsafe fn bytes_pointer(&mut self) -> Result<*const u8, Error> {
+        Ok(unsafe { self.next_arg() })
+    }
+    #[cfg(feature = "n-specifier")]
+    unsafe fn count_pointer(&mut self) -> Result<*mut (), Error> {
+        Ok(unsafe { self.next_arg() })
     }
 }
 
-/// Parse the [Length field](https://en.wikipedia.org/wiki/Printf_format_string#Length_field).
-fn parse_length(sub: &[u8]) -> (Length, &[u8]) {
-    match sub.first().copied() {
-        Some(b'h') => match sub.get(1).copied() {
-            Some(b'h') => (Length::Char, sub.get(2..).unwrap_or(&[])),
-            _ => (Length::Short, next_char(sub)),
-        },
-        Some(b'l') => match sub.get(1).copied() {
-            Some(b'l') => (Length::LongLong, sub.get(2..).unwrap_or(&[])),
-            _ => (Length::Long, next_char(sub)),
-        },
-        Some(b'z') => (Length::Usize, next_char(sub)),
-        Some(b't') => (Length::Isize, next_char(sub)),
-        _ => (Length::Int, sub),
+/// Arguments passed as a slice of Rust values. Every method is safe to call,
+/// and a missing or mismatched argument is an error.
+struct SliceArgs<'a>(core::slice::Iter<'a, &'a dyn PrintfArg>);
+
+impl<'a> SliceArgs<'a> {
+    fn next(&mut self) -> Result<Promoted<'a>, Error> {
+        let arg: &'a dyn PrintfArg = *self.0.next().ok_or(Error::MissingArgument)?;
+        Ok(arg.promote())
+    }
+
+    /// Read an integer as C would with `va_arg`, allowing either signedness.
+    fn integer(&mut self) -> Result<i64, Error> {
+        match self.next()? {
+            Promoted::Int(x) => Ok(x.into()),
+            Promoted::Uint(x) => Ok(u64::from(x) as i64),
+            _ => Err(Error::ArgumentType),
+        }
+    }
+}
+
+/// `x`, truncated to the type given by the length modifier.
+// Some casts are only needed on some platforms.
+#[allow(clippy::unnecessary_cast)]
+fn signed_int(length: Length, x: i64) -> SignedInt {
+    match length {
+        Length::Int => SignedInt::Int(x as c_int),
+        Length::Char => SignedInt::Char(x as c_schar),
+        Length::Short => SignedInt::Short(x as c_short),
+        Length::Long => SignedInt::Long(x as c_long),
+        Length::LongLong => SignedInt::LongLong(x as c_longlong),
+        Length::Usize | Length::Isize => SignedInt::Isize(x as isize),
+    }
+}
+
+/// `x`, truncated to the type given by the length modifier.
+#[allow(clippy::unnecessary_cast)]
+fn unsigned_int(length: Length, x: u64) -> UnsignedInt {
+    match length {
+        Length::Int => UnsignedInt::Int(x as c_uint),
+        Length::Char => UnsignedInt::Char(x as c_uchar),
+        Length::Short => UnsignedInt::Short(x as c_ushort),
+        Length::Long => UnsignedInt::Long(x as c_ulong),
+        Length::LongLong => UnsignedInt::LongLong(x as c_ulonglong),
+        Length::Usize | Length::Isize => UnsignedInt::Isize(x as usize),
+    }
+}
+
+// Some casts are only needed on some platforms.
+#[allow(clippy::unnecessary_cast)]
+impl<'a> ArgSource<'a> for SliceArgs<'a> {
+    unsafe fn int(&mut self) -> Result<c_int, Error> {
+        Ok(self.integer()? as c_int)
+    }
+    unsafe fn signed(&mut self, length: Length) -> Result<SignedInt, Error> {
+        Ok(signed_int(length, self.integer()?))
+    }
+    unsafe fn unsigned(&mut self, length: Length) -> Result<UnsignedInt, Error> {
+        Ok(unsigned_int(length, self.integer()? as u64))
+    }
+    unsafe fn double(&mut self) -> Result<f64, Error> {
+        match self.next()? {
+            Promoted::Double(x) => Ok(x),
+            _ => Err(Error::ArgumentType),
+        }
+    }
+    unsafe fn string(&mut self) -> Result<Option<&'a CStr>, Error> {
+        match self.next()? {
+            Promoted::String(s) => Ok(s),
+            _ => Err(Error::ArgumentType),
+        }
+    }
+    #[cfg(feature = "wide")]
+    unsafe fn wide_string(&mut self) -> Result<Option<&'a [WChar]>, Error> {
+        Err(Error::ArgumentType)
+    }
+    #[cfg(feature = "wide")]
+    unsafe fn wide_char(&mut self) -> Result<WChar, Error> {
+        Ok(self.integer()? as WChar)
+    }
+    unsafe fn ansi_string(&mut self) -> Result<Option<&'a [u8]>, Error> {
+        // There's no Rust type for it, and reading a raw pointer isn't safe.
+        Err(Error::ArgumentType)
+    }
+    unsafe fn pointer(&mut self) -> Result<*const (), Error> {
+        match self.next()? {
+            Promoted::Pointer(p) => Ok(p),
+            Promoted::String(s) => Ok(s.map_or(core::ptr::null(), |s| s.as_ptr().cast())),
+            _ => Err(Error::ArgumentType),
+        }
+    }
+    #[cfg(feature = "pointer-ext")]
+    unsafe fn bytes_pointer(&mut self) -> Result<*const u8, Error> {
+        // Reading through a pointer isn't safe.
+        Err(Error::ArgumentType)
+    }
+    #[cfg(feature = "n-specifier")]
+    unsafe fn count_pointer(&mut self) -> Result<*mut (), Error> {
+        // Writing through a pointer isn't safe.
+        Err(Error::Forbidden(b'n'))
+    }
+}
+
+/// The wide string at `ptr`, without its null terminator.
+///
+/// # Safety
+///
+/// `ptr` must point to a null-terminated wide string that lives for `'a`.
+#[cfg(feature = "wide")]
+unsafe fn wide_str<'a>(ptr: *const WChar) -> &'a [WChar] {
+    let mut len = 0;
+    while unsafe { *ptr.add(len) } != 0 {
+        len += 1;
+    }
+    unsafe { core::slice::from_raw_parts(ptr, len) }
+}
+
+/// Read a width or precision, taking it from the arguments if it's `*`.
+unsafe fn resolve<'a>(count: Count, args: &mut impl ArgSource<'a>) -> Result<c_int, Error> {
+    match count {
+        Count::Value(value) => Ok(value),
+        Count::Arg | Count::Position(_) => unsafe { args.int() },
     }
 }
 
 /// Parse a format parameter and write it somewhere.
 ///
+/// This uses the default [`Config`]; see [`format_with_config`] to change it.
+/// `handler` is called with each run of literal text and each conversion; to
+/// write to a [`Sink`] instead, use [`format_sink`].
+///
 /// # Safety
 ///
 /// [`VaList`]s are *very* unsafe. The passed `format` and `args` parameter must be a valid [`printf` format string](http://www.cplusplus.com/reference/cstdio/printf/).
 pub unsafe fn format(
     format: *const c_char,
-    mut args: VaList,
-    mut handler: impl FnMut(Argument) -> c_int,
+    args: VaList,
+    handler: impl FnMut(Argument) -> c_int,
 ) -> c_int {
-    let str = unsafe { CStr::from_ptr(format).to_bytes() };
-    let mut iter = str.split(|&c| c == b'%');
-    let mut written = 0;
+    unsafe { format_sink(format, args, handler) }
+}
 
-    macro_rules! err {
-        ($ex: expr) => {{
-            let res = $ex;
-            if res < 0 {
-                return -1;
-            } else {
-                written += res;
-            }
-        }};
+/// Parse a format parameter and write it to a [`Sink`].
+///
+/// This uses the default [`Config`]; see [`format_with_config`] to change it.
+///
+/// # Safety
+///
+/// [`VaList`]s are *very* unsafe. The passed `format` and `args` parameter must be a valid [`printf` format string](http://www.cplusplus.com/reference/cstdio/printf/).
+pub unsafe fn format_sink(format: *const c_char, args: VaList, sink: impl Sink) -> c_int {
+    unsafe { format_with_config(format, args, &Config::new(), sink) }
+}
+
+/// Parse a format parameter and write it somewhere, with the given [`Config`].
+///
+/// # Safety
+///
+/// [`VaList`]s are *very* unsafe. The passed `format` and `args` parameter must be a valid [`printf` format string](http://www.cplusplus.com/reference/cstdio/printf/).
+pub unsafe fn format_with_config(
+    format: *const c_char,
+    args: VaList,
+    config: &Config,
+    sink: impl Sink,
+) -> c_int {
+    unsafe { try_format_with_config(format, args, config, Untyped(sink)) }.unwrap_or(-1)
+}
+
+/// [`format_with_config`], returning why it failed instead of -1.
+pub(crate) unsafe fn try_format_with_config<S: Sink>(
+    format: *const c_char,
+    args: VaList,
+    config: &Config,
+    sink: S,
+) -> Result<c_int, S::Error>
+where
+    S::Error: From<Error>,
+{
+    let format = unsafe { CStr::from_ptr(format) };
+    unsafe { format_impl(format.to_bytes(), args, config, sink) }
+}
+
+/// Parse a wide format string, like `swprintf`, and write it somewhere.
+///
+/// Literal text is passed to the sink as [`Specifier::WideText`]. As in C,
+/// `%s` and `%c` still take narrow strings and characters, and `%ls` and
+/// `%lc` take wide ones. [`output::fmt_write`][crate::output::fmt_write]
+/// writes all of them as UTF-8, and
+/// [`output::wide_buffer`][crate::output::wide_buffer] writes them to a
+/// [`WChar`] buffer.
+///
+/// # Safety
+///
+/// [`VaList`]s are *very* unsafe. The passed `format` and `args` parameter must be a valid [`printf` format string](http://www.cplusplus.com/reference/cstdio/printf/).
+#[cfg(feature = "wide")]
+pub unsafe fn format_wide(format: *const WChar, args: VaList, sink: impl Sink) -> c_int {
+    let format = unsafe { wide_str(format) };
+    unsafe { format_impl(format, args, &Config::new(), Untyped(sink)) }.unwrap_or(-1)
+}
+
+/// Parse a format string that may be controlled by an attacker, and write it
+/// somewhere.
+///
+/// The width, precision and total output are capped by `limits`, `%n` is
+/// forbidden, and `%s` can be forbidden with [`Limits::allow_dereference`].
+/// When a limit is hit, formatting stops and the matching [`Error`] is
+/// returned.
+///
+/// ```rust
+/// # #![feature(c_variadic)]
+/// # use core::ffi::{c_char, c_int};
+/// use printf_compat::{Limits, format_untrusted, output};
+///
+/// unsafe extern "C" fn log_message(str: *const c_char, args: ...) -> c_int {
+///     const LIMITS: Limits = Limits::new().max_output(1024);
+///     let mut s = String::new();
+///     let res = unsafe { format_untrusted(str, args, &LIMITS, output::fmt_write(&mut s)) };
+///     println!("{}", s);
+///     res.unwrap_or(-1)
+/// }
+/// ```
+///
+/// # Safety
+///
+/// The arguments still have to match the format string: this can't stop a
+/// malicious format string from reading more arguments than were passed. The
+/// limits make sure that doing so can't write to memory, and, if
+/// dereferencing is forbidden, can't read through the bogus arguments either.
+pub unsafe fn format_untrusted<S: Sink>(
+    format: *const c_char,
+    args: VaList,
+    limits: &Limits,
+    sink: S,
+) -> Result<c_int, S::Error>
+where
+    S::Error: From<Error>,
+{
+    unsafe { try_format_with_config(format, args, &Config::new().limits(*limits), sink) }
+}
+
+/// Parse a format string and write it somewhere, taking the arguments from a
+/// slice of Rust values instead of a [`VaList`].
+///
+/// Since every argument knows its type, this is safe: a missing argument
+/// returns [`Error::MissingArgument`], and one that doesn't match its
+/// conversion returns [`Error::ArgumentType`]. `%n` is always forbidden.
+///
+/// ```rust
+/// use printf_compat::{format_slice, output};
+///
+/// let mut s = String::new();
+/// let res = format_slice(c"%5d|%-4x|", &[&1, &255u8], output::fmt_write(&mut s));
+/// assert_eq!(res, Ok(11));
+/// assert_eq!(s, "    1|ff  |");
+/// ```
+pub fn format_slice<S: Sink>(
+    format: &CStr,
+    args: &[&dyn PrintfArg],
+    sink: S,
+) -> Result<c_int, S::Error>
+where
+    S::Error: From<Error>,
+{
+    format_slice_with_config(format, args, &Config::new(), sink)
+}
+
+/// [`format_slice`], with the given [`Config`].
+pub(crate) fn format_slice_with_config<S: Sink>(
+    format: &CStr,
+    args: &[&dyn PrintfArg],
+    config: &Config,
+    sink: S,
+) -> Result<c_int, S::Error>
+where
+    S::Error: From<Error>,
+{
+    let args = SliceArgs(args.iter());
+    // SAFETY: `SliceArgs` checks every argument.
+    unsafe { format_impl(format.to_bytes(), args, config, sink) }
+}
+
+/// Format a string like C's `sprintf`, taking the arguments from a slice of
+/// Rust values. See [`format_slice`] for details.
+///
+/// ```rust
+/// use printf_compat::{Error, sprintf};
+///
+/// assert_eq!(sprintf(c"%03d|%s", &[&7, &c"seven"]).unwrap(), "007|seven");
+/// assert_eq!(sprintf(c"%d %d", &[&7]), Err(Error::MissingArgument));
+/// assert_eq!(sprintf(c"%s", &[&7]), Err(Error::ArgumentType));
+/// ```
+#[cfg(feature = "std")]
+pub fn sprintf(format: &CStr, args: &[&dyn PrintfArg]) -> Result<String, Error> {
+    let mut s = String::new();
+    format_slice(format, args, crate::output::fmt_sink(&mut s))?;
+    Ok(s)
+}
+
+/// Read the argument of a custom [`Conversion`].
+unsafe fn read_custom<'a>(
+    args: &mut impl ArgSource<'a>,
+    conversion: &Conversion,
+    length: Length,
+    config: &Config,
+) -> Result<Promoted<'a>, Error> {
+    Ok(match conversion.arg_type() {
+        ArgType::Int => Promoted::Int(unsafe { args.signed(length)? }),
+        ArgType::Uint => Promoted::Uint(unsafe { args.unsigned(length)? }),
+        ArgType::Double => Promoted::Double(unsafe { args.double()? }),
+        ArgType::String if config.limits.is_some_and(|l| !l.dereference) => {
+            return Err(Error::Forbidden(conversion.conversion()));
+        }
+        ArgType::String => Promoted::String(unsafe { args.string()? }),
+        ArgType::Pointer => Promoted::Pointer(unsafe { args.pointer()? }),
+    })
+}
+
+/// A conversion specification, checked against the [`Config`], but without
+/// its arguments.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Conv {
+    /// The argument's position, from `n$`, if it's numbered.
+    position: Option<usize>,
+    flags: Flags,
+    width: Count,
+    precision: Option<Count>,
+    /// The length modifier, after Microsoft's `%S` and `%C` are taken into
+    /// account.
+    length: Length,
+    /// The length modifier as written, which custom conversions get.
+    spec_length: Length,
+    conversion: u8,
+    custom: Option<Conversion>,
+    #[cfg(feature = "pointer-ext")]
+    suffix: Option<Suffix>,
+}
+
+impl Conv {
+    /// Whether this is `%n`, rather than a custom conversion.
+    pub(crate) fn is_bytes_written(&self) -> bool {
+        self.custom.is_none() && self.conversion == b'n'
+    }
+
+    /// Check an argument that was decoded for this conversion, rather than
+    /// read by [`convert`], against the limits in `config`, with `written`
+    /// bytes before it.
+    pub(crate) fn check_decoded(
+        &self,
+        arg: &Argument,
+        config: &Config,
+        written: c_int,
+    ) -> Result<(), Error> {
+        let Some(limits) = config.limits else {
+            return Ok(());
+        };
+        check_limits(config, arg.width, arg.precision, written)?;
+        let dereferenced = match arg.specifier {
+            Specifier::Bytes(_) | Specifier::String(_) | Specifier::WideString(_) => true,
+            Specifier::Custom { value, .. } => matches!(value, Promoted::String(_)),
+            #[cfg(feature = "pointer-ext")]
+            Specifier::PointerExt(ext) => !matches!(ext, PointerExt::Error(_)),
+            _ => false,
+        };
+        if dereferenced && !limits.dereference {
+            return Err(Error::Forbidden(self.conversion));
+        }
+        Ok(())
+    }
+
+    /// Whether any of the arguments it reads are numbered.
+    fn is_positional(&self) -> bool {
+        self.position.is_some()
+            || matches!(self.width, Count::Position(_))
+            || matches!(self.precision, Some(Count::Position(_)))
+    }
+}
+
+/// A part of a format string.
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum Piece<'s, C> {
+    /// Literal text.
+    Text(&'s [C]),
+    Conversion(Conv),
+}
+
+/// What [`Pieces`] is looking at.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum State {
+    /// A run of literal text, up to the next `%` or the end of the string.
+    Text,
+    /// A `%`, which starts a conversion specification.
+    Conversion,
+    /// The end of the string, or an error.
+    Done,
+}
+
+/// Splits a format string into [`Piece`]s in one pass, stopping at the first
+/// error.
+///
+/// Literal text is never empty, and `%%` is part of the text before it, so
+/// that the sink is called as few times as possible. A conversion
+/// specification is parsed from the rest of the string, so one like `%-5%`
+/// is a conversion like any other.
+#[derive(Clone)]
+pub(crate) struct Pieces<'s, 'c, C: FormatChar> {
+    /// The part of the format string that hasn't been split yet.
+    str: &'s [C],
+    config: &'c Config,
+    state: State,
+}
+
+impl<'s, 'c, C: FormatChar> Pieces<'s, 'c, C> {
+    pub(crate) fn new(str: &'s [C], config: &'c Config) -> Self {
+        Pieces {
+            str,
+            config,
+            state: State::Text,
+        }
     }
-    if let Some(begin) = iter.next() {
-        err!(handler(Specifier::Bytes(begin).into()));
+
+    /// Whether the `%` at `index` is followed by another, which prints a `%`
+    /// without needing a sink call of its own. A custom `%` conversion
+    /// always gets one.
+    fn is_escaped_percent(&self, index: usize) -> bool {
+        self.str
+            .get(index + 1)
+            .is_some_and(|c| c.to_ascii() == b'%')
+            && !self
+                .config
+                .conversions
+                .iter()
+                .any(|c| c.conversion() == b'%')
     }
-    let mut last_was_percent = false;
-    for (sub, next) in iter.map(Some).chain(core::iter::once(None)).tuple_windows() {
-        let sub = match sub {
-            Some(sub) => sub,
-            None => break,
+
+    /// Parse the conversion specification at the start of `sub`, just after
+    /// the `%`, returning it and the rest of the string.
+    fn conversion(&self, sub: &'s [C]) -> Result<(Conv, &'s [C]), Error> {
+        let config = self.config;
+        // `%p` extensions take their suffix from the text after the conversion.
+        #[cfg_attr(not(feature = "pointer-ext"), allow(unused_mut))]
+        let (spec, mut rest) =
+            parse_spec(sub, config.dialect.extensions()).ok_or(Error::InvalidFormat)?;
+        let ch = spec.conversion.ok_or(Error::InvalidFormat)?;
+        let custom = config.conversions.iter().find(|c| c.conversion() == ch);
+        let (ch, length) = match ch {
+            // Microsoft's `%S` and `%C` are `%s` and `%c` of the other width.
+            b'S' | b'C' if config.dialect == Dialect::Msvc => match spec.length {
+                Length::Short => (ch.to_ascii_lowercase(), Length::Int),
+                _ => (ch.to_ascii_lowercase(), Length::Long),
+            },
+            _ => (ch, spec.length),
+        };
+        #[cfg(feature = "pointer-ext")]
+        let suffix = match ch {
+            b'p' if config.pointer_extensions && custom.is_none() => {
+                let len = rest
+                    .iter()
+                    .take_while(|c| c.to_ascii().is_ascii_alphanumeric())
+                    .count();
+                let suffix = Suffix::parse(&rest[..len]);
+                rest = &rest[len..];
+                suffix
+            }
+            _ => None,
         };
-        if last_was_percent {
-            err!(handler(Specifier::Bytes(sub).into()));
-            last_was_percent = false;
-            continue;
+        let conv = Conv {
+            position: spec.position,
+            flags: spec.flags,
+            width: spec.width,
+            precision: spec.precision,
+            length,
+            spec_length: spec.length,
+            conversion: ch,
+            custom: custom.copied(),
+            #[cfg(feature = "pointer-ext")]
+            suffix,
+        };
+        Ok((conv, rest))
+    }
+}
+
+impl<'s, C: FormatChar> Iterator for Pieces<'s, '_, C> {
+    type Item = Result<Piece<'s, C>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.state {
+            State::Text => {
+                let end = self
+                    .str
+                    .iter()
+                    .position(|c| c.to_ascii() == b'%')
+                    .unwrap_or(self.str.len());
+                let (text, rest) = if self.is_escaped_percent(end) {
+                    // Keep the first `%` of `%%` as part of the text, and
+                    // skip the second.
+                    (&self.str[..=end], &self.str[end + 2..])
+                } else {
+                    let (text, rest) = self.str.split_at(end);
+                    if !rest.is_empty() {
+                        self.state = State::Conversion;
+                    }
+                    (text, rest)
+                };
+                self.str = rest;
+                if self.str.is_empty() {
+                    self.state = State::Done;
+                }
+                if text.is_empty() {
+                    return self.next();
+                }
+                Some(Ok(Piece::Text(text)))
+            }
+            State::Conversion => match self.conversion(&self.str[1..]) {
+                Ok((conv, rest)) => {
+                    self.str = rest;
+                    self.state = State::Text;
+                    Some(Ok(Piece::Conversion(conv)))
+                }
+                Err(e) => {
+                    self.state = State::Done;
+                    Some(Err(e))
+                }
+            },
+            State::Done => None,
         }
-        let (flags, sub) = parse_flags(sub);
-        let (width, sub) = unsafe { parse_width(sub, &mut args) };
-        let (precision, sub) = unsafe { parse_precision(sub, &mut args) };
-        let (length, sub) = parse_length(sub);
-        let ch = sub
-            .first()
-            .unwrap_or(if next.is_some() { &b'%' } else { &0 });
-        err!(handler(Argument {
-            flags,
-            width,
-            precision,
-            specifier: match ch {
-                b'%' => {
-                    last_was_percent = true;
-                    Specifier::Percent
+    }
+}
+
+unsafe fn format_impl<'a, C: FormatText, S: Sink>(
+    str: &[C],
+    args: impl ArgSource<'a>,
+    config: &Config,
+    sink: S,
+) -> Result<c_int, S::Error>
+where
+    S::Error: From<Error>,
+{
+    unsafe { run(Pieces::new(str, config), args, config, sink) }
+}
+
+/// Format the pieces of a [`CompiledFormat`][crate::CompiledFormat].
+pub(crate) unsafe fn run_compiled<S: Sink>(
+    pieces: &[Piece<'_, u8>],
+    args: VaList,
+    config: &Config,
+    sink: S,
+) -> Result<c_int, S::Error>
+where
+    S::Error: From<Error>,
+{
+    unsafe { run(pieces.iter().copied().map(Ok), args, config, sink) }
+}
+
+/// [`run_compiled`], taking the arguments from a slice.
+pub(crate) fn run_compiled_slice<S: Sink>(
+    pieces: &[Piece<'_, u8>],
+    args: &[&dyn PrintfArg],
+    config: &Config,
+    sink: S,
+) -> Result<c_int, S::Error>
+where
+    S::Error: From<Error>,
+{
+    let args = SliceArgs(args.iter());
+    // SAFETY: `SliceArgs` checks every argument.
+    unsafe { run(pieces.iter().copied().map(Ok), args, config, sink) }
+}
+
+/// Read the arguments for each of `pieces`, and write them to `sink`.
+unsafe fn run<'s, 'a, C: FormatText + 's, S: Sink>(
+    pieces: impl Iterator<Item = Result<Piece<'s, C>, Error>> + Clone,
+    #[cfg_attr(not(feature = "positional"), allow(unused_mut))] mut args: impl ArgSource<'a>,
+    config: &Config,
+    sink: S,
+) -> Result<c_int, S::Error>
+where
+    S::Error: From<Error>,
+{
+    #[cfg(feature = "positional")]
+    if let Some(args) = unsafe { Positional::read(pieces.clone(), &mut args, config)? } {
+        return unsafe { write_pieces(pieces, args, config, sink) };
+    }
+    unsafe { write_pieces(pieces, args, config, sink) }
+}
+
+/// [`run`], once it's known how the arguments are read.
+unsafe fn write_pieces<'s, 'a, C: FormatText + 's, S: Sink>(
+    pieces: impl Iterator<Item = Result<Piece<'s, C>, Error>>,
+    mut args: impl ArgSource<'a>,
+    config: &Config,
+    sink: S,
+) -> Result<c_int, S::Error>
+where
+    S::Error: From<Error>,
+{
+    let mut written = 0;
+
+    let mut sink = Limited::new(sink, config);
+    // Read `errno` before the sink has a chance to change it.
+    #[cfg(feature = "m-specifier")]
+    let errno = config.errno.map(|errno| (errno.0)());
+    #[cfg(not(feature = "m-specifier"))]
+    let errno = None;
+
+    for piece in pieces {
+        let res = match piece? {
+            Piece::Text(text) => C::write_text(&mut sink, text)?,
+            Piece::Conversion(conv) => {
+                args.start(&conv)?;
+                match unsafe { convert(&conv, &mut args, config, written, errno)? } {
+                    Some(arg) => sink.write_argument(arg)?,
+                    None => continue,
                 }
-                b'd' | b'i' => Specifier::Int(unsafe { length.parse_signed(&mut args) }),
-                b'x' => Specifier::Hex(unsafe { length.parse_unsigned(&mut args) }),
-                b'X' => Specifier::UpperHex(unsafe { length.parse_unsigned(&mut args) }),
-                b'u' => Specifier::Uint(unsafe { length.parse_unsigned(&mut args) }),
-                b'o' => Specifier::Octal(unsafe { length.parse_unsigned(&mut args) }),
-                b'f' | b'F' => Specifier::Double {
-                    value: unsafe { args.next_arg() },
-                    format: DoubleFormat::Normal.set_upper(ch.is_ascii_uppercase()),
-                },
-                b'e' | b'E' => Specifier::Double {
-                    value: unsafe { args.next_arg() },
-                    format: DoubleFormat::Scientific.set_upper(ch.is_ascii_uppercase()),
-                },
-                b'g' | b'G' => Specifier::Double {
-                    value: unsafe { args.next_arg() },
-                    format: DoubleFormat::Auto.set_upper(ch.is_ascii_uppercase()),
-                },
-                b'a' | b'A' => Specifier::Double {
-                    value: unsafe { args.next_arg() },
-                    format: DoubleFormat::Hex.set_upper(ch.is_ascii_uppercase()),
-                },
-                b's' => {
-                    let arg: *mut c_char = unsafe { args.next_arg() };
-                    // As a common extension supported by glibc, musl, and
-                    // others, format a NULL pointer as "(null)".
-                    if arg.is_null() {
-                        Specifier::Bytes(b"(null)")
-                    } else {
-                        Specifier::String(unsafe { CStr::from_ptr(arg) })
+            }
+        };
+        written += res as c_int;
+    }
+    sink.finish()?;
+    Ok(written)
+}
+
+/// Check a conversion's width and precision against the limits in `config`,
+/// with `written` bytes before it.
+fn check_limits(
+    config: &Config,
+    width: c_int,
+    precision: Option<c_int>,
+    written: c_int,
+) -> Result<(), Error> {
+    if let Some(limits) = config.limits {
+        if width > limits.max_width {
+            return Err(Error::WidthLimit);
+        }
+        if precision.is_some_and(|p| p > limits.max_precision) {
+            return Err(Error::PrecisionLimit);
+        }
+        if written as usize + width as usize > limits.max_output {
+            return Err(Error::OutputLimit);
+        }
+    }
+    Ok(())
+}
+
+/// Read a conversion's arguments, returning what to pass to the sink, if
+/// anything.
+#[cfg_attr(not(feature = "m-specifier"), allow(unused_variables))]
+unsafe fn convert<'a>(
+    conv: &Conv,
+    args: &mut impl ArgSource<'a>,
+    config: &Config,
+    written: c_int,
+    errno: Option<c_int>,
+) -> Result<Option<Argument<'a>>, Error> {
+    let mut flags = conv.flags;
+    let mut width = unsafe { resolve(conv.width, args)? };
+    if width < 0 {
+        // A negative width taken from the arguments is a `-` flag followed
+        // by a positive width.
+        flags.insert(Flags::LEFT_ALIGN);
+        width = width.checked_neg().ok_or(Error::InvalidFormat)?;
+    }
+    // A negative precision taken from the arguments is treated as if the
+    // precision were omitted.
+    let precision = match conv.precision {
+        Some(p) => Some(unsafe { resolve(p, args)? }).filter(|&p| p >= 0),
+        None => None,
+    };
+    check_limits(config, width, precision, written)?;
+    let (ch, length) = (conv.conversion, conv.length);
+    let specifier = match ch {
+        _ if let Some(conversion) = conv.custom => Some(Specifier::Custom {
+            conversion,
+            value: unsafe { read_custom(args, &conversion, conv.spec_length, config)? },
+        }),
+        b'n' if config.limits.is_some() => return Err(Error::Forbidden(b'n')),
+        #[cfg(feature = "n-specifier")]
+        b'n' => match config.bytes_written {
+            BytesWrittenPolicy::Reject => return Err(Error::Forbidden(b'n')),
+            BytesWrittenPolicy::Ignore => {
+                unsafe { args.pointer()? };
+                None
+            }
+            BytesWrittenPolicy::Write => {
+                unsafe { args.write_count(length, written)? };
+                None
+            }
+            BytesWrittenPolicy::Handler => Some(Specifier::WriteBytesWritten(written, unsafe {
+                args.pointer()?.cast()
+            })),
+        },
+        _ => Some(match ch {
+            b'%' => Specifier::Percent,
+            b'd' | b'i' => Specifier::Int(unsafe { args.signed(length)? }),
+            b'x' => Specifier::Hex(unsafe { args.unsigned(length)? }),
+            b'X' => Specifier::UpperHex(unsafe { args.unsigned(length)? }),
+            b'u' => Specifier::Uint(unsafe { args.unsigned(length)? }),
+            b'b' if config.dialect.binary() => Specifier::Binary(unsafe { args.unsigned(length)? }),
+            b'B' if config.dialect.binary() => {
+                Specifier::UpperBinary(unsafe { args.unsigned(length)? })
+            }
+            b'o' => Specifier::Octal(unsafe { args.unsigned(length)? }),
+            #[cfg(feature = "float")]
+            b'f' | b'F' => Specifier::Double {
+                value: unsafe { args.double()? },
+                format: DoubleFormat::Normal.set_upper(ch.is_ascii_uppercase()),
+            },
+            #[cfg(feature = "float")]
+            b'e' | b'E' => Specifier::Double {
+                value: unsafe { args.double()? },
+                format: DoubleFormat::Scientific.set_upper(ch.is_ascii_uppercase()),
+            },
+            #[cfg(feature = "float")]
+            b'g' | b'G' => Specifier::Double {
+                value: unsafe { args.double()? },
+                format: DoubleFormat::Auto.set_upper(ch.is_ascii_uppercase()),
+            },
+            #[cfg(not(feature = "float"))]
+            b'f' | b'F' | b'e' | b'E' | b'g' | b'G' => {
+                unsafe { args.double()? };
+                Specifier::Disabled(ch)
+            }
+            #[cfg(feature = "hexfloat")]
+            b'a' | b'A' => Specifier::Double {
+                value: unsafe { args.double()? },
+                format: DoubleFormat::Hex.set_upper(ch.is_ascii_uppercase()),
+            },
+            #[cfg(not(feature = "hexfloat"))]
+            b'a' | b'A' => {
+                unsafe { args.double()? };
+                Specifier::Disabled(ch)
+            }
+            b's' | b'Z' if config.limits.is_some_and(|l| !l.dereference) => {
+                return Err(Error::Forbidden(ch));
+            }
+            #[cfg(feature = "wide")]
+            b's' if length == Length::Long => match unsafe { args.wide_string()? } {
+                Some(s) => Specifier::WideString(s),
+                None if config.dialect == Dialect::C11 => return Err(Error::NullPointer),
+                None => Specifier::Bytes(b"(null)"),
+            },
+            #[cfg(not(feature = "wide"))]
+            b's' if length == Length::Long => {
+                unsafe { args.pointer()? };
+                Specifier::Disabled(b's')
+            }
+            b's' => {
+                // As a common extension supported by glibc, musl, and
+                // others, format a NULL pointer as "(null)".
+                match unsafe { args.string()? } {
+                    Some(s) => Specifier::String(s),
+                    None if config.dialect == Dialect::C11 => {
+                        return Err(Error::NullPointer);
                     }
+                    None => Specifier::Bytes(b"(null)"),
                 }
-                b'c' => {
-                    trait CharToInt {
-                        type IntType;
+            }
+            // `char` is promoted to `int` or `unsigned int` depending on
+            // its signedness, but both are the same size.
+            #[cfg(feature = "wide")]
+            b'c' if length == Length::Long => Specifier::WideChar(unsafe { args.wide_char()? }),
+            #[cfg(not(feature = "wide"))]
+            b'c' if length == Length::Long => {
+                unsafe { args.int()? };
+                Specifier::Disabled(b'c')
+            }
+            b'c' => Specifier::Char(unsafe { args.int()? } as c_char),
+            b'Z' if config.dialect == Dialect::Msvc && length == Length::Int => {
+                match unsafe { args.ansi_string()? } {
+                    Some(s) => Specifier::Bytes(s),
+                    None => Specifier::Bytes(b"(null)"),
+                }
+            }
+            #[cfg(feature = "pointer-ext")]
+            b'p' if config.pointer_extensions => {
+                match conv.suffix {
+                    None => Specifier::Pointer(unsafe { args.pointer()? }),
+                    Some(Suffix::Error) => {
+                        let ptr = unsafe { args.pointer()? };
+                        // `IS_ERR`: error numbers are stored in the last
+                        // page of the address space.
+                        match ptr as isize {
+                            err @ -4095..=-1 => {
+                                Specifier::PointerExt(PointerExt::Error(err as c_int))
+                            }
+                            _ => Specifier::Pointer(ptr),
+                        }
                     }
-
-                    impl CharToInt for c_schar {
-                        type IntType = c_int;
+                    Some(_) if config.limits.is_some_and(|l| !l.dereference) => {
+                        return Err(Error::Forbidden(b'p'));
                     }
-
-                    impl CharToInt for c_uchar {
-                        type IntType = c_uint;
+                    Some(suffix) => {
+                        let len = suffix.len(width);
+                        if let Suffix::Hex { .. } = suffix {
+                            // The width is the length of the hex dump.
+                            width = 0;
+                        }
+                        match unsafe { args.bytes(len)? } {
+                            Some(bytes) => Specifier::PointerExt(
+                                suffix.with_bytes(bytes).ok_or(Error::InvalidFormat)?,
+                            ),
+                            None => Specifier::Bytes(b"(null)"),
+                        }
                     }
-
-                    Specifier::Char(
-                        unsafe { args.next_arg::<<c_char as CharToInt>::IntType>() } as c_char
-                    )
                 }
-                b'p' => Specifier::Pointer(unsafe { args.next_arg() }),
-                b'n' => Specifier::WriteBytesWritten(written, unsafe { args.next_arg() }),
-                _ => return -1,
-            },
-        }));
-        err!(handler(Specifier::Bytes(next_char(sub)).into()));
-    }
-    written
+            }
+            b'p' => Specifier::Pointer(unsafe { args.pointer()? }),
+            #[cfg(feature = "m-specifier")]
+            b'm' if matches!(config.dialect, Dialect::Glibc | Dialect::Musl) => {
+                Specifier::Errno(errno.ok_or(Error::Forbidden(b'm'))?)
+            }
+            #[cfg(not(feature = "m-specifier"))]
+            b'm' if matches!(config.dialect, Dialect::Glibc | Dialect::Musl) => {
+                Specifier::Disabled(b'm')
+            }
+            #[cfg(not(feature = "n-specifier"))]
+            b'n' => {
+                unsafe { args.pointer()? };
+                Specifier::Disabled(b'n')
+            }
+            _ => return Err(Error::InvalidFormat),
+        }),
+    };
+    Ok(specifier.map(|specifier| Argument {
+        flags,
+        width,
+        precision,
+        specifier,
+    }))
 }
@@ -0,0 +1,283 @@
+//! POSIX's numbered arguments, like `%2$s` and `%*1$d`.
+//!
+//! A `VaList` can only be read in order, so every conversion is looked at
+//! first, to find the type of each argument. They're all read, and then each
+//! conversion takes the ones it numbers.
+
+use core::ffi::*;
+
+use printf_compat_spec::{Count, FormatChar, Length};
+
+#[cfg(feature = "wide")]
+use crate::WChar;
+use crate::{Config, Error, SignedInt, UnsignedInt};
+
+use super::{ArgSource, Conv, Piece, convert, signed_int, unsigned_int};
+
+/// The most arguments that a format string can number.
+const MAX_POSITIONAL: usize = 32;
+
+/// What a conversion reads an argument as.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Kind {
+    /// No conversion uses the argument, so it can't be skipped.
+    Unused,
+    /// Any integer, with the length modifier it's promoted to.
+    Integer(Length),
+    Double,
+    String,
+    #[cfg(feature = "wide")]
+    WideString,
+    #[cfg(feature = "wide")]
+    WideChar,
+    AnsiString,
+    Pointer,
+    #[cfg(feature = "pointer-ext")]
+    BytesPointer,
+    #[cfg(feature = "n-specifier")]
+    CountPointer,
+}
+
+/// An argument that has been read.
+#[derive(Debug, Copy, Clone)]
+enum Value<'a> {
+    Unread,
+    Integer(i64),
+    Double(f64),
+    String(Option<&'a CStr>),
+    #[cfg(feature = "wide")]
+    WideString(Option<&'a [WChar]>),
+    #[cfg(feature = "wide")]
+    WideChar(WChar),
+    AnsiString(Option<&'a [u8]>),
+    Pointer(*const ()),
+    #[cfg(feature = "pointer-ext")]
+    BytesPointer(*const u8),
+    #[cfg(feature = "n-specifier")]
+    CountPointer(*mut ()),
+}
+
+impl Kind {
+    /// An integer of the type given by the length modifier, which can be read
+    /// as any other integer of the same size, like C's `printf` allows.
+    fn integer(length: Length) -> Self {
+        Kind::Integer(match length {
+            Length::Char | Length::Short => Length::Int,
+            Length::Usize => Length::Isize,
+            length => length,
+        })
+    }
+
+    unsafe fn read<'a>(self, args: &mut impl ArgSource<'a>) -> Result<Value<'a>, Error> {
+        Ok(unsafe {
+            match self {
+                Kind::Unused => return Err(Error::InvalidFormat),
+                Kind::Integer(length) => Value::Integer(args.signed(length)?.into()),
+                Kind::Double => Value::Double(args.double()?),
+                Kind::String => Value::String(args.string()?),
+                #[cfg(feature = "wide")]
+                Kind::WideString => Value::WideString(args.wide_string()?),
+                #[cfg(feature = "wide")]
+                Kind::WideChar => Value::WideChar(args.wide_char()?),
+                Kind::AnsiString => Value::AnsiString(args.ansi_string()?),
+                Kind::Pointer => Value::Pointer(args.pointer()?),
+                #[cfg(feature = "pointer-ext")]
+                Kind::BytesPointer => Value::BytesPointer(args.bytes_pointer()?),
+                #[cfg(feature = "n-specifier")]
+                Kind::CountPointer => Value::CountPointer(args.count_pointer()?),
+            }
+        })
+    }
+}
+
+/// Whether `conv` reads no arguments, so it doesn't need to be numbered.
+fn reads_nothing(conv: &Conv) -> bool {
+    conv.custom.is_none()
+        && matches!(conv.conversion, b'%' | b'm')
+        && !conv.is_positional()
+        && conv.width != Count::Arg
+        && conv.precision != Some(Count::Arg)
+}
+
+/// Numbered arguments, read before anything is written.
+///
+/// While the conversions are first looked at, this records what each
+/// argument is read as, and returns placeholders. Once the arguments have
+/// been read, it returns them.
+pub(super) struct Positional<'a> {
+    kinds: [Kind; MAX_POSITIONAL],
+    values: [Value<'a>; MAX_POSITIONAL],
+    /// How many arguments there are.
+    len: usize,
+    /// The positions of the current conversion's arguments, in the order
+    /// that they're read.
+    order: [usize; 3],
+    order_len: usize,
+    next: usize,
+    recording: bool,
+}
+
+impl<'a> Positional<'a> {
+    /// Read the arguments of `pieces` in the order of their numbers, or
+    /// return `None` if they aren't numbered.
+    ///
+    /// Whether they are is decided by the first conversion that reads an
+    /// argument; mixing numbered and unnumbered arguments is invalid.
+    pub(super) unsafe fn read<'s, C: FormatChar + 's>(
+        pieces: impl Iterator<Item = Result<Piece<'s, C>, Error>> + Clone,
+        args: &mut impl ArgSource<'a>,
+        config: &Config,
+    ) -> Result<Option<Self>, Error> {
+        let numbered = pieces
+            .clone()
+            .map_while(Result::ok)
+            .find_map(|piece| match piece {
+                Piece::Conversion(conv) if !reads_nothing(&conv) => Some(conv.is_positional()),
+                _ => None,
+            });
+        if numbered != Some(true) {
+            return Ok(None);
+        }
+
+        let mut positional = Positional {
+            kinds: [Kind::Unused; MAX_POSITIONAL],
+            values: [Value::Unread; MAX_POSITIONAL],
+            len: 0,
+            order: [0; 3],
+            order_len: 0,
+            next: 0,
+            recording: true,
+        };
+        for piece in pieces {
+            if let Piece::Conversion(conv) = piece? {
+                positional.start(&conv)?;
+                // The placeholders aren't printed, so the byte count and
+                // `errno` don't matter.
+                unsafe { convert(&conv, &mut positional, config, 0, Some(0))? };
+            }
+        }
+        for i in 0..positional.len {
+            positional.values[i] = unsafe { positional.kinds[i].read(args)? };
+        }
+        positional.recording = false;
+        Ok(Some(positional))
+    }
+
+    /// The index of the next argument that the current conversion reads as
+    /// `kind`.
+    fn next(&mut self, kind: Kind) -> Result<usize, Error> {
+        let position = *self.order[..self.order_len]
+            .get(self.next)
+            .ok_or(Error::InvalidFormat)?;
+        self.next += 1;
+        let index = position - 1;
+        let recorded = self.kinds.get_mut(index).ok_or(Error::InvalidFormat)?;
+        if self.recording {
+            if *recorded != Kind::Unused && *recorded != kind {
+                return Err(Error::InvalidFormat);
+            }
+            *recorded = kind;
+            self.len = self.len.max(position);
+        }
+        Ok(index)
+    }
+
+    fn integer(&mut self, length: Length) -> Result<i64, Error> {
+        Ok(match self.values[self.next(Kind::integer(length))?] {
+            Value::Integer(x) => x,
+            _ => 0,
+        })
+    }
+
+    fn push(&mut self, position: usize) {
+        self.order[self.order_len] = position;
+        self.order_len += 1;
+    }
+}
+
+impl<'a> ArgSource<'a> for Positional<'a> {
+    unsafe fn int(&mut self) -> Result<c_int, Error> {
+        Ok(self.integer(Length::Int)? as c_int)
+    }
+    unsafe fn signed(&mut self, length: Length) -> Result<SignedInt, Error> {
+        Ok(signed_int(length, self.integer(length)?))
+    }
+    unsafe fn unsigned(&mut self, length: Length) -> Result<UnsignedInt, Error> {
+        Ok(unsigned_int(length, self.integer(length)? as u64))
+    }
+    unsafe fn double(&mut self) -> Result<f64, Error> {
+        Ok(match self.values[self.next(Kind::Double)?] {
+            Value::Double(x) => x,
+            _ => 0.0,
+        })
+    }
+    unsafe fn string(&mut self) -> Result<Option<&'a CStr>, Error> {
+        Ok(match self.values[self.next(Kind::String)?] {
+            Value::String(s) => s,
+            _ => Some(c""),
+        })
+    }
+    #[cfg(feature = "wide")]
+    unsafe fn wide_string(&mut self) -> Result<Option<&'a [WChar]>, Error> {
+        Ok(match self.values[self.next(Kind::WideString)?] {
+            Value::WideString(s) => s,
+            _ => Some(&[]),
+        })
+    }
+    #[cfg(feature = "wide")]
+    unsafe fn wide_char(&mut self) -> Result<WChar, Error> {
+        Ok(match self.values[self.next(Kind::WideChar)?] {
+            Value::WideChar(c) => c,
+            _ => 0,
+        })
+    }
+    unsafe fn ansi_string(&mut self) -> Result<Option<&'a [u8]>, Error> {
+        Ok(match self.values[self.next(Kind::AnsiString)?] {
+            Value::AnsiString(s) => s,
+            _ => Some(&[]),
+        })
+    }
+    unsafe fn pointer(&mut self) -> Result<*const (), Error> {
+        Ok(match self.values[self.next(Kind::Pointer)?] {
+            Value::Pointer(p) => p,
+            _ => core::ptr::null(),
+        })
+    }
+    #[cfg(feature = "pointer-ext")]
+    unsafe fn bytes_pointer(&mut self) -> Result<*const u8, Error> {
+        Ok(match self.values[self.next(Kind::BytesPointer)?] {
+            Value::BytesPointer(p) => p,
+            _ => core::ptr::null(),
+        })
+    }
+    #[cfg(feature = "n-specifier")]
+    unsafe fn count_pointer(&mut self) -> Result<*mut (), Error> {
+        Ok(match self.values[self.next(Kind::CountPointer)?] {
+            Value::CountPointer(p) => p,
+            _ => core::ptr::null_mut(),
+        })
+    }
+    #[cfg(feature = "n-specifier")]
+    unsafe fn write_count(&mut self, length: Length, count: c_int) -> Result<(), Error> {
+        let ptr = unsafe { self.count_pointer()? };
+        if self.recording {
+            return Ok(());
+        }
+        unsafe { super::write_count(ptr, length, count) }
+    }
+    fn start(&mut self, conv: &Conv) -> Result<(), Error> {
+        self.order_len = 0;
+        self.next = 0;
+        for count in [Some(conv.width), conv.precision].into_iter().flatten() {
+            match count {
+                Count::Value(_) => {}
+                Count::Arg => return Err(Error::InvalidFormat),
+                Count::Position(position) => self.push(position),
+            }
+        }
+        if let Some(position) = conv.position {
+            self.push(position);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,528 @@
+//! The Linux kernel's `%p` extensions, enabled with
+//! [`Config::pointer_extensions`][crate::Config::pointer_extensions].
+
+use core::ffi::c_int;
+use core::fmt;
+
+use printf_compat_spec::FormatChar;
+
+/// How [`PointerExt::Ipv6`] is printed.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum Ipv6Format {
+    /// `%pI6`: eight groups of four digits, like `0001:0db8:0000:…`.
+    Full,
+    /// `%pi6`: 32 digits without colons.
+    NoColons,
+    /// `%pI6c`: the shortest form from RFC 5952, like `1:db8::1`.
+    Compressed,
+}
+
+/// What a Linux kernel `%p` extension points to, with how to print it.
+///
+/// The pointer has already been read through, so the bytes are only borrowed
+/// for as long as the format arguments are. [`Display`][fmt::Display] prints
+/// it like the kernel's `printk`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum PointerExt<'a> {
+    /// `%pI4`, or `%pi4` with `leading_zeros`: an IPv4 address, like
+    /// `10.0.0.1`.
+    Ipv4 {
+        addr: &'a [u8; 4],
+        leading_zeros: bool,
+    },
+    /// `%pI6`, `%pi6` or `%pI6c`: an IPv6 address.
+    Ipv6 {
+        addr: &'a [u8; 16],
+        format: Ipv6Format,
+    },
+    /// `%pM`, `%pMF`, `%pm`, and their reversed forms `%pMR` and `%pmR`: a
+    /// MAC address, like `00:11:22:33:44:55`.
+    Mac {
+        addr: &'a [u8; 6],
+        /// `:` for `%pM`, `-` for `%pMF`, and none for `%pm`.
+        separator: Option<char>,
+        reversed: bool,
+    },
+    /// `%pU`, `%pUb`, `%pUB`, `%pUl` or `%pUL`: a UUID, like
+    /// `00112233-4455-6677-8899-aabbccddeeff`.
+    Uuid {
+        bytes: &'a [u8; 16],
+        /// `%pUB` and `%pUL`.
+        upper: bool,
+        /// `%pUl` and `%pUL`: the first three fields are stored
+        /// little-endian.
+        little_endian: bool,
+    },
+    /// `%ph`, `%phC`, `%phD` or `%phN`: a hex dump of up to 64 bytes, whose
+    /// length is the field width (1 if there isn't one).
+    Hex {
+        bytes: &'a [u8],
+        /// ` ` for `%ph`, `:` for `%phC`, `-` for `%phD`, and none for
+        /// `%phN`.
+        separator: Option<char>,
+    },
+    /// `%pe` of an `ERR_PTR`: a negative error number, printed by its
+    /// name, like `-EINVAL`.
+    Error(c_int),
+}
+
+/// A recognized `%p` suffix, before its argument is read.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum Suffix {
+    Ipv4 {
+        leading_zeros: bool,
+    },
+    Ipv6(Ipv6Format),
+    Mac {
+        separator: Option<char>,
+        reversed: bool,
+    },
+    Uuid {
+        upper: bool,
+        little_endian: bool,
+    },
+    Hex {
+        separator: Option<char>,
+    },
+    Error,
+}
+
+impl Suffix {
+    /// Parse the letters and digits that follow `%p`. Returns `None` if they
+    /// aren't an extension, in which case the kernel prints the plain
+    /// pointer.
+    pub(crate) fn parse<C: FormatChar>(suffix: &[C]) -> Option<Self> {
+        let mut buf = [0; 4];
+        if suffix.len() > buf.len() {
+            return None;
+        }
+        for (b, c) in buf.iter_mut().zip(suffix) {
+            *b = c.to_ascii();
+        }
+        Some(match &buf[..suffix.len()] {
+            b"I4" => Suffix::Ipv4 {
+                leading_zeros: false,
+            },
+            b"i4" => Suffix::Ipv4 {
+                leading_zeros: true,
+            },
+            b"I6" => Suffix::Ipv6(Ipv6Format::Full),
+            b"i6" => Suffix::Ipv6(Ipv6Format::NoColons),
+            b"I6c" => Suffix::Ipv6(Ipv6Format::Compressed),
+            b"M" => Suffix::Mac {
+                separator: Some(':'),
+                reversed: false,
+            },
+            b"MR" => Suffix::Mac {
+                separator: Some(':'),
+                reversed: true,
+            },
+            b"MF" => Suffix::Mac {
+                separator: Some('-'),
+                reversed: false,
+            },
+            b"m" => Suffix::Mac {
+                separator: None,
+                reversed: false,
+            },
+            b"mR" => Suffix::Mac {
+                separator: None,
+                reversed: true,
+            },
+            b"U" | b"Ub" => Suffix::Uuid {
+                upper: false,
+                little_endian: false,
+            },
+            b"UB" => Suffix::Uuid {
+                upper: true,
+                little_endian: false,
+            },
+            b"Ul" => Suffix::Uuid {
+                upper: false,
+                little_endian: true,
+            },
+            b"UL" => Suffix::Uuid {
+                upper: true,
+                little_endian: true,
+            },
+            b"h" => Suffix::Hex {
+                separator: Some(' '),
+            },
+            b"hC" => Suffix::Hex {
+                separator: Some(':'),
+            },
+            b"hD" => Suffix::Hex {
+                separator: Some('-'),
+            },
+            b"hN" => Suffix::Hex { separator: None },
+            b"e" => Suffix::Error,
+            _ => return None,
+        })
+    }
+
+    /// The number of bytes read through the pointer. A hex dump's length
+    /// comes from the width instead.
+    pub(crate) fn len(self, width: c_int) -> usize {
+        match self {
+            Suffix::Ipv4 { .. } => 4,
+            Suffix::Ipv6(_) | Suffix::Uuid { .. } => 16,
+            Suffix::Mac { .. } => 6,
+            Suffix::Hex { .. } if width > 0 => width.min(64) as usize,
+            Suffix::Hex { .. } => 1,
+            Suffix::Error => 0,
+        }
+    }
+
+    /// Combine the suffix with the bytes that the pointer points to, which
+    /// must be [`len`][Self::len] long.
+    pub(crate) fn with_bytes(self, bytes: &[u8]) -> Option<PointerExt<'_>> {
+        Some(match self {
+            Suffix::Ipv4 { leading_zeros } => PointerExt::Ipv4 {
+                addr: bytes.try_into().ok()?,
+                leading_zeros,
+            },
+            Suffix::Ipv6(format) => PointerExt::Ipv6 {
+                addr: bytes.try_into().ok()?,
+                format,
+            },
+            Suffix::Mac {
+                separator,
+                reversed,
+            } => PointerExt::Mac {
+                addr: bytes.try_into().ok()?,
+                separator,
+                reversed,
+            },
+            Suffix::Uuid {
+                upper,
+                little_endian,
+            } => PointerExt::Uuid {
+                bytes: bytes.try_into().ok()?,
+                upper,
+                little_endian,
+            },
+            Suffix::Hex { separator } => PointerExt::Hex { bytes, separator },
+            Suffix::Error => return None,
+        })
+    }
+}
+
+/// The kernel's names for error numbers 1 to 133.
+const NAMES: [&str; 133] = [
+    "EPERM",
+    "ENOENT",
+    "ESRCH",
+    "EINTR",
+    "EIO",
+    "ENXIO",
+    "E2BIG",
+    "ENOEXEC",
+    "EBADF",
+    "ECHILD",
+    "EAGAIN",
+    "ENOMEM",
+    "EACCES",
+    "EFAULT",
+    "ENOTBLK",
+    "EBUSY",
+    "EEXIST",
+    "EXDEV",
+    "ENODEV",
+    "ENOTDIR",
+    "EISDIR",
+    "EINVAL",
+    "ENFILE",
+    "EMFILE",
+    "ENOTTY",
+    "ETXTBSY",
+    "EFBIG",
+    "ENOSPC",
+    "ESPIPE",
+    "EROFS",
+    "EMLINK",
+    "EPIPE",
+    "EDOM",
+    "ERANGE",
+    "EDEADLK",
+    "ENAMETOOLONG",
+    "ENOLCK",
+    "ENOSYS",
+    "ENOTEMPTY",
+    "ELOOP",
+    "",
+    "ENOMSG",
+    "EIDRM",
+    "ECHRNG",
+    "EL2NSYNC",
+    "EL3HLT",
+    "EL3RST",
+    "ELNRNG",
+    "EUNATCH",
+    "ENOCSI",
+    "EL2HLT",
+    "EBADE",
+    "EBADR",
+    "EXFULL",
+    "ENOANO",
+    "EBADRQC",
+    "EBADSLT",
+    "",
+    "EBFONT",
+    "ENOSTR",
+    "ENODATA",
+    "ETIME",
+    "ENOSR",
+    "ENONET",
+    "ENOPKG",
+    "EREMOTE",
+    "ENOLINK",
+    "EADV",
+    "ESRMNT",
+    "ECOMM",
+    "EPROTO",
+    "EMULTIHOP",
+    "EDOTDOT",
+    "EBADMSG",
+    "EOVERFLOW",
+    "ENOTUNIQ",
+    "EBADFD",
+    "EREMCHG",
+    "ELIBACC",
+    "ELIBBAD",
+    "ELIBSCN",
+    "ELIBMAX",
+    "ELIBEXEC",
+    "EILSEQ",
+    "ERESTART",
+    "ESTRPIPE",
+    "EUSERS",
+    "ENOTSOCK",
+    "EDESTADDRREQ",
+    "EMSGSIZE",
+    "EPROTOTYPE",
+    "ENOPROTOOPT",
+    "EPROTONOSUPPORT",
+    "ESOCKTNOSUPPORT",
+    "EOPNOTSUPP",
+    "EPFNOSUPPORT",
+    "EAFNOSUPPORT",
+    "EADDRINUSE",
+    "EADDRNOTAVAIL",
+    "ENETDOWN",
+    "ENETUNREACH",
+    "ENETRESET",
+    "ECONNABORTED",
+    "ECONNRESET",
+    "ENOBUFS",
+    "EISCONN",
+    "ENOTCONN",
+    "ESHUTDOWN",
+    "ETOOMANYREFS",
+    "ETIMEDOUT",
+    "ECONNREFUSED",
+    "EHOSTDOWN",
+    "EHOSTUNREACH",
+    "EALREADY",
+    "EINPROGRESS",
+    "ESTALE",
+    "EUCLEAN",
+    "ENOTNAM",
+    "ENAVAIL",
+    "EISNAM",
+    "EREMOTEIO",
+    "EDQUOT",
+    "ENOMEDIUM",
+    "EMEDIUMTYPE",
+    "ECANCELED",
+    "ENOKEY",
+    "EKEYEXPIRED",
+    "EKEYREVOKED",
+    "EKEYREJECTED",
+    "EOWNERDEAD",
+    "ENOTRECOVERABLE",
+    "ERFKILL",
+    "EHWPOISON",
+];
+
+/// The kernel's internal error numbers, starting at 512.
+const KERNEL_NAMES: [&str; 19] = [
+    "ERESTARTSYS",
+    "ERESTARTNOINTR",
+    "ERESTARTNOHAND",
+    "ENOIOCTLCMD",
+    "ERESTART_RESTARTBLOCK",
+    "EPROBE_DEFER",
+    "EOPENSTALE",
+    "ENOPARAM",
+    "",
+    "EBADHANDLE",
+    "ENOTSYNC",
+    "EBADCOOKIE",
+    "ENOTSUPP",
+    "ETOOSMALL",
+    "ESERVERFAULT",
+    "EBADTYPE",
+    "EJUKEBOX",
+    "EIOCBQUEUED",
+    "ERECALLCONFLICT",
+];
+
+/// The name of a positive error number, like `EINVAL`.
+fn error_name(errno: c_int) -> Option<&'static str> {
+    let name = match errno {
+        1..=133 => NAMES[errno as usize - 1],
+        512.. => *KERNEL_NAMES.get(errno as usize - 512)?,
+        _ => return None,
+    };
+    (!name.is_empty()).then_some(name)
+}
+
+/// Write `bytes` as lowercase or uppercase hex, with `separator` between
+/// each byte.
+fn write_bytes<'a>(
+    f: &mut fmt::Formatter<'_>,
+    bytes: impl IntoIterator<Item = &'a u8>,
+    separator: Option<char>,
+    upper: bool,
+) -> fmt::Result {
+    for (i, byte) in bytes.into_iter().enumerate() {
+        if let Some(separator) = separator.filter(|_| i > 0) {
+            write!(f, "{separator}")?;
+        }
+        if upper {
+            write!(f, "{byte:02X}")?;
+        } else {
+            write!(f, "{byte:02x}")?;
+        }
+    }
+    Ok(())
+}
+
+fn write_ipv4(f: &mut fmt::Formatter<'_>, addr: &[u8; 4], leading_zeros: bool) -> fmt::Result {
+    for (i, byte) in addr.iter().enumerate() {
+        if i > 0 {
+            f.write_str(".")?;
+        }
+        if leading_zeros {
+            write!(f, "{byte:03}")?;
+        } else {
+            write!(f, "{byte}")?;
+        }
+    }
+    Ok(())
+}
+
+/// `%pI6c`: leading zeros are dropped, the first longest run of two or more
+/// zero groups becomes `::`, and IPv4-mapped addresses end in dotted decimal.
+fn write_ipv6_compressed(f: &mut fmt::Formatter<'_>, addr: &[u8; 16]) -> fmt::Result {
+    let v4_mapped = addr[..10].iter().all(|&b| b == 0) && addr[10..12] == [0xff, 0xff];
+    let groups = if v4_mapped { 6 } else { 8 };
+    let group = |i: usize| u16::from_be_bytes([addr[2 * i], addr[2 * i + 1]]);
+
+    let (mut start, mut longest) = (groups, 0);
+    let mut i = 0;
+    while i < groups {
+        let run = (i..groups).take_while(|&j| group(j) == 0).count();
+        if run > longest {
+            (start, longest) = (i, run);
+        }
+        i += run.max(1);
+    }
+    if longest < 2 {
+        start = groups;
+    }
+
+    let mut i = 0;
+    let mut need_colon = false;
+    while i < groups {
+        if i == start {
+            f.write_str("::")?;
+            i += longest;
+            need_colon = false;
+            continue;
+        }
+        if need_colon {
+            f.write_str(":")?;
+        }
+        write!(f, "{:x}", group(i))?;
+        need_colon = true;
+        i += 1;
+    }
+    if v4_mapped {
+        if need_colon {
+            f.write_str(":")?;
+        }
+        write_ipv4(f, &[addr[12], addr[13], addr[14], addr[15]], false)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for PointerExt<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            PointerExt::Ipv4 {
+                addr,
+                leading_zeros,
+            } => write_ipv4(f, addr, leading_zeros),
+            PointerExt::Ipv6 {
+                addr,
+                format: Ipv6Format::Full,
+            } => {
+                for (i, group) in addr.chunks(2).enumerate() {
+                    if i > 0 {
+                        f.write_str(":")?;
+                    }
+                    write_bytes(f, group, None, false)?;
+                }
+                Ok(())
+            }
+            PointerExt::Ipv6 {
+                addr,
+                format: Ipv6Format::NoColons,
+            } => write_bytes(f, addr, None, false),
+            PointerExt::Ipv6 {
+                addr,
+                format: Ipv6Format::Compressed,
+            } => write_ipv6_compressed(f, addr),
+            PointerExt::Mac {
+                addr,
+                separator,
+                reversed: false,
+            } => write_bytes(f, addr, separator, false),
+            PointerExt::Mac {
+                addr,
+                separator,
+                reversed: true,
+            } => write_bytes(f, addr.iter().rev(), separator, false),
+            PointerExt::Uuid {
+                bytes,
+                upper,
+                little_endian,
+            } => {
+                const BIG_ENDIAN: [usize; 16] =
+                    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+                const LITTLE_ENDIAN: [usize; 16] =
+                    [3, 2, 1, 0, 5, 4, 7, 6, 8, 9, 10, 11, 12, 13, 14, 15];
+                let order = if little_endian {
+                    &LITTLE_ENDIAN
+                } else {
+                    &BIG_ENDIAN
+                };
+                for (i, &index) in order.iter().enumerate() {
+                    if matches!(i, 4 | 6 | 8 | 10) {
+                        f.write_str("-")?;
+                    }
+                    write_bytes(f, [&bytes[index]], None, upper)?;
+                }
+                Ok(())
+            }
+            PointerExt::Hex { bytes, separator } => write_bytes(f, bytes, separator, false),
+            PointerExt::Error(err) => match err.checked_neg().and_then(error_name) {
+                Some(name) => write!(f, "-{name}"),
+                None => write!(f, "{err}"),
+            },
+        }
+    }
+}
@@ -0,0 +1,197 @@
+//! A reusable formatting setup.
+
+use core::ffi::*;
+use core::fmt;
+
+use crate::output::{self, FmtWrite, Sink, Untyped, VaListDisplay};
+use crate::parser::{format_slice_with_config, try_format_with_config};
+use crate::{
+    BytesWrittenPolicy, CompiledFormat, Config, Conversion, Dialect, Error, Limits, Locale,
+    PrintfArg,
+};
+
+/// A [`Config`], with the functions that format with it as methods.
+///
+/// Set it up once, usually as a `const`, and use it everywhere instead of
+/// passing a [`Config`] to each call. The free functions, like
+/// [`format()`][crate::format()], are shortcuts for `Printf::new()`.
+///
+/// The sink helpers, like [`fmt_write`][Printf::fmt_write], imitate the
+/// output of the [`Dialect`] that it's set up with, and write numbers in its
+/// [`Locale`].
+///
+/// ```rust
+/// # #![feature(c_variadic)]
+/// use core::ffi::{c_char, c_int};
+/// use printf_compat::{Dialect, Printf};
+///
+/// const PRINTF: Printf = Printf::new().dialect(Dialect::Musl);
+///
+/// #[unsafe(no_mangle)]
+/// unsafe extern "C" fn c_library_print(str: *const c_char, args: ...) -> c_int {
+///     let mut s = String::new();
+///     let bytes_written = unsafe { PRINTF.format(str, args, PRINTF.fmt_write(&mut s)) };
+///     println!("{}", s);
+///     bytes_written
+/// }
+///
+/// assert_eq!(PRINTF.sprintf(c"%p", &[&core::ptr::null::<()>()]).unwrap(), "0");
+/// ```
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct Printf {
+    config: Config,
+}
+
+impl Printf {
+    /// The default setup, the same as the free functions use.
+    pub const fn new() -> Self {
+        Printf {
+            config: Config::new(),
+        }
+    }
+
+    /// Use an existing [`Config`].
+    pub const fn with_config(config: Config) -> Self {
+        Printf { config }
+    }
+
+    /// The [`Config`] that it formats with.
+    pub const fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// See [`Config::bytes_written`].
+    pub const fn bytes_written(self, policy: BytesWrittenPolicy) -> Self {
+        Self::with_config(self.config.bytes_written(policy))
+    }
+
+    /// See [`Config::dialect`].
+    pub const fn dialect(self, dialect: Dialect) -> Self {
+        Self::with_config(self.config.dialect(dialect))
+    }
+
+    /// See [`Config::errno`].
+    pub const fn errno(self, errno: fn() -> c_int) -> Self {
+        Self::with_config(self.config.errno(errno))
+    }
+
+    /// See [`Config::conversions`].
+    pub const fn conversions(self, conversions: &'static [Conversion]) -> Self {
+        Self::with_config(self.config.conversions(conversions))
+    }
+
+    /// See [`Config::locale`].
+    pub const fn locale(self, locale: Locale) -> Self {
+        Self::with_config(self.config.locale(locale))
+    }
+
+    /// See [`Config::pointer_extensions`].
+    #[cfg(feature = "pointer-ext")]
+    pub const fn pointer_extensions(self, enabled: bool) -> Self {
+        Self::with_config(self.config.pointer_extensions(enabled))
+    }
+
+    /// See [`Config::limits`].
+    pub const fn limits(self, limits: Limits) -> Self {
+        Self::with_config(self.config.limits(limits))
+    }
+
+    /// Parse a format string and write it somewhere, like
+    /// [`format_with_config`][crate::format_with_config]. Returns -1 on
+    /// error.
+    ///
+    /// # Safety
+    ///
+    /// [`VaList`]s are *very* unsafe. The passed `format` and `args` parameter must be a valid [`printf` format string](http://www.cplusplus.com/reference/cstdio/printf/).
+    pub unsafe fn format(&self, format: *const c_char, args: VaList, sink: impl Sink) -> c_int {
+        unsafe { self.try_format(format, args, Untyped(sink)) }.unwrap_or(-1)
+    }
+
+    /// Parse a format string and write it somewhere, returning why it failed
+    /// instead of -1.
+    ///
+    /// # Safety
+    ///
+    /// [`VaList`]s are *very* unsafe. The passed `format` and `args` parameter must be a valid [`printf` format string](http://www.cplusplus.com/reference/cstdio/printf/).
+    pub unsafe fn try_format<S: Sink>(
+        &self,
+        format: *const c_char,
+        args: VaList,
+        sink: S,
+    ) -> Result<c_int, S::Error>
+    where
+        S::Error: From<Error>,
+    {
+        unsafe { try_format_with_config(format, args, &self.config, sink) }
+    }
+
+    /// Parse a format string and write it somewhere, taking the arguments
+    /// from a slice. See [`format_slice`][crate::format_slice].
+    pub fn format_slice<S: Sink>(
+        &self,
+        format: &CStr,
+        args: &[&dyn PrintfArg],
+        sink: S,
+    ) -> Result<c_int, S::Error>
+    where
+        S::Error: From<Error>,
+    {
+        format_slice_with_config(format, args, &self.config, sink)
+    }
+
+    /// Format a string, taking the arguments from a slice. See
+    /// [`sprintf`][crate::sprintf].
+    #[cfg(feature = "std")]
+    pub fn sprintf(&self, format: &CStr, args: &[&dyn PrintfArg]) -> Result<String, Error> {
+        let mut s = String::new();
+        self.format_slice(format, args, self.fmt_write(&mut s))?;
+        Ok(s)
+    }
+
+    /// Parse a format string once, to format it with this setup many times.
+    /// See [`CompiledFormat`].
+    pub fn compile<'f, const N: usize>(
+        &self,
+        format: &'f CStr,
+    ) -> Result<CompiledFormat<'f, N>, Error> {
+        CompiledFormat::new(format, &self.config)
+    }
+
+    /// Read a format string's arguments into a [`Record`][crate::Record], to
+    /// format them later. See [`capture`][crate::capture].
+    ///
+    /// # Safety
+    ///
+    /// [`VaList`]s are *very* unsafe. The passed `format` and `args` parameter must be a valid [`printf` format string](http://www.cplusplus.com/reference/cstdio/printf/).
+    #[cfg(feature = "std")]
+    pub unsafe fn capture(
+        &self,
+        format: *const c_char,
+        args: VaList,
+    ) -> Result<crate::Record, Error> {
+        unsafe { crate::record::capture_with_config(format, args, &self.config) }
+    }
+
+    /// Returns an object that implements [`Display`][fmt::Display]. See
+    /// [`output::display`].
+    ///
+    /// # Safety
+    ///
+    /// [`VaList`]s are *very* unsafe. The passed `format` and `args` parameter must be a valid [`printf` format string](http://www.cplusplus.com/reference/cstdio/printf/).
+    pub unsafe fn display<'a>(&self, format: *const c_char, args: VaList<'a>) -> VaListDisplay<'a> {
+        unsafe { output::display_with_config(format, args, self.config) }
+    }
+
+    /// Write to a struct that implements [`fmt::Write`]. See
+    /// [`output::fmt_write_locale`].
+    pub fn fmt_write<'a, W: fmt::Write>(&self, w: &'a mut W) -> FmtWrite<'a, W> {
+        output::fmt_write_locale(w, self.config.dialect, self.config.locale)
+    }
+
+    /// Write to a struct that implements [`io::Write`][std::io::Write]. See
+    /// [`output::io_write_locale`].
+    #[cfg(feature = "std")]
+    pub fn io_write<'a, W: std::io::Write>(&self, w: &'a mut W) -> output::IoWrite<'a, W> {
+        output::io_write_locale(w, self.config.dialect, self.config.locale)
+    }
+}
@@ -0,0 +1,142 @@
+//! Passing Rust values to C-style formatting, without a `va_list`.
+
+use core::ffi::*;
+use core::mem::size_of;
+
+use crate::{SignedInt, UnsignedInt};
+
+/// A value as C would pass it through `...`, after the default argument
+/// promotions.
+///
+/// Integers smaller than `int` become `int`, and `float` becomes `double`.
+/// Conversions read the promoted value with the type given by their length
+/// modifier, so `%hhd` of a [`SignedInt::Int`] truncates it to a
+/// `signed char`, just like C.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Promoted<'a> {
+    /// A signed integer, used by `%d`, `%i`, `%c` and `*`. Also accepted by
+    /// the unsigned conversions.
+    Int(SignedInt),
+    /// An unsigned integer, used by `%u`, `%o`, `%x` and `%X`. Also accepted
+    /// by the signed conversions.
+    Uint(UnsignedInt),
+    /// A floating-point number, used by `%f`, `%e`, `%g` and `%a`.
+    Double(f64),
+    /// A string, used by `%s`. `None` is printed as `(null)`.
+    String(Option<&'a CStr>),
+    /// A pointer, used by `%p`.
+    Pointer(*const ()),
+}
+
+/// A Rust value that can be passed to [`sprintf`][crate::sprintf] and
+/// [`format_slice`][crate::format_slice].
+///
+/// ```rust
+/// use printf_compat::sprintf;
+///
+/// let s = sprintf(c"%s has %d items (%lu bytes)", &[&c"cart", &3u8, &4096u64]);
+/// assert_eq!(s.unwrap(), "cart has 3 items (4096 bytes)");
+/// ```
+///
+/// A [`char`] is passed as its code point, so `%c` only prints it correctly
+/// if it's ASCII.
+pub trait PrintfArg {
+    /// Convert the value to the type that C would pass it as.
+    fn promote(&self) -> Promoted<'_>;
+}
+
+/// Promote a signed integer of type `T`.
+// Some casts are only needed on some platforms.
+#[allow(clippy::unnecessary_cast)]
+fn signed<T>(x: i64) -> Promoted<'static> {
+    Promoted::Int(if size_of::<T>() <= size_of::<c_int>() {
+        SignedInt::Int(x as c_int)
+    } else if size_of::<T>() <= size_of::<c_long>() {
+        SignedInt::Long(x as c_long)
+    } else {
+        SignedInt::LongLong(x as c_longlong)
+    })
+}
+
+/// Promote an unsigned integer of type `T`.
+#[allow(clippy::unnecessary_cast)]
+fn unsigned<T>(x: u64) -> Promoted<'static> {
+    if size_of::<T>() < size_of::<c_int>() {
+        // Every value fits in an `int`, so that's what it's promoted to.
+        Promoted::Int(SignedInt::Int(x as c_int))
+    } else if size_of::<T>() <= size_of::<c_uint>() {
+        Promoted::Uint(UnsignedInt::Int(x as c_uint))
+    } else if size_of::<T>() <= size_of::<c_ulong>() {
+        Promoted::Uint(UnsignedInt::Long(x as c_ulong))
+    } else {
+        Promoted::Uint(UnsignedInt::LongLong(x as c_ulonglong))
+    }
+}
+
+macro_rules! impl_int {
+    ($promote:ident: $($ty:ty),*) => {$(
+        impl PrintfArg for $ty {
+            fn promote(&self) -> Promoted<'_> {
+                $promote::<$ty>((*self).into())
+            }
+        }
+    )*};
+}
+
+impl_int!(signed: i8, i16, i32, i64);
+impl_int!(unsigned: u8, u16, u32, u64);
+
+impl PrintfArg for isize {
+    fn promote(&self) -> Promoted<'_> {
+        Promoted::Int(SignedInt::Isize(*self))
+    }
+}
+
+impl PrintfArg for usize {
+    fn promote(&self) -> Promoted<'_> {
+        Promoted::Uint(UnsignedInt::Isize(*self))
+    }
+}
+
+impl PrintfArg for f32 {
+    fn promote(&self) -> Promoted<'_> {
+        Promoted::Double((*self).into())
+    }
+}
+
+impl PrintfArg for f64 {
+    fn promote(&self) -> Promoted<'_> {
+        Promoted::Double(*self)
+    }
+}
+
+impl PrintfArg for char {
+    fn promote(&self) -> Promoted<'_> {
+        unsigned::<u32>((*self).into())
+    }
+}
+
+impl PrintfArg for &CStr {
+    fn promote(&self) -> Promoted<'_> {
+        Promoted::String(Some(self))
+    }
+}
+
+impl PrintfArg for Option<&CStr> {
+    fn promote(&self) -> Promoted<'_> {
+        Promoted::String(*self)
+    }
+}
+
+impl<T: ?Sized> PrintfArg for *const T {
+    fn promote(&self) -> Promoted<'_> {
+        Promoted::Pointer(self.cast())
+    }
+}
+
+impl<T: ?Sized> PrintfArg for *mut T {
+    fn promote(&self) -> Promoted<'_> {
+        Promoted::Pointer(self.cast())
+    }
+}
@@ -0,0 +1,437 @@
+//! Formatting arguments that are stored to be written later.
+
+use core::ffi::*;
+use core::fmt;
+use core::ops::Range;
+use std::vec::Vec;
+
+use crate::output::{self, Limited, Sink, Untyped};
+use crate::parser::try_format_with_config;
+#[cfg(feature = "pointer-ext")]
+use crate::pointer_ext::{Ipv6Format, PointerExt};
+use crate::{
+    Argument, BytesWrittenPolicy, Config, Conversion, DoubleFormat, Error, Flags, Promoted,
+    SignedInt, Specifier, UnsignedInt, WChar,
+};
+
+/// A format string with its arguments already read, to be formatted later.
+///
+/// A `Record` owns everything that it needs: the format string's literal text
+/// and the strings that the arguments point to are copied into it, and `%m`
+/// reads `errno` when it's captured. The format string itself isn't kept,
+/// since it's already been parsed.
+/// It's [`Send`], so a log callback can [`capture`] a message on the C
+/// library's thread, and leave the formatting to a background thread.
+///
+/// ```rust
+/// # #![feature(c_variadic)]
+/// use core::ffi::{c_char, c_int};
+/// use std::sync::mpsc::Sender;
+/// use printf_compat::{Record, capture};
+///
+/// unsafe extern "C" fn log_message(tx: &Sender<Record>, str: *const c_char, args: ...) -> c_int {
+///     match unsafe { capture(str, args) } {
+///         Ok(record) => {
+///             let _ = tx.send(record);
+///             0
+///         }
+///         Err(_) => -1,
+///     }
+/// }
+///
+/// let (tx, rx) = std::sync::mpsc::channel();
+/// std::thread::spawn(move || {
+///     for record in rx {
+///         println!("{}", record);
+///     }
+/// });
+/// unsafe { log_message(&tx, c"%s: %d".as_ptr(), c"answer".as_ptr(), 42) };
+/// ```
+///
+/// Pointers are kept as their addresses, so `%p` still prints them, but a
+/// custom [`Conversion`] that reads through one would read a pointer that
+/// may no longer be valid.
+#[derive(Debug, Clone)]
+pub struct Record {
+    config: Config,
+    items: Vec<Item>,
+    bytes: Vec<u8>,
+    wide: Vec<WChar>,
+}
+
+/// Literal text, or a conversion.
+#[derive(Debug, Clone)]
+enum Item {
+    Literal(Range<usize>),
+    Argument(OwnedArgument),
+}
+
+/// An [`Argument`] whose strings are in a [`Record`]'s buffers.
+#[derive(Debug, Clone)]
+struct OwnedArgument {
+    flags: Flags,
+    width: c_int,
+    precision: Option<c_int>,
+    specifier: OwnedSpecifier,
+}
+
+/// A [`Specifier`], with ranges of a [`Record`]'s buffers instead of
+/// references, and addresses instead of pointers.
+#[derive(Debug, Clone)]
+enum OwnedSpecifier {
+    Percent,
+    Int(SignedInt),
+    Uint(UnsignedInt),
+    Octal(UnsignedInt),
+    Double {
+        value: f64,
+        format: DoubleFormat,
+    },
+    Bytes(Range<usize>),
+    /// Includes the null terminator.
+    String(Range<usize>),
+    Char(c_char),
+    WideText(Range<usize>),
+    WideString(Range<usize>),
+    WideChar(WChar),
+    Hex(UnsignedInt),
+    UpperHex(UnsignedInt),
+    Binary(UnsignedInt),
+    UpperBinary(UnsignedInt),
+    Pointer(usize),
+    Custom {
+        conversion: Conversion,
+        value: OwnedPromoted,
+    },
+    #[cfg(feature = "pointer-ext")]
+    PointerExt(OwnedPointerExt),
+    Errno(c_int),
+    Disabled(u8),
+}
+
+/// A [`Promoted`] value, stored like [`OwnedSpecifier`].
+#[derive(Debug, Clone)]
+enum OwnedPromoted {
+    Int(SignedInt),
+    Uint(UnsignedInt),
+    Double(f64),
+    String(Option<Range<usize>>),
+    Pointer(usize),
+}
+
+/// A [`PointerExt`], with the bytes that it points to.
+#[cfg(feature = "pointer-ext")]
+#[derive(Debug, Clone)]
+enum OwnedPointerExt {
+    Ipv4 {
+        addr: [u8; 4],
+        leading_zeros: bool,
+    },
+    Ipv6 {
+        addr: [u8; 16],
+        format: Ipv6Format,
+    },
+    Mac {
+        addr: [u8; 6],
+        separator: Option<char>,
+        reversed: bool,
+    },
+    Uuid {
+        bytes: [u8; 16],
+        upper: bool,
+        little_endian: bool,
+    },
+    Hex {
+        bytes: Range<usize>,
+        separator: Option<char>,
+    },
+    Error(c_int),
+}
+
+/// Read a format string's arguments into a [`Record`], to format them later.
+///
+/// This uses the default [`Config`]; see [`Printf::capture`][crate::Printf::capture]
+/// to change it. `%n` is always forbidden, since nothing has been written
+/// yet.
+///
+/// # Safety
+///
+/// [`VaList`]s are *very* unsafe. The passed `format` and `args` parameter must be a valid [`printf` format string](http://www.cplusplus.com/reference/cstdio/printf/).
+pub unsafe fn capture(format: *const c_char, args: VaList) -> Result<Record, Error> {
+    unsafe { capture_with_config(format, args, &Config::new()) }
+}
+
+/// [`capture`], with the given [`Config`].
+pub(crate) unsafe fn capture_with_config(
+    format: *const c_char,
+    args: VaList,
+    config: &Config,
+) -> Result<Record, Error> {
+    let config = config.bytes_written(BytesWrittenPolicy::Reject);
+    let mut record = Record {
+        config,
+        items: Vec::new(),
+        bytes: Vec::new(),
+        wide: Vec::new(),
+    };
+    // Nothing is written yet, so the output limit is left for `try_format`.
+    let capture_config = match config.limits {
+        Some(limits) => config.limits(limits.max_output(usize::MAX)),
+        None => config,
+    };
+    unsafe { try_format_with_config(format, args, &capture_config, Capture(&mut record)) }?;
+    Ok(record)
+}
+
+impl Record {
+    /// The [`Config`] that it was captured with, and formats with.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Write the captured text and arguments to `sink`. Returns -1 on error.
+    pub fn format(&self, sink: impl Sink) -> c_int {
+        self.try_format(Untyped(sink)).unwrap_or(-1)
+    }
+
+    /// Write the captured text and arguments to `sink`, returning why it
+    /// failed instead of -1.
+    pub fn try_format<S: Sink>(&self, sink: S) -> Result<c_int, S::Error>
+    where
+        S::Error: From<Error>,
+    {
+        let mut sink = Limited::new(sink, &self.config);
+        let mut written = 0;
+        for item in &self.items {
+            written += match item {
+                Item::Literal(text) => sink.write_literal(self.bytes(text))?,
+                Item::Argument(arg) => sink.write_argument(self.argument(arg))?,
+            };
+        }
+        sink.finish()?;
+        Ok(written as c_int)
+    }
+
+    fn bytes(&self, range: &Range<usize>) -> &[u8] {
+        self.bytes.get(range.clone()).unwrap_or_default()
+    }
+
+    fn wide(&self, range: &Range<usize>) -> &[WChar] {
+        self.wide.get(range.clone()).unwrap_or_default()
+    }
+
+    fn c_str(&self, range: &Range<usize>) -> &CStr {
+        CStr::from_bytes_with_nul(self.bytes(range)).unwrap_or_default()
+    }
+
+    fn push_bytes(&mut self, bytes: &[u8]) -> Range<usize> {
+        let start = self.bytes.len();
+        self.bytes.extend_from_slice(bytes);
+        start..self.bytes.len()
+    }
+
+    fn push_wide(&mut self, wide: &[WChar]) -> Range<usize> {
+        let start = self.wide.len();
+        self.wide.extend_from_slice(wide);
+        start..self.wide.len()
+    }
+
+    /// Copy the borrowed parts of `specifier` into the buffers.
+    fn store(&mut self, specifier: Specifier) -> Result<OwnedSpecifier, Error> {
+        Ok(match specifier {
+            Specifier::Percent => OwnedSpecifier::Percent,
+            Specifier::Int(data) => OwnedSpecifier::Int(data),
+            Specifier::Uint(data) => OwnedSpecifier::Uint(data),
+            Specifier::Octal(data) => OwnedSpecifier::Octal(data),
+            Specifier::Double { value, format } => OwnedSpecifier::Double { value, format },
+            Specifier::Bytes(data) => OwnedSpecifier::Bytes(self.push_bytes(data)),
+            Specifier::String(data) => {
+                OwnedSpecifier::String(self.push_bytes(data.to_bytes_with_nul()))
+            }
+            Specifier::Char(data) => OwnedSpecifier::Char(data),
+            Specifier::WideText(data) => OwnedSpecifier::WideText(self.push_wide(data)),
+            Specifier::WideString(data) => OwnedSpecifier::WideString(self.push_wide(data)),
+            Specifier::WideChar(data) => OwnedSpecifier::WideChar(data),
+            Specifier::Hex(data) => OwnedSpecifier::Hex(data),
+            Specifier::UpperHex(data) => OwnedSpecifier::UpperHex(data),
+            Specifier::Binary(data) => OwnedSpecifier::Binary(data),
+            Specifier::UpperBinary(data) => OwnedSpecifier::UpperBinary(data),
+            Specifier::Pointer(data) => OwnedSpecifier::Pointer(data.addr()),
+            Specifier::Custom { conversion, value } => OwnedSpecifier::Custom {
+                conversion,
+                value: match value {
+                    Promoted::Int(data) => OwnedPromoted::Int(data),
+                    Promoted::Uint(data) => OwnedPromoted::Uint(data),
+                    Promoted::Double(data) => OwnedPromoted::Double(data),
+                    Promoted::String(data) => OwnedPromoted::String(
+                        data.map(|data| self.push_bytes(data.to_bytes_with_nul())),
+                    ),
+                    Promoted::Pointer(data) => OwnedPromoted::Pointer(data.addr()),
+                },
+            },
+            #[cfg(feature = "pointer-ext")]
+            Specifier::PointerExt(data) => OwnedSpecifier::PointerExt(match data {
+                PointerExt::Ipv4 {
+                    addr,
+                    leading_zeros,
+                } => OwnedPointerExt::Ipv4 {
+                    addr: *addr,
+                    leading_zeros,
+                },
+                PointerExt::Ipv6 { addr, format } => OwnedPointerExt::Ipv6 {
+                    addr: *addr,
+                    format,
+                },
+                PointerExt::Mac {
+                    addr,
+                    separator,
+                    reversed,
+                } => OwnedPointerExt::Mac {
+                    addr: *addr,
+                    separator,
+                    reversed,
+                },
+                PointerExt::Uuid {
+                    bytes,
+                    upper,
+                    little_endian,
+                } => OwnedPointerExt::Uuid {
+                    bytes: *bytes,
+                    upper,
+                    little_endian,
+                },
+                PointerExt::Hex { bytes, separator } => OwnedPointerExt::Hex {
+                    bytes: self.push_bytes(bytes),
+                    separator,
+                },
+                PointerExt::Error(errno) => OwnedPointerExt::Error(errno),
+            }),
+            Specifier::Errno(errno) => OwnedSpecifier::Errno(errno),
+            Specifier::WriteBytesWritten(_, _) => return Err(Error::Forbidden(b'n')),
+            Specifier::Disabled(ch) => OwnedSpecifier::Disabled(ch),
+        })
+    }
+
+    /// Turn a stored argument back into an [`Argument`].
+    fn argument<'a>(&'a self, arg: &'a OwnedArgument) -> Argument<'a> {
+        let specifier = match &arg.specifier {
+            OwnedSpecifier::Percent => Specifier::Percent,
+            OwnedSpecifier::Int(data) => Specifier::Int(*data),
+            OwnedSpecifier::Uint(data) => Specifier::Uint(*data),
+            OwnedSpecifier::Octal(data) => Specifier::Octal(*data),
+            OwnedSpecifier::Double { value, format } => Specifier::Double {
+                value: *value,
+                format: *format,
+            },
+            OwnedSpecifier::Bytes(data) => Specifier::Bytes(self.bytes(data)),
+            OwnedSpecifier::String(data) => Specifier::String(self.c_str(data)),
+            OwnedSpecifier::Char(data) => Specifier::Char(*data),
+            OwnedSpecifier::WideText(data) => Specifier::WideText(self.wide(data)),
+            OwnedSpecifier::WideString(data) => Specifier::WideString(self.wide(data)),
+            OwnedSpecifier::WideChar(data) => Specifier::WideChar(*data),
+            OwnedSpecifier::Hex(data) => Specifier::Hex(*data),
+            OwnedSpecifier::UpperHex(data) => Specifier::UpperHex(*data),
+            OwnedSpecifier::Binary(data) => Specifier::Binary(*data),
+            OwnedSpecifier::UpperBinary(data) => Specifier::UpperBinary(*data),
+            OwnedSpecifier::Pointer(addr) => {
+                Specifier::Pointer(core::ptr::without_provenance(*addr))
+            }
+            OwnedSpecifier::Custom { conversion, value } => Specifier::Custom {
+                conversion: *conversion,
+                value: match value {
+                    OwnedPromoted::Int(data) => Promoted::Int(*data),
+                    OwnedPromoted::Uint(data) => Promoted::Uint(*data),
+                    OwnedPromoted::Double(data) => Promoted::Double(*data),
+                    OwnedPromoted::String(data) => {
+                        Promoted::String(data.as_ref().map(|data| self.c_str(data)))
+                    }
+                    OwnedPromoted::Pointer(addr) => {
+                        Promoted::Pointer(core::ptr::without_provenance(*addr))
+                    }
+                },
+            },
+            #[cfg(feature = "pointer-ext")]
+            OwnedSpecifier::PointerExt(data) => Specifier::PointerExt(match data {
+                OwnedPointerExt::Ipv4 {
+                    addr,
+                    leading_zeros,
+                } => PointerExt::Ipv4 {
+                    addr,
+                    leading_zeros: *leading_zeros,
+                },
+                OwnedPointerExt::Ipv6 { addr, format } => PointerExt::Ipv6 {
+                    addr,
+                    format: *format,
+                },
+                OwnedPointerExt::Mac {
+                    addr,
+                    separator,
+                    reversed,
+                } => PointerExt::Mac {
+                    addr,
+                    separator: *separator,
+                    reversed: *reversed,
+                },
+                OwnedPointerExt::Uuid {
+                    bytes,
+                    upper,
+                    little_endian,
+                } => PointerExt::Uuid {
+                    bytes,
+                    upper: *upper,
+                    little_endian: *little_endian,
+                },
+                OwnedPointerExt::Hex { bytes, separator } => PointerExt::Hex {
+                    bytes: self.bytes(bytes),
+                    separator: *separator,
+                },
+                OwnedPointerExt::Error(errno) => PointerExt::Error(*errno),
+            }),
+            OwnedSpecifier::Errno(errno) => Specifier::Errno(*errno),
+            OwnedSpecifier::Disabled(ch) => Specifier::Disabled(*ch),
+        };
+        Argument {
+            flags: arg.flags,
+            width: arg.width,
+            precision: arg.precision,
+            specifier,
+        }
+    }
+}
+
+/// Formats the record with the dialect and locale of its [`Config`].
+impl fmt::Display for Record {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sink = output::fmt_write_locale(f, self.config.dialect, self.config.locale);
+        match self.try_format(sink) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(fmt::Error),
+        }
+    }
+}
+
+/// The [`Sink`] that fills in a [`Record`].
+///
+/// Nothing is written yet, so arguments count as 0 bytes.
+struct Capture<'a>(&'a mut Record);
+
+impl Sink for Capture<'_> {
+    type Error = Error;
+
+    fn write_literal(&mut self, text: &[u8]) -> Result<usize, Error> {
+        let range = self.0.push_bytes(text);
+        self.0.items.push(Item::Literal(range));
+        Ok(text.len())
+    }
+
+    fn write_argument(&mut self, arg: Argument) -> Result<usize, Error> {
+        let specifier = self.0.store(arg.specifier)?;
+        self.0.items.push(Item::Argument(OwnedArgument {
+            flags: arg.flags,
+            width: arg.width,
+            precision: arg.precision,
+            specifier,
+        }));
+        Ok(0)
+    }
+}
@@ -0,0 +1,356 @@
+//! Parsing input with a format string, like C's `sscanf`.
+//!
+//! ```rust
+//! # #![feature(c_variadic)]
+//! use core::ffi::{c_char, c_int};
+//!
+//! #[unsafe(no_mangle)]
+//! unsafe extern "C" fn sscanf(input: *const c_char, format: *const c_char, args: ...) -> c_int {
+//!     unsafe { printf_compat::scan::vsscanf(input, format, args) }
+//! }
+//! ```
+//!
+//! # Differences
+//!
+//! - Wide strings (`%ls`, `%lc` and `%l[`) are not supported, and make
+//!   scanning fail with `-1`.
+//! - `%a` and the other floating-point conversions don't accept hexadecimal
+//!   floats, and require the `float` cargo feature.
+//! - Integers that don't fit in their type wrap around instead of saturating.
+
+use core::ffi::*;
+use core::ptr;
+
+use printf_compat_spec::{Count, Extensions, Length, parse_length, parse_width};
+
+/// `isspace` in the C locale.
+fn is_space(c: u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\n' | b'\x0b' | b'\x0c' | b'\r')
+}
+
+fn skip_space(s: &[u8]) -> &[u8] {
+    let start = s.iter().position(|&c| !is_space(c)).unwrap_or(s.len());
+    &s[start..]
+}
+
+/// Parse an integer in `base` (or, if `base` is 0, the base given by its
+/// prefix, like `strtol`) from the first `width` bytes of `input`.
+fn scan_int(input: &mut &[u8], width: usize, mut base: u32) -> Option<u64> {
+    let field = &input[..width.min(input.len())];
+    let mut i = 0;
+    let negative = match field.first() {
+        Some(b'-') => {
+            i += 1;
+            true
+        }
+        Some(b'+') => {
+            i += 1;
+            false
+        }
+        _ => false,
+    };
+    let has_hex_prefix =
+        field.get(i) == Some(&b'0') && matches!(field.get(i + 1), Some(b'x' | b'X'));
+    // Like glibc, a `0x` prefix without any digits after it is read as 0.
+    let mut zero = false;
+    if (base == 0 || base == 16) && has_hex_prefix {
+        i += 2;
+        base = 16;
+        zero = true;
+    } else if base == 0 {
+        base = if field.get(i) == Some(&b'0') { 8 } else { 10 };
+    }
+    let start = i;
+    let mut value: u64 = 0;
+    while let Some(digit) = field.get(i).and_then(|&c| (c as char).to_digit(base)) {
+        value = value.wrapping_mul(base.into()).wrapping_add(digit.into());
+        i += 1;
+    }
+    if i == start && !zero {
+        return None;
+    }
+    *input = &input[i..];
+    Some(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
+
+/// Parse a decimal floating-point number, infinity or NaN, like `strtod`,
+/// from the first `width` bytes of `input`.
+#[cfg(feature = "float")]
+fn scan_float(input: &mut &[u8], width: usize) -> Option<f64> {
+    let field = &input[..width.min(input.len())];
+    let mut i = usize::from(matches!(field.first(), Some(b'+' | b'-')));
+    let digits = |i: &mut usize| {
+        let start = *i;
+        while field.get(*i).is_some_and(u8::is_ascii_digit) {
+            *i += 1;
+        }
+        *i - start
+    };
+    let word = [&b"infinity"[..], b"inf", b"nan"].into_iter().find(|word| {
+        field[i..]
+            .get(..word.len())
+            .is_some_and(|s| s.eq_ignore_ascii_case(word))
+    });
+    let mut end = None;
+    if let Some(word) = word {
+        i += word.len();
+    } else {
+        let mut count = digits(&mut i);
+        if field.get(i) == Some(&b'.') {
+            i += 1;
+            count += digits(&mut i);
+        }
+        if count == 0 {
+            return None;
+        }
+        if matches!(field.get(i), Some(b'e' | b'E')) {
+            // Like glibc, an exponent without any digits is consumed, but
+            // ignored.
+            let mantissa = i;
+            i += 1;
+            if matches!(field.get(i), Some(b'+' | b'-')) {
+                i += 1;
+            }
+            if digits(&mut i) == 0 {
+                end = Some(mantissa);
+            }
+        }
+    }
+    let number = &field[..end.unwrap_or(i)];
+    let value = core::str::from_utf8(number).ok()?.parse().ok()?;
+    *input = &input[i..];
+    Some(value)
+}
+
+/// Store the low bits of `value` through the next pointer argument, with the
+/// type given by the length modifier.
+unsafe fn store_int(args: &mut VaList, length: Length, value: u64) {
+    unsafe fn store<T>(args: &mut VaList, value: T) {
+        let ptr: *mut T = unsafe { args.next_arg() };
+        unsafe { ptr.write_unaligned(value) };
+    }
+    unsafe {
+        match length {
+            Length::Int => store(args, value as c_int),
+            Length::Char => store(args, value as c_schar),
+            Length::Short => store(args, value as c_short),
+            Length::Long => store(args, value as c_long),
+            Length::LongLong => store(args, value as c_longlong),
+            Length::Usize | Length::Isize => store(args, value as isize),
+        }
+    }
+}
+
+/// Copy `bytes` to the next `char *` argument, adding a null terminator if
+/// `terminate` is set.
+unsafe fn store_bytes(args: &mut VaList, bytes: &[u8], terminate: bool) {
+    let ptr: *mut u8 = unsafe { args.next_arg::<*mut c_char>() }.cast();
+    unsafe {
+        ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+        if terminate {
+            ptr.add(bytes.len()).write(0);
+        }
+    }
+}
+
+/// Parse the scanset of a `%[` conversion, just after the `[`. Returns the
+/// set, whether it's negated, and the rest of the format string.
+fn parse_scanset(sub: &[u8]) -> Option<([bool; 256], &[u8])> {
+    let (negated, sub) = match sub.first() {
+        Some(b'^') => (true, &sub[1..]),
+        _ => (false, sub),
+    };
+    // A `]` right at the start is part of the set.
+    let end = sub.iter().skip(1).position(|&c| c == b']').map(|i| i + 1)?;
+    let (chars, rest) = (&sub[..end], &sub[end + 1..]);
+    let mut set = [negated; 256];
+    let mut i = 0;
+    while i < chars.len() {
+        // A `-` that isn't first or last is a range, as in glibc.
+        if i > 0 && chars[i] == b'-' && i + 1 < chars.len() && chars[i - 1] <= chars[i + 1] {
+            for c in chars[i - 1]..=chars[i + 1] {
+                set[c as usize] = !negated;
+            }
+            i += 2;
+        } else {
+            set[chars[i] as usize] = !negated;
+            i += 1;
+        }
+    }
+    Some((set, rest))
+}
+
+/// Read formatted input from a string, like C's `vsscanf`.
+///
+/// Returns the number of input items assigned, which can be fewer than the
+/// number of conversions if there was a matching failure. If the input ends
+/// before anything was assigned, returns `-1` (`EOF`). An invalid format
+/// string also returns `-1`.
+///
+/// # Safety
+///
+/// `input` and `format` must be null-terminated strings. `args` must contain
+/// a valid pointer for every conversion in `format` that isn't suppressed
+/// with `*`, pointing to enough space for the converted value.
+pub unsafe fn vsscanf(input: *const c_char, format: *const c_char, mut args: VaList) -> c_int {
+    let original = unsafe { CStr::from_ptr(input).to_bytes() };
+    let mut input = original;
+    let mut format = unsafe { CStr::from_ptr(format).to_bytes() };
+    let mut assigned: c_int = 0;
+
+    // The input ran out: like glibc, that's only an error if nothing was
+    // assigned yet.
+    let input_failure = |assigned| if assigned == 0 { -1 } else { assigned };
+
+    while let Some(&ch) = format.first() {
+        if is_space(ch) {
+            format = skip_space(format);
+            input = skip_space(input);
+            continue;
+        }
+        if ch != b'%' {
+            match input.first() {
+                Some(&c) if c == ch => {
+                    input = &input[1..];
+                    format = &format[1..];
+                    continue;
+                }
+                Some(_) => return assigned,
+                None => return input_failure(assigned),
+            }
+        }
+        // A `*` before the width parses as an argument width, but here it
+        // means that the result is discarded.
+        let Some((suppress, sub)) = parse_width(&format[1..]) else {
+            return -1;
+        };
+        let (suppress, (width, sub)) = match suppress {
+            Count::Arg => match parse_width(sub) {
+                Some(width) => (true, width),
+                None => return -1,
+            },
+            width => (false, (width, sub)),
+        };
+        let width = match width {
+            Count::Value(0) => None,
+            Count::Value(width) => Some(width as usize),
+            Count::Arg | Count::Position(_) => return -1,
+        };
+        let (length, sub) = parse_length(sub, Extensions::empty());
+        let Some(&conversion) = sub.first() else {
+            return -1;
+        };
+        format = &sub[1..];
+        if length != Length::Int && matches!(conversion, b'%' | b's' | b'c' | b'[') {
+            return -1;
+        }
+
+        if !matches!(conversion, b'c' | b'[' | b'n') {
+            input = skip_space(input);
+        }
+        if input.is_empty() && conversion != b'n' {
+            return input_failure(assigned);
+        }
+        let field_width = width.unwrap_or(usize::MAX);
+        let before = input;
+        match conversion {
+            b'%' => {
+                if input[0] != b'%' {
+                    return assigned;
+                }
+                input = &input[1..];
+                continue;
+            }
+            b'n' => {
+                if !suppress {
+                    let count = (original.len() - input.len()) as u64;
+                    unsafe { store_int(&mut args, length, count) };
+                }
+                continue;
+            }
+            b'd' | b'i' | b'u' | b'o' | b'x' | b'X' | b'p' => {
+                let base = match conversion {
+                    b'd' | b'u' => 10,
+                    b'i' => 0,
+                    b'o' => 8,
+                    _ => 16,
+                };
+                let Some(value) = scan_int(&mut input, field_width, base) else {
+                    return assigned;
+                };
+                if !suppress {
+                    if conversion == b'p' {
+                        let ptr: *mut *mut c_void = unsafe { args.next_arg() };
+                        unsafe { ptr.write_unaligned(value as usize as *mut c_void) };
+                    } else {
+                        unsafe { store_int(&mut args, length, value) };
+                    }
+                }
+            }
+            #[cfg(feature = "float")]
+            b'f' | b'F' | b'e' | b'E' | b'g' | b'G' | b'a' | b'A' => {
+                let Some(value) = scan_float(&mut input, field_width) else {
+                    return assigned;
+                };
+                if !suppress {
+                    match length {
+                        Length::Int => {
+                            let ptr: *mut f32 = unsafe { args.next_arg() };
+                            unsafe { ptr.write_unaligned(value as f32) };
+                        }
+                        Length::Long => {
+                            let ptr: *mut f64 = unsafe { args.next_arg() };
+                            unsafe { ptr.write_unaligned(value) };
+                        }
+                        _ => return -1,
+                    }
+                }
+            }
+            b's' => {
+                let len = input
+                    .iter()
+                    .take(field_width)
+                    .position(|&c| is_space(c))
+                    .unwrap_or(field_width.min(input.len()));
+                input = &input[len..];
+                if !suppress {
+                    unsafe { store_bytes(&mut args, &before[..len], true) };
+                }
+            }
+            b'c' => {
+                let len = width.unwrap_or(1).min(input.len());
+                input = &input[len..];
+                if !suppress {
+                    unsafe { store_bytes(&mut args, &before[..len], false) };
+                }
+            }
+            b'[' => {
+                let Some((set, rest)) = parse_scanset(format) else {
+                    return -1;
+                };
+                format = rest;
+                let len = input
+                    .iter()
+                    .take(field_width)
+                    .position(|&c| !set[c as usize])
+                    .unwrap_or(field_width.min(input.len()));
+                if len == 0 {
+                    return assigned;
+                }
+                input = &input[len..];
+                if !suppress {
+                    unsafe { store_bytes(&mut args, &before[..len], true) };
+                }
+            }
+            _ => return -1,
+        }
+        if !suppress {
+            assigned += 1;
+        }
+    }
+    assigned
+}
@@ -0,0 +1,107 @@
+#![feature(c_variadic)]
+
+use core::ffi::*;
+
+unsafe extern "C" {
+    fn sscanf(s: *const c_char, format: *const c_char, ...) -> c_int;
+}
+
+unsafe extern "C" fn rust_sscanf(s: *const c_char, format: *const c_char, args: ...) -> c_int {
+    unsafe { printf_compat::scan::vsscanf(s, format, args) }
+}
+
+/// Assert that `rust_sscanf` returns the same value and stores the same
+/// results as C's `sscanf`, and that it returns `expected`.
+///
+/// This takes the input, the format, and a name and type for each
+/// argument, followed by `=>` and then the expected return value. Each
+/// argument is zero-initialized and passed as a pointer.
+///
+/// Example usage:
+///
+/// ```
+/// assert_eq_scan!(c"1 2", c"%d %d", a: c_int, b: c_int => 2);
+/// ```
+macro_rules! assert_eq_scan {
+    ($input:literal, $format:literal $(, $name:ident: $ty:ty)* => $expected:expr) => {{
+        let c = {
+            $(let mut $name: $ty = Default::default();)*
+            let ret = unsafe { sscanf($input.as_ptr(), $format.as_ptr() $(, &mut $name as *mut $ty)*) };
+            (ret, $($name,)*)
+        };
+        let rust = {
+            $(let mut $name: $ty = Default::default();)*
+            let ret = unsafe { rust_sscanf($input.as_ptr(), $format.as_ptr() $(, &mut $name as *mut $ty)*) };
+            (ret, $($name,)*)
+        };
+        assert_eq!(c, rust, "{:?} with {:?}", $input, $format);
+        assert_eq!(c.0, $expected, "{:?} with {:?}", $input, $format);
+    }};
+}
+
+#[test]
+fn test_int() {
+    assert_eq_scan!(c"42", c"%d", a: c_int => 1);
+    assert_eq_scan!(c"  -17 +5", c"%d%d", a: c_int, b: c_int => 2);
+    assert_eq_scan!(c"12345", c"%2d%3d", a: c_int, b: c_int => 2);
+    assert_eq_scan!(c"0x1f 017 9", c"%i %i %i", a: c_int, b: c_int, c: c_int => 3);
+    assert_eq_scan!(c"ff 0XFF 777 4294967295", c"%x %X %o %u", a: c_uint, b: c_uint, c: c_uint, d: c_uint => 4);
+    assert_eq_scan!(c"-1", c"%u", a: c_uint => 1);
+    assert_eq_scan!(c"0xg", c"%x%c", a: c_uint, b: c_char => 2);
+}
+
+#[test]
+fn test_int_length() {
+    assert_eq_scan!(c"-5 300 -70000", c"%hhd %hd %ld", a: c_schar, b: c_short, c: c_long => 3);
+    assert_eq_scan!(c"-9223372036854775808 18446744073709551615", c"%lld %llu", a: c_longlong, b: c_ulonglong => 2);
+    assert_eq_scan!(c"123 456", c"%zu %td", a: usize, b: isize => 2);
+    assert_eq_scan!(c"255 65535", c"%hhu %hx", a: c_uchar, b: c_ushort => 2);
+}
+
+#[cfg(feature = "float")]
+#[test]
+fn test_float() {
+    assert_eq_scan!(c"1.5 -2e3 .25", c"%f %e %g", a: f32, b: f32, c: f32 => 3);
+    assert_eq_scan!(c"3.141592653589793 1e-300", c"%lf %le", a: f64, b: f64 => 2);
+    assert_eq_scan!(c"1e+ 5.", c"%lf%c %lf", a: f64, b: c_char, c: f64 => 3);
+    assert_eq_scan!(c"123.456", c"%4lf%lf", a: f64, b: f64 => 2);
+    assert_eq_scan!(c"inf -INFINITY", c"%lf %lf", a: f64, b: f64 => 2);
+    assert_eq_scan!(c"x", c"%lf", a: f64 => 0);
+}
+
+#[test]
+fn test_str() {
+    assert_eq_scan!(c"  hello world", c"%s%s", a: [c_char; 16], b: [c_char; 16] => 2);
+    assert_eq_scan!(c"abcdefgh", c"%3s%s", a: [c_char; 16], b: [c_char; 16] => 2);
+    assert_eq_scan!(c" abc", c"%c%2c", a: c_char, b: [c_char; 4] => 2);
+    assert_eq_scan!(c"ab", c"%3c", a: [c_char; 4] => 1);
+}
+
+#[test]
+fn test_scanset() {
+    assert_eq_scan!(c"abc123def", c"%[a-z]%[0-9]%s", a: [c_char; 16], b: [c_char; 16], c: [c_char; 16] => 3);
+    assert_eq_scan!(c"key=value", c"%[^=]=%s", a: [c_char; 16], b: [c_char; 16] => 2);
+    assert_eq_scan!(c"]]-x", c"%[]-]%c", a: [c_char; 16], b: c_char => 2);
+    assert_eq_scan!(c"aaaa", c"%2[a]", a: [c_char; 16] => 1);
+    assert_eq_scan!(c"123", c"%[a-z]", a: [c_char; 16] => 0);
+}
+
+#[test]
+fn test_suppress_and_count() {
+    assert_eq_scan!(c"1 2 3", c"%*d %d %n%*d%n", a: c_int, b: c_int, c: c_int => 1);
+    assert_eq_scan!(c"abc", c"%*s%n", a: c_int => 0);
+    assert_eq_scan!(c"10%", c"%d%%%hhn", a: c_int, b: c_schar => 1);
+    assert_eq_scan!(c"0x10", c"%p", a: usize => 1);
+}
+
+#[test]
+fn test_failures() {
+    assert_eq_scan!(c"", c"%d", a: c_int => -1);
+    assert_eq_scan!(c"   ", c"%d", a: c_int => -1);
+    assert_eq_scan!(c"", c"abc" => -1);
+    assert_eq_scan!(c"abc", c"%d", a: c_int => 0);
+    assert_eq_scan!(c"1 x", c"%d %d", a: c_int, b: c_int => 1);
+    assert_eq_scan!(c"1", c"%d %d", a: c_int, b: c_int => 1);
+    assert_eq_scan!(c"5", c"%*d%d", a: c_int => -1);
+    assert_eq_scan!(c"a:b", c"a;%c", a: c_char => 0);
+}
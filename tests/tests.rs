@@ -21,6 +21,43 @@ unsafe extern "C" fn rust_fmt(str: *const c_char, args: ...) -> Box<(c_int, Stri
     Box::new((bytes_written, s))
 }
 
+/// Format with `config`, writing with [`printf_compat::output::fmt_write`].
+unsafe extern "C" fn rust_fmt_with_config(
+    config: &printf_compat::Config,
+    str: *const c_char,
+    args: ...
+) -> Box<(c_int, String)> {
+    let mut s = String::new();
+    let written = unsafe {
+        printf_compat::format_with_config(
+            str,
+            args,
+            config,
+            printf_compat::output::fmt_write(&mut s),
+        )
+    };
+    Box::new((written, s))
+}
+
+/// Format with `dialect` for both parsing and output.
+unsafe extern "C" fn rust_fmt_dialect(
+    dialect: &printf_compat::Dialect,
+    str: *const c_char,
+    args: ...
+) -> Box<(c_int, String)> {
+    let config = printf_compat::Config::new().dialect(*dialect);
+    let mut s = String::new();
+    let written = unsafe {
+        printf_compat::format_with_config(
+            str,
+            args,
+            &config,
+            printf_compat::output::fmt_write_dialect(&mut s, *dialect),
+        )
+    };
+    Box::new((written, s))
+}
+
 macro_rules! c_fmt {
     ($format:literal $(, $p:expr)*) => {{
         let mut ptr = null_mut();
@@ -59,6 +96,7 @@ fn assert_fmt_err(fmt: &CStr) {
     let bytes_written = unsafe { asprintf(&mut ptr, fmt.as_ptr()) };
     assert_eq!(bytes_written, -1, "asprintf parse unexpectedly succeeded");
 
+    #[allow(clippy::let_and_return)]
     unsafe extern "C" fn format(str: *const c_char, args: ...) -> c_int {
         let mut s = String::new();
         let bytes_written = unsafe {
@@ -84,7 +122,208 @@ fn test_plain() {
         assert_eq_fmt!(c"abc %% def" => "abc % def");
         assert_eq_fmt!(c"abc %%%% def" => "abc %% def");
         assert_eq_fmt!(c"%%%%%%" => "%%%");
+        assert_eq_fmt!(c"%%%d|100%%|%%d", 7 => "%7|100%|%d");
+        assert_eq_fmt!(c"a%%%%b|% d|% 3d", 5, -5 => "a%%b| 5| -5");
+        // A width or flags on `%%` are ignored.
+        assert_eq_fmt!(c"[%5%][%-5%][% %]" => "[%][%][%]");
+    }
+}
+
+#[test]
+fn test_handler_calls() {
+    use printf_compat::argument::Specifier;
+    use printf_compat::{format_slice, output};
+
+    fn calls(format: &CStr, args: &[&dyn printf_compat::PrintfArg]) -> Vec<String> {
+        let mut calls = Vec::new();
+        format_slice(
+            format,
+            args,
+            output::handler(|arg| {
+                calls.push(match arg.specifier {
+                    Specifier::Bytes(text) => String::from_utf8(text.to_vec()).unwrap(),
+                    Specifier::Percent => "Percent".into(),
+                    Specifier::Int(i) => format!("Int({i})"),
+                    Specifier::String(s) => format!("String({s:?})"),
+                    other => panic!("unexpected {other:?}"),
+                });
+                1
+            }),
+        )
+        .unwrap();
+        calls
+    }
+
+    assert_eq!(calls(c"", &[]), [""; 0]);
+    assert_eq!(calls(c"abc", &[]), ["abc"]);
+    assert_eq!(calls(c"%d", &[&1]), ["Int(1)"]);
+    assert_eq!(calls(c"%d%d", &[&1, &2]), ["Int(1)", "Int(2)"]);
+    assert_eq!(calls(c"a%db", &[&1]), ["a", "Int(1)", "b"]);
+    assert_eq!(calls(c"100%%", &[]), ["100%"]);
+    assert_eq!(calls(c"%%", &[]), ["%"]);
+    assert_eq!(calls(c"a %% b", &[]), ["a %", " b"]);
+    assert_eq!(calls(c"%%%%", &[]), ["%", "%"]);
+    assert_eq!(
+        calls(c"%s: 50%% of %d%%\n", &[&c"x", &8]),
+        ["String(\"x\")", ": 50%", " of ", "Int(8)", "%", "\n"]
+    );
+    // `%%` with flags or a width is still a conversion.
+    assert_eq!(calls(c"a%5%b", &[]), ["a", "Percent", "b"]);
+
+    // `format` takes a closure without naming its argument's type.
+    unsafe extern "C" fn count_ints(str: *const c_char, args: ...) -> c_int {
+        let mut ints = 0;
+        let written = unsafe {
+            printf_compat::format(str, args, |arg| {
+                if let Specifier::Int(_) = arg.specifier {
+                    ints += 1;
+                }
+                0
+            })
+        };
+        assert_eq!(written, 0);
+        ints
+    }
+    assert_eq!(unsafe { count_ints(c"%d and %d".as_ptr(), 1, 2) }, 2);
+}
+
+#[test]
+fn test_sink() {
+    use printf_compat::argument::Argument;
+    use printf_compat::output::{Sink, io_sink};
+    use printf_compat::{Error, format_slice};
+
+    #[derive(Debug, PartialEq)]
+    enum SinkError {
+        Format(Error),
+        Full,
+    }
+
+    impl From<Error> for SinkError {
+        fn from(e: Error) -> Self {
+            SinkError::Format(e)
+        }
+    }
+
+    /// Records each call, failing after `capacity` bytes.
+    struct Recorder<'a> {
+        calls: &'a mut Vec<String>,
+        capacity: usize,
+    }
+
+    impl Sink for Recorder<'_> {
+        type Error = SinkError;
+
+        fn write_literal(&mut self, text: &[u8]) -> Result<usize, SinkError> {
+            self.capacity = self
+                .capacity
+                .checked_sub(text.len())
+                .ok_or(SinkError::Full)?;
+            self.calls
+                .push(format!("literal {:?}", String::from_utf8_lossy(text)));
+            Ok(text.len())
+        }
+
+        fn write_argument(&mut self, arg: Argument) -> Result<usize, SinkError> {
+            self.capacity = self.capacity.checked_sub(1).ok_or(SinkError::Full)?;
+            self.calls.push(format!("argument {:?}", arg.specifier));
+            Ok(1)
+        }
+
+        fn finish(&mut self) -> Result<(), SinkError> {
+            self.calls.push("finish".into());
+            Ok(())
+        }
+    }
+
+    fn calls(
+        format: &CStr,
+        args: &[&dyn printf_compat::PrintfArg],
+        capacity: usize,
+    ) -> (Result<c_int, SinkError>, Vec<String>) {
+        let mut calls = Vec::new();
+        let sink = Recorder {
+            calls: &mut calls,
+            capacity,
+        };
+        (format_slice(format, args, sink), calls)
+    }
+
+    assert_eq!(
+        calls(c"a %d%%", &[&5], 10),
+        (
+            Ok(4),
+            vec![
+                "literal \"a \"".into(),
+                "argument Int(Int(5))".into(),
+                "literal \"%\"".into(),
+                "finish".into()
+            ]
+        )
+    );
+    assert_eq!(calls(c"", &[], 0), (Ok(0), vec!["finish".into()]));
+    // `finish` isn't called when formatting fails.
+    assert_eq!(
+        calls(c"a%d", &[&5], 1),
+        (Err(SinkError::Full), vec!["literal \"a\"".into()])
+    );
+    assert_eq!(
+        calls(c"a%d", &[], 10),
+        (
+            Err(SinkError::Format(Error::MissingArgument)),
+            vec!["literal \"a\"".into()]
+        )
+    );
+
+    // Closures report their failures as `Error::Handler`.
+    assert_eq!(
+        format_slice(c"a", &[], |_: Argument| -1),
+        Err(Error::Handler)
+    );
+    // `c_int` functions report any error as -1.
+    unsafe extern "C" fn format_full(str: *const c_char, args: ...) -> c_int {
+        let mut calls = Vec::new();
+        let sink = Recorder {
+            calls: &mut calls,
+            capacity: 1,
+        };
+        unsafe { printf_compat::format_sink(str, args, sink) }
+    }
+    unsafe {
+        assert_eq!(format_full(c"%d".as_ptr(), 1), 1);
+        assert_eq!(format_full(c"%d%d".as_ptr(), 1, 2), -1);
+    }
+
+    /// A writer that fails after `0` bytes, and records whether it was flushed.
+    struct Limited(usize, bool);
+
+    impl std::io::Write for Limited {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if buf.len() > self.0 {
+                return Err(std::io::ErrorKind::WriteZero.into());
+            }
+            self.0 -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.1 = true;
+            Ok(())
+        }
     }
+
+    let mut w = Limited(8, false);
+    assert_eq!(format_slice(c"%5d|", &[&1], io_sink(&mut w)).unwrap(), 6);
+    assert!(w.1);
+    let mut w = Limited(3, false);
+    let err = format_slice(c"%5d|", &[&1], io_sink(&mut w)).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::WriteZero);
+    assert!(!w.1);
+    let err = format_slice(c"%d", &[], io_sink(&mut Vec::new())).unwrap_err();
+    assert_eq!(
+        *err.get_ref().unwrap().downcast_ref::<Error>().unwrap(),
+        Error::MissingArgument
+    );
 }
 
 #[test]
@@ -101,6 +340,24 @@ fn test_str() {
     }
 }
 
+/// Widths over [`u16::MAX`], which `std`'s formatting can't pad to.
+#[test]
+fn test_long_width() {
+    macro_rules! assert_eq_c {
+        ($format:literal $(, $p:expr)*) => {
+            assert_eq!(*rust_fmt($format.as_ptr() $(, $p)*), c_fmt!($format $(, $p)*));
+        };
+    }
+
+    unsafe {
+        let p = 0x1234 as *const c_void;
+        assert_eq_c!(c"%70000p|%-70000p|%70000p", p, p, null_mut::<c_void>());
+        assert_eq_c!(c"%#70000o|%#-70000o|%#070000o", 8, 8, 8);
+        assert_eq_c!(c"%70000b|%-70000b|%#070000b", 5, 5, 5);
+        assert_eq_c!(c"%.70000b|%#70001.70000B|%.70000b", 5, 5, 0);
+    }
+}
+
 #[test]
 fn test_int() {
     unsafe {
@@ -121,6 +378,8 @@ fn test_int() {
         assert_eq_fmt!(c"%-10i", 23125 => "23125     ");
         assert_eq_fmt!(c"%-5i", 23125 => "23125");
         assert_eq_fmt!(c"%-4i", 23125 => "23125");
+        assert_eq_fmt!(c"%*i|", -7, 23125 => "23125  |");
+        assert_eq_fmt!(c"%.*i|", -7, 23125 => "23125|");
     }
 }
 
@@ -233,12 +492,125 @@ fn test_hex() {
     }
 }
 
+#[test]
+fn test_binary() {
+    use printf_compat::Dialect;
+
+    unsafe {
+        assert_eq_fmt!(c"%b|%B", 10, 10 => "1010|1010");
+        assert_eq_fmt!(c"%b|%#b|%.0b|%#.0b", 0, 0, 0, 0 => "0|0||");
+        assert_eq_fmt!(c"%8b|%-8b|%08b", 5, 5, 5 => "     101|101     |00000101");
+        assert_eq_fmt!(c"%#b|%#B|%#8b|%#-8B|%#08b", 5, 5, 5, 5, 5 => "0b101|0B101|   0b101|0B101   |0b000101");
+        assert_eq_fmt!(c"%.5b|%8.5b|%08.5b|%#.5b", 5, 5, 5, 5 => "00101|   00101|   00101|0b00101");
+        assert_eq_fmt!(c"%hhb|%hb|%lb", -1, 0x1ffff, 1 as c_ulong => "11111111|1111111111111111|1");
+        assert_eq_fmt!(c"%llB", u64::MAX => "1111111111111111111111111111111111111111111111111111111111111111");
+
+        assert_eq!(
+            *rust_fmt_dialect(&Dialect::NewlibNano, c"%b".as_ptr(), 5),
+            (3, "101".into())
+        );
+        for dialect in [Dialect::Msvc, Dialect::C11] {
+            assert_eq!(rust_fmt_dialect(&dialect, c"%b".as_ptr(), 5).0, -1);
+            assert_eq!(rust_fmt_dialect(&dialect, c"%B".as_ptr(), 5).0, -1);
+        }
+    }
+}
+
+#[cfg(feature = "float")]
 #[test]
 fn test_float() {
     unsafe {
         assert_eq_fmt!(c"%f", 1234f64 => "1234.000000");
         assert_eq_fmt!(c"%.5f", 1234f64 => "1234.00000");
         assert_eq_fmt!(c"%.*f", 1234f64, 3 => "1234.000");
+        assert_eq_fmt!(c"%.*f", 1234f64, -3 => "1234.000000");
+        assert_eq_fmt!(c"%.20f", 0.1f64 => "0.10000000000000000555");
+        assert_eq_fmt!(c"%.0f", 0.5f64 => "0");
+        assert_eq_fmt!(c"%.0f", 1.5f64 => "2");
+        assert_eq_fmt!(c"%.0f", 2.5f64 => "2");
+        assert_eq_fmt!(c"%.1f", 0.25f64 => "0.2");
+        assert_eq_fmt!(c"%.1f", 0.35f64 => "0.3");
+        assert_eq_fmt!(c"%.2f", 9.999f64 => "10.00");
+        assert_eq_fmt!(c"%.1f", 0.96f64 => "1.0");
+        assert_eq_fmt!(c"%#.0f", 3f64 => "3.");
+        assert_eq_fmt!(c"%f", -0.0f64 => "-0.000000");
+        assert_eq_fmt!(c"%+.2f", 1.23456f64 => "+1.23");
+        assert_eq_fmt!(c"% .2f", 1.23456f64 => " 1.23");
+        assert_eq_fmt!(c"%010.2f", -1.23456f64 => "-000001.23");
+        assert_eq_fmt!(c"%-10.2f|", 1.23456f64 => "1.23      |");
+        assert_eq_fmt!(c"%10.2f", 1.23456f64 => "      1.23");
+        assert_eq_fmt!(c"%f", 1e20f64 => "100000000000000000000.000000");
+        assert_eq_fmt!(c"%f", f64::INFINITY => "inf");
+        assert_eq_fmt!(c"%F", f64::NEG_INFINITY => "-INF");
+        assert_eq_fmt!(c"%06f", f64::INFINITY => "   inf");
+        assert_eq_fmt!(c"%-6f|", f64::NAN => "nan   |");
+    }
+}
+
+#[cfg(feature = "float")]
+#[test]
+fn test_scientific() {
+    unsafe {
+        assert_eq_fmt!(c"%e", 1234f64 => "1.234000e+03");
+        assert_eq_fmt!(c"%E", 1234f64 => "1.234000E+03");
+        assert_eq_fmt!(c"%e", 0f64 => "0.000000e+00");
+        assert_eq_fmt!(c"%.17e", 0.1f64 => "1.00000000000000006e-01");
+        assert_eq_fmt!(c"%.2e", 9.999f64 => "1.00e+01");
+        assert_eq_fmt!(c"%.0e", 2.5f64 => "2e+00");
+        assert_eq_fmt!(c"%#.0e", 2.5f64 => "2.e+00");
+        assert_eq_fmt!(c"%.2e", 123456789f64 => "1.23e+08");
+        assert_eq_fmt!(c"%.0e", 125f64 => "1e+02");
+        assert_eq_fmt!(c"%.1e", 125f64 => "1.2e+02");
+        assert_eq_fmt!(c"%.1e", 135f64 => "1.4e+02");
+        assert_eq_fmt!(c"%e", 1e-300f64 => "1.000000e-300");
+        assert_eq_fmt!(c"%e", f64::MAX => "1.797693e+308");
+        assert_eq_fmt!(c"%e", 5e-324f64 => "4.940656e-324");
+        assert_eq_fmt!(c"%+012.3e", 0.00123f64 => "+001.230e-03");
+        assert_eq_fmt!(c"%e", f64::NAN => "nan");
+    }
+}
+
+/// Compare long float expansions with C, where writing out the expected
+/// output would be impractical.
+#[cfg(feature = "float")]
+#[test]
+fn test_float_exact() {
+    let values = [
+        0.1,
+        1.0 / 3.0,
+        2.0 / 3.0,
+        1e-10,
+        1e-300,
+        5e-324,
+        f64::MIN_POSITIVE,
+        f64::MAX,
+        123456789.12345679,
+        0.5,
+        9.5,
+    ];
+    for value in values {
+        for value in [value, -value] {
+            unsafe {
+                assert_eq!(
+                    c_fmt!(c"%.1100f", value),
+                    *rust_fmt(c"%.1100f".as_ptr(), value)
+                );
+                assert_eq!(
+                    c_fmt!(c"%.1000e", value),
+                    *rust_fmt(c"%.1000e".as_ptr(), value)
+                );
+                for precision in 0..20 {
+                    assert_eq!(
+                        c_fmt!(c"%.*f", precision, value),
+                        *rust_fmt(c"%.*f".as_ptr(), precision, value)
+                    );
+                    assert_eq!(
+                        c_fmt!(c"%.*e", precision, value),
+                        *rust_fmt(c"%.*e".as_ptr(), precision, value)
+                    );
+                }
+            }
+        }
     }
 }
 
@@ -251,10 +623,1442 @@ fn test_char() {
     }
 }
 
+/// Test that disabled conversions still consume their argument, and can be
+/// replaced with a placeholder.
+#[cfg(not(feature = "float"))]
+#[test]
+fn test_disabled() {
+    use printf_compat::output::{fmt_sink, fmt_write, placeholder};
+
+    unsafe extern "C" fn format(str: *const c_char, args: ...) -> Box<(c_int, String)> {
+        let mut s = String::new();
+        let bytes_written =
+            unsafe { printf_compat::format_sink(str, args, placeholder(b"?", fmt_sink(&mut s))) };
+        Box::new((bytes_written, s))
+    }
+    unsafe extern "C" fn format_err(str: *const c_char, args: ...) -> c_int {
+        let mut s = String::new();
+        unsafe { printf_compat::format(str, args, fmt_write(&mut s)) }
+    }
+    unsafe {
+        assert_eq!(*format(c"%f %d".as_ptr(), 1.5f64, 2), (3, "? 2".into()));
+        assert_eq!(
+            *format(c"%-3e|%3a|".as_ptr(), 1.5f64, 1.5f64),
+            (8, "?  |  ?|".into())
+        );
+        assert_eq!(format_err(c"%f".as_ptr(), 1.5f64), -1);
+        #[cfg(not(feature = "m-specifier"))]
+        assert_eq!(*format(c"%m|%d".as_ptr(), 2), (3, "?|2".into()));
+    }
+}
+
+#[cfg(feature = "n-specifier")]
+#[test]
+fn test_bytes_written() {
+    use printf_compat::{BytesWrittenPolicy, Config};
+
+    let write = Config::new().bytes_written(BytesWrittenPolicy::Write);
+    let (mut a, mut b, mut c, mut d) = (-1 as c_int, -1 as c_schar, -1 as c_longlong, -1isize);
+    let (mut ca, mut cb, mut cc, mut cd) = (-1 as c_int, -1 as c_schar, -1 as c_longlong, -1isize);
+    unsafe {
+        let (bytes_written, s) = c_fmt!(
+            c"ab%nc%5d%hhn|%s%lln%zn",
+            &mut ca as *mut c_int,
+            1,
+            &mut cb as *mut c_schar,
+            c"xyz".as_ptr(),
+            &mut cc as *mut c_longlong,
+            &mut cd as *mut isize
+        );
+        assert_eq!(
+            *rust_fmt_with_config(
+                &write,
+                c"ab%nc%5d%hhn|%s%lln%zn".as_ptr(),
+                &mut a as *mut c_int,
+                1,
+                &mut b as *mut c_schar,
+                c"xyz".as_ptr(),
+                &mut c as *mut c_longlong,
+                &mut d as *mut isize
+            ),
+            (bytes_written, s)
+        );
+        assert_eq!((a, b, c, d), (ca, cb, cc, cd));
+        assert_eq!((a, b, c, d), (2, 8, 12, 12));
+
+        assert_eq!(
+            *rust_fmt_with_config(&write, c"a%n".as_ptr(), null_mut::<c_int>()),
+            (-1, "a".into())
+        );
+
+        let mut a = -1 as c_int;
+        let ignore = Config::new().bytes_written(BytesWrittenPolicy::Ignore);
+        assert_eq!(
+            *rust_fmt_with_config(&ignore, c"a%nb%d".as_ptr(), &mut a as *mut c_int, 3),
+            (3, "ab3".into())
+        );
+        assert_eq!(a, -1);
+
+        let reject = Config::new();
+        assert_eq!(
+            *rust_fmt_with_config(&reject, c"a%nb".as_ptr(), &mut a as *mut c_int),
+            (-1, "a".into())
+        );
+        let handler = Config::new().bytes_written(BytesWrittenPolicy::Handler);
+        assert_eq!(
+            *rust_fmt_with_config(&handler, c"a%nb".as_ptr(), &mut a as *mut c_int),
+            (-1, "a".into())
+        );
+        assert_eq!(a, -1);
+    }
+}
+
+#[cfg(feature = "wide")]
+#[test]
+fn test_wide() {
+    use printf_compat::{argument::WChar, format_wide, output};
+
+    unsafe extern "C" {
+        fn swprintf(s: *mut WChar, n: usize, format: *const WChar, ...) -> c_int;
+    }
+
+    unsafe extern "C" fn rust_swprintf(format: *const WChar, args: ...) -> Box<(c_int, String)> {
+        let mut buf = [0; 64];
+        let written = unsafe { format_wide(format, args.clone(), output::wide_buffer(&mut buf)) };
+        let mut s = String::new();
+        let bytes_written = unsafe { format_wide(format, args, output::fmt_write(&mut s)) };
+        assert_eq!(usize::try_from(bytes_written).unwrap(), s.len());
+        let wide = buf[..usize::try_from(written).unwrap()].iter();
+        assert_eq!(
+            s,
+            wide.map(|&c| char::from_u32(c).unwrap())
+                .collect::<String>()
+        );
+        Box::new((written, s))
+    }
+
+    fn w(s: &str) -> Vec<WChar> {
+        s.chars().map(WChar::from).chain([0]).collect()
+    }
+
+    macro_rules! assert_eq_wfmt {
+        ($format:literal $(, $p:expr)* => $expected:literal) => {
+            let format = w($format);
+            let mut buf = [0; 64];
+            let written = unsafe { swprintf(buf.as_mut_ptr(), buf.len(), format.as_ptr() $(, $p)*) };
+            let c: String = buf[..usize::try_from(written).unwrap()]
+                .iter()
+                .map(|&c| char::from_u32(c).unwrap())
+                .collect();
+            assert_eq!(c, $expected);
+            assert_eq!((written, c), *unsafe { rust_swprintf(format.as_ptr() $(, $p)*) });
+        };
+    }
+
+    let wide = w("wïde");
+    assert_eq_wfmt!("plain wïde text" => "plain wïde text");
+    assert_eq_wfmt!("%ls|%6ls|%-6ls|%.2ls", wide.as_ptr(), wide.as_ptr(), wide.as_ptr(), wide.as_ptr() => "wïde|  wïde|wïde  |wï");
+    assert_eq_wfmt!("%s %c", c"narrow".as_ptr(), b'x' as c_int => "narrow x");
+    assert_eq_wfmt!("%lc%3lc", 'é' as c_uint, 'ü' as c_uint => "é  ü");
+    assert_eq_wfmt!("%d%%%5x", -12, 255 => "-12%   ff");
+    assert_eq_wfmt!("%ls", core::ptr::null::<WChar>() => "(null)");
+
+    let wide = w("wide");
+    unsafe {
+        assert_eq_fmt!(c"%ls|%5lc|%-6ls|", wide.as_ptr(), b'x' as c_uint, wide.as_ptr() => "wide|    x|wide  |");
+    }
+}
+
+#[test]
+fn test_glibc_extensions() {
+    unsafe {
+        assert_eq_fmt!(c"%p|%8p|%-8p|", null_mut::<c_void>(), null_mut::<c_void>(), null_mut::<c_void>() => "(nil)|   (nil)|(nil)   |");
+        assert_eq_fmt!(c"%#o|%#o|%#6o|%#-6o|%#06o", 0, 8, 8, 8, 8 => "0|010|   010|010   |000010");
+        assert_eq_fmt!(c"%Zu %Zd", 42usize, -1isize => "42 -1");
+        #[cfg(feature = "float")]
+        assert_eq_fmt!(c"%f %5.1f %E", -f64::NAN, -f64::NAN, f64::NAN => "-nan  -nan NAN");
+    }
+}
+
+#[cfg(feature = "positional")]
+#[test]
+fn test_positional() {
+    use printf_compat::output::fmt_write;
+    use printf_compat::{Config, format_slice};
+
+    let config = Config::new();
+
+    unsafe {
+        assert_eq_fmt!(c"%2$s %1$d", 5, c"a".as_ptr() => "a 5");
+        assert_eq_fmt!(c"%1$d %1$x %1$#o", 8 => "8 8 010");
+        assert_eq_fmt!(c"%1$*2$d|%1$-*2$d|", 7, 3 => "  7|7  |");
+        assert_eq_fmt!(c"%3$*1$.*2$s|%%%2$d", 4, 2, c"xyz".as_ptr() => "  xy|%2");
+        assert_eq_fmt!(c"%3$c%2$hhd%1$lld", 1i64 << 40, 300, b'z' as c_int => "z441099511627776");
+        #[cfg(feature = "float")]
+        assert_eq_fmt!(c"%2$.*1$f %3$e", 2, 1.005, 2.5 => "1.00 2.500000e+00");
+        #[cfg(feature = "m-specifier")]
+        assert_eq_fmt!(c"%m %1$s", c"a".as_ptr() => "Success a");
+
+        // A gap, an unnumbered argument after a numbered one, or the reverse.
+        assert_eq!(
+            *rust_fmt_with_config(&config, c"a%2$d".as_ptr(), 1, 2),
+            (-1, "".into())
+        );
+        assert_eq!(
+            *rust_fmt_with_config(&config, c"a%1$d%d".as_ptr(), 1, 2),
+            (-1, "".into())
+        );
+        assert_eq!(
+            *rust_fmt_with_config(&config, c"a%1$*d".as_ptr(), 1, 2),
+            (-1, "".into())
+        );
+        assert_eq!(
+            *rust_fmt_with_config(&config, c"a%d%1$d".as_ptr(), 1, 2),
+            (-1, "a1".into())
+        );
+        // The same argument read as two types.
+        assert_eq!(
+            *rust_fmt_with_config(&config, c"%1$d%1$s".as_ptr(), 1),
+            (-1, "".into())
+        );
+        // More arguments than can be numbered.
+        assert_eq!(
+            *rust_fmt_with_config(&config, c"%33$d".as_ptr(), 1),
+            (-1, "".into())
+        );
+        assert_eq!(
+            *rust_fmt_with_config(&config, c"%0$d".as_ptr(), 1),
+            (-1, "".into())
+        );
+    }
+
+    let mut s = String::new();
+    assert_eq!(
+        format_slice(c"%2$s%1$d%2$s", &[&1, &c"b"], fmt_write(&mut s)),
+        Ok(3)
+    );
+    assert_eq!(s, "b1b");
+}
+
+#[test]
+fn test_dialect() {
+    use printf_compat::Dialect;
+
+    let pointers = |dialect, str: &CStr, ptr: *const c_void| unsafe {
+        rust_fmt_dialect(&dialect, str.as_ptr(), ptr, ptr).1
+    };
+    let null = core::ptr::null();
+    let ptr = 0x1a as *const c_void;
+    assert_eq!(pointers(Dialect::Glibc, c"%p|%5p", null), "(nil)|(nil)");
+    assert_eq!(pointers(Dialect::Musl, c"%p|%5p", null), "0|    0");
+    assert_eq!(pointers(Dialect::Musl, c"%p|%5p", ptr), "0x1a| 0x1a");
+    assert_eq!(pointers(Dialect::NewlibNano, c"%p|%5p", null), "0x0|  0x0");
+    assert_eq!(pointers(Dialect::C11, c"%p|%5p", null), "0x0|  0x0");
+    #[cfg(target_pointer_width = "64")]
+    {
+        assert_eq!(
+            pointers(Dialect::Msvc, c"%p|%5p", null),
+            "0000000000000000|0000000000000000"
+        );
+        assert_eq!(
+            pointers(Dialect::Msvc, c"%p|%-18p|", ptr),
+            "000000000000001A|000000000000001A  |"
+        );
+    }
+
+    unsafe {
+        assert_eq!(
+            *rust_fmt_dialect(&Dialect::NewlibNano, c"%#o %#o".as_ptr(), 0, 8),
+            (6, "00 010".into())
+        );
+        assert_eq!(
+            *rust_fmt_dialect(&Dialect::Musl, c"%#o %#o".as_ptr(), 0, 8),
+            (5, "0 010".into())
+        );
+        assert_eq!(
+            *rust_fmt_dialect(&Dialect::Musl, c"%s".as_ptr(), null_mut::<c_char>()),
+            (6, "(null)".into())
+        );
+        assert_eq!(
+            *rust_fmt_dialect(&Dialect::C11, c"a%s".as_ptr(), null_mut::<c_char>()),
+            (-1, "a".into())
+        );
+        assert_eq!(
+            rust_fmt_dialect(&Dialect::Musl, c"%Zu".as_ptr(), 1usize).0,
+            -1
+        );
+        #[cfg(feature = "float")]
+        {
+            assert_eq!(
+                *rust_fmt_dialect(&Dialect::Msvc, c"%e|%E".as_ptr(), 1.0, -1e-300),
+                (28, "1.000000e+000|-1.000000E-300".into())
+            );
+            assert_eq!(
+                *rust_fmt_dialect(
+                    &Dialect::Msvc,
+                    c"%f|%F|%f".as_ptr(),
+                    -f64::NAN,
+                    -f64::NAN,
+                    f64::NAN
+                ),
+                (23, "-nan(ind)|-NAN(IND)|nan".into())
+            );
+            assert_eq!(
+                *rust_fmt_dialect(
+                    &Dialect::NewlibNano,
+                    c"%f|%5f".as_ptr(),
+                    -f64::NAN,
+                    -f64::NAN
+                ),
+                (9, "nan|  nan".into())
+            );
+            assert_eq!(
+                *rust_fmt_dialect(&Dialect::C11, c"%e".as_ptr(), 1.0),
+                (12, "1.000000e+00".into())
+            );
+        }
+    }
+}
+
+#[test]
+fn test_msvc_extensions() {
+    use printf_compat::Dialect;
+
+    #[repr(C)]
+    struct AnsiString {
+        length: c_ushort,
+        maximum_length: c_ushort,
+        buffer: *const c_char,
+    }
+
+    let msvc = &Dialect::Msvc;
+    let ansi = AnsiString {
+        length: 3,
+        maximum_length: 6,
+        buffer: c"abcdef".as_ptr(),
+    };
+    unsafe {
+        assert_eq!(
+            *rust_fmt_dialect(msvc, c"%I64d|%I64x".as_ptr(), -1i64, u64::MAX),
+            (19, "-1|ffffffffffffffff".into())
+        );
+        assert_eq!(
+            *rust_fmt_dialect(msvc, c"%I32u|%Iu|%Id".as_ptr(), 7u32, usize::MAX, -2isize),
+            (
+                format!("7|{}|-2", usize::MAX).len() as c_int,
+                format!("7|{}|-2", usize::MAX)
+            )
+        );
+        assert_eq!(
+            *rust_fmt_dialect(msvc, c"%Ix".as_ptr(), 255usize),
+            (2, "ff".into())
+        );
+        assert_eq!(
+            *rust_fmt_dialect(
+                msvc,
+                c"%5Z|%.2Z|%Z".as_ptr(),
+                &ansi,
+                &ansi,
+                null_mut::<c_void>()
+            ),
+            (15, "  abc|ab|(null)".into())
+        );
+        assert_eq!(
+            *rust_fmt_dialect(msvc, c"%hS|%hC".as_ptr(), c"narrow".as_ptr(), b'c' as c_int),
+            (8, "narrow|c".into())
+        );
+        #[cfg(feature = "wide")]
+        {
+            use printf_compat::argument::WChar;
+            let wide: Vec<WChar> = "wide\0".chars().map(WChar::from).collect();
+            assert_eq!(
+                *rust_fmt_dialect(
+                    msvc,
+                    c"%S|%ws|%C|%wc".as_ptr(),
+                    wide.as_ptr(),
+                    wide.as_ptr(),
+                    0xe9,
+                    0xe9
+                ),
+                (15, "wide|wide|\u{e9}|\u{e9}".into())
+            );
+        }
+
+        // These are only extensions in MSVC.
+        for dialect in [Dialect::Glibc, Dialect::C11] {
+            assert_eq!(rust_fmt_dialect(&dialect, c"%I64d".as_ptr(), 1i64).0, -1);
+            assert_eq!(rust_fmt_dialect(&dialect, c"%Z".as_ptr(), &ansi).0, -1);
+            assert_eq!(rust_fmt_dialect(&dialect, c"%wd".as_ptr(), 1).0, -1);
+        }
+    }
+}
+
+#[cfg(feature = "m-specifier")]
+#[test]
+fn test_errno() {
+    use printf_compat::{Config, Dialect, strerror};
+
+    unsafe extern "C" {
+        fn __errno_location() -> *mut c_int;
+        #[link_name = "strerror"]
+        fn c_strerror(errnum: c_int) -> *const c_char;
+    }
+
+    for errno in -1..140 {
+        let expected = unsafe { CStr::from_ptr(c_strerror(errno)) }
+            .to_str()
+            .unwrap();
+        match strerror(errno) {
+            Some(message) => assert_eq!(message, expected),
+            None => assert_eq!(expected, format!("Unknown error {errno}")),
+        }
+    }
+
+    unsafe {
+        *__errno_location() = 2;
+        assert_eq_fmt!(c"open failed: %m" => "open failed: No such file or directory");
+        *__errno_location() = 13;
+        assert_eq_fmt!(c"%20m|%-20m|%.10m|%d", 5 => "   Permission denied|Permission denied   |Permission|5");
+        *__errno_location() = 4000;
+        assert_eq_fmt!(c"%m|%20m" => "Unknown error 4000|  Unknown error 4000");
+        let (written, s) = *rust_fmt(c"%70000m|%-70000m".as_ptr());
+        assert_eq!((written, s.clone()), c_fmt!(c"%70000m|%-70000m"));
+        assert_eq!(s.trim(), "Unknown error 4000|Unknown error 4000");
+    }
+
+    fn errno() -> c_int {
+        22
+    }
+    unsafe {
+        assert_eq!(
+            *rust_fmt_with_config(&Config::new().errno(errno), c"%m".as_ptr()),
+            (16, "Invalid argument".into())
+        );
+        *__errno_location() = 22;
+        assert_eq!(
+            *rust_fmt_with_config(&Config::new().dialect(Dialect::Musl), c"%m".as_ptr()),
+            (16, "Invalid argument".into())
+        );
+        assert_eq!(
+            rust_fmt_with_config(&Config::new().dialect(Dialect::Msvc), c"%m".as_ptr()).0,
+            -1
+        );
+    }
+}
+
+#[cfg(feature = "pointer-ext")]
+#[test]
+fn test_pointer_ext() {
+    use printf_compat::{Config, Limits, format_with_config, output::fmt_write};
+
+    unsafe extern "C" fn printk(str: *const c_char, args: ...) -> Box<(c_int, String)> {
+        const CONFIG: Config = Config::new().pointer_extensions(true);
+        let mut s = String::new();
+        let written = unsafe { format_with_config(str, args, &CONFIG, fmt_write(&mut s)) };
+        assert_eq!(usize::try_from(written).ok(), Some(s.len()));
+        Box::new((written, s))
+    }
+
+    let ipv4 = [192u8, 168, 0, 1];
+    let mac = [0x00u8, 0x11, 0x22, 0xaa, 0xbb, 0xcc];
+    let uuid: [u8; 16] = core::array::from_fn(|i| i as u8 * 0x11);
+    let ipv6 = [0x20u8, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    let mapped = [0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 10, 0, 0, 1];
+    let single_zero = [0u8, 1, 0, 0, 0, 2, 0, 3, 0, 4, 0, 5, 0, 6, 0, 7];
+    let p = |bytes: &[u8]| bytes.as_ptr();
+    let fmt = |str: &CStr, ptr: *const u8| unsafe { printk(str.as_ptr(), ptr).1 };
+    assert_eq!(fmt(c"ip %pI4!", p(&ipv4)), "ip 192.168.0.1!");
+    assert_eq!(fmt(c"%pi4", p(&ipv4)), "192.168.000.001");
+    assert_eq!(fmt(c"[%20pI4]", p(&ipv4)), "[         192.168.0.1]");
+    assert_eq!(fmt(c"%-12pI4|", p(&ipv4)), "192.168.0.1 |");
+    assert_eq!(
+        fmt(c"%pI6", p(&ipv6)),
+        "2001:0db8:0000:0000:0000:0000:0000:0001"
+    );
+    assert_eq!(fmt(c"%pi6", p(&ipv6)), "20010db8000000000000000000000001");
+    assert_eq!(fmt(c"%pI6c", p(&ipv6)), "2001:db8::1");
+    assert_eq!(fmt(c"%pI6c", p(&[0; 16])), "::");
+    assert_eq!(fmt(c"%pI6c", p(&mapped)), "::ffff:10.0.0.1");
+    assert_eq!(fmt(c"%pI6c", p(&single_zero)), "1:0:2:3:4:5:6:7");
+    assert_eq!(fmt(c"%pM", p(&mac)), "00:11:22:aa:bb:cc");
+    assert_eq!(fmt(c"%pMR", p(&mac)), "cc:bb:aa:22:11:00");
+    assert_eq!(fmt(c"%pMF", p(&mac)), "00-11-22-aa-bb-cc");
+    assert_eq!(fmt(c"%pm", p(&mac)), "001122aabbcc");
+    assert_eq!(fmt(c"%pmR", p(&mac)), "ccbbaa221100");
+    assert_eq!(
+        fmt(c"%pU", p(&uuid)),
+        "00112233-4455-6677-8899-aabbccddeeff"
+    );
+    assert_eq!(
+        fmt(c"%pUB", p(&uuid)),
+        "00112233-4455-6677-8899-AABBCCDDEEFF"
+    );
+    assert_eq!(
+        fmt(c"%pUl", p(&uuid)),
+        "33221100-5544-7766-8899-aabbccddeeff"
+    );
+    assert_eq!(
+        fmt(c"%pUL", p(&uuid)),
+        "33221100-5544-7766-8899-AABBCCDDEEFF"
+    );
+    assert_eq!(fmt(c"%ph", p(&mac)), "00");
+    assert_eq!(fmt(c"%6ph", p(&mac)), "00 11 22 aa bb cc");
+    assert_eq!(
+        unsafe { printk(c"%3phC|%3phD|%3phN".as_ptr(), p(&mac), p(&mac), p(&mac)).1 },
+        "00:11:22|00-11-22|001122"
+    );
+    assert_eq!(fmt(c"%pM", core::ptr::null()), "(null)");
+    assert_eq!(fmt(c"%pe", -22isize as *const u8), "-EINVAL");
+    assert_eq!(fmt(c"%pe", -517isize as *const u8), "-EPROBE_DEFER");
+    assert_eq!(fmt(c"%pe", -4000isize as *const u8), "-4000");
+    assert_eq!(fmt(c"%pe", 0x1234 as *const u8), "0x1234");
+    // Unknown suffixes are consumed, and the pointer is printed plainly.
+    assert_eq!(fmt(c"%pZZ9 %%", 0x1a as *const u8), "0x1a %");
+
+    unsafe extern "C" fn untrusted(str: *const c_char, args: ...) -> c_int {
+        let config = Config::new()
+            .pointer_extensions(true)
+            .limits(Limits::new().allow_dereference(false));
+        let mut s = String::new();
+        unsafe { format_with_config(str, args, &config, fmt_write(&mut s)) }
+    }
+    unsafe {
+        assert_eq!(untrusted(c"%pM".as_ptr(), p(&mac)), -1);
+        assert_eq!(untrusted(c"%pe".as_ptr(), -22isize), 7);
+    }
+
+    // Without the config, the suffix is literal text.
+    assert_eq!(
+        unsafe { rust_fmt(c"%pM".as_ptr(), 0x1a as *const u8).1 },
+        "0x1aM"
+    );
+}
+
+#[test]
+fn test_custom_conversions() {
+    use core::fmt;
+    use printf_compat::argument::Flags;
+    use printf_compat::{ArgType, Config, Conversion, Limits, Promoted};
+
+    /// `%Q`: a quoted string.
+    fn quote(w: &mut dyn fmt::Write, value: Promoted, _: Flags, _: Option<c_int>) -> fmt::Result {
+        match value {
+            Promoted::String(Some(s)) => write!(w, "{:?}", s),
+            _ => w.write_str("NULL"),
+        }
+    }
+
+    /// `%W`: an integer as big-endian bytes, `#` adding a length prefix.
+    fn wire(
+        w: &mut dyn fmt::Write,
+        value: Promoted,
+        flags: Flags,
+        _: Option<c_int>,
+    ) -> fmt::Result {
+        let Promoted::Uint(x) = value else {
+            return Err(fmt::Error);
+        };
+        let bytes = u64::from(x).to_be_bytes();
+        let start = bytes.iter().position(|&b| b != 0).unwrap_or(7);
+        if flags.contains(Flags::ALTERNATE_FORM) {
+            write!(w, "{}:", 8 - start)?;
+        }
+        bytes[start..].iter().try_for_each(|b| write!(w, "{b:02x}"))
+    }
+
+    /// `%d`, replaced to print Roman numerals up to 3.
+    fn roman(w: &mut dyn fmt::Write, value: Promoted, _: Flags, _: Option<c_int>) -> fmt::Result {
+        let Promoted::Int(x) = value else {
+            return Err(fmt::Error);
+        };
+        w.write_str(["", "I", "II", "III"][i64::from(x) as usize])
+    }
+
+    const CONVERSIONS: &[Conversion] = &[
+        Conversion::new(b'Q', ArgType::String, quote),
+        Conversion::new(b'W', ArgType::Uint, wire),
+    ];
+
+    let config = Config::new().conversions(CONVERSIONS);
+    unsafe {
+        assert_eq!(
+            *rust_fmt_with_config(
+                &config,
+                c"%Q|%8Q|%-6Q|".as_ptr(),
+                c"a\"b".as_ptr(),
+                c"x".as_ptr(),
+                null_mut::<c_char>()
+            ),
+            (23, "\"a\\\"b\"|     \"x\"|NULL  |".into())
+        );
+        assert_eq!(
+            *rust_fmt_with_config(
+                &config,
+                c"%W %#W %#lW %d".as_ptr(),
+                0x1234,
+                0,
+                0x10000 as c_ulong,
+                5
+            ),
+            (20, "1234 1:00 3:010000 5".into())
+        );
+        assert_eq!(rust_fmt_with_config(&config, c"%R".as_ptr(), 1).0, -1);
+        assert_eq!(
+            rust_fmt_with_config(&Config::new(), c"%Q".as_ptr(), c"a".as_ptr()).0,
+            -1
+        );
+
+        const REPLACED: &[Conversion] = &[Conversion::new(b'd', ArgType::Int, roman)];
+        assert_eq!(
+            *rust_fmt_with_config(
+                &Config::new().conversions(REPLACED),
+                c"%d|%3d|%u".as_ptr(),
+                2,
+                3,
+                4
+            ),
+            (8, "II|III|4".into())
+        );
+
+        let untrusted = config.limits(Limits::new().allow_dereference(false));
+        assert_eq!(
+            rust_fmt_with_config(&untrusted, c"%Q".as_ptr(), c"a".as_ptr()).0,
+            -1
+        );
+        assert_eq!(rust_fmt_with_config(&untrusted, c"%W".as_ptr(), 1).0, 2);
+    }
+}
+
+#[test]
+fn test_printf_builder() {
+    use printf_compat::{Dialect, Error, Printf};
+
+    const MUSL: Printf = Printf::new().dialect(Dialect::Musl);
+
+    unsafe extern "C" fn format(
+        printf: &Printf,
+        str: *const c_char,
+        args: ...
+    ) -> Box<(c_int, String)> {
+        let mut s = String::new();
+        let written = unsafe { printf.format(str, args, printf.fmt_write(&mut s)) };
+        Box::new((written, s))
+    }
+
+    unsafe extern "C" fn try_format(
+        printf: &Printf,
+        str: *const c_char,
+        args: ...
+    ) -> Box<Result<c_int, Error>> {
+        Box::new(unsafe { printf.try_format(str, args, printf.fmt_write(&mut String::new())) })
+    }
+
+    unsafe extern "C" fn display(printf: &Printf, out: &mut String, str: *const c_char, args: ...) {
+        *out = unsafe { printf.display(str, args) }.to_string();
+    }
+
+    let null = null_mut::<c_void>();
+    unsafe {
+        assert_eq!(
+            *format(&MUSL, c"%p|%d".as_ptr(), null, 5),
+            (3, "0|5".into())
+        );
+        assert_eq!(
+            *format(&Printf::new(), c"%p|%d".as_ptr(), null, 5),
+            (7, "(nil)|5".into())
+        );
+        let mut s = String::new();
+        display(&MUSL, &mut s, c"%5p".as_ptr(), null);
+        assert_eq!(s, "    0");
+        assert_eq!(
+            *try_format(&MUSL, c"%k".as_ptr()),
+            Err(Error::InvalidFormat)
+        );
+    }
+
+    assert_eq!(MUSL.sprintf(c"%p|%s", &[&null, &c"x"]).unwrap(), "0|x");
+    let mut out = Vec::new();
+    MUSL.format_slice(c"%p", &[&null], MUSL.io_write(&mut out))
+        .unwrap();
+    assert_eq!(out, b"0");
+    assert_eq!(MUSL.config().dialect(Dialect::Musl), *MUSL.config());
+    assert_eq!(Printf::default(), Printf::new());
+}
+
+#[test]
+fn test_locale() {
+    use core::cell::Cell;
+    use printf_compat::{Config, Dialect, Locale, format_with_config, output};
+
+    unsafe extern "C" {
+        fn newlocale(mask: c_int, locale: *const c_char, base: *mut c_void) -> *mut c_void;
+        fn uselocale(locale: *mut c_void) -> *mut c_void;
+        fn freelocale(locale: *mut c_void);
+    }
+    const LC_NUMERIC_MASK: c_int = 1 << 1;
+
+    unsafe extern "C" fn format(
+        locale: &Locale,
+        str: *const c_char,
+        args: ...
+    ) -> Box<(c_int, String)> {
+        let config = Config::new().locale(*locale);
+        let mut s = String::new();
+        let handler = output::fmt_write_locale(&mut s, Dialect::Glibc, *locale);
+        let written = unsafe { format_with_config(str, args.clone(), &config, handler) };
+        let mut v = Vec::new();
+        let handler = output::io_write_locale(&mut v, Dialect::Glibc, *locale);
+        assert_eq!(written, unsafe {
+            format_with_config(str, args, &config, handler)
+        });
+        assert_eq!(s.as_bytes(), v);
+        Box::new((written, s))
+    }
+
+    std::thread_local! {
+        /// Whether glibc has the locale that's being tested.
+        static INSTALLED: Cell<bool> = const { Cell::new(true) };
+    }
+
+    /// Assert that `format` gives `expected`, and that glibc agrees if it
+    /// has the locale.
+    macro_rules! assert_eq_locale {
+        ($locale:expr, $format:literal $(, $p:expr)* => $expected:literal) => {
+            let (bytes_written, s) = *format(&$locale, $format.as_ptr() $(, $p)*);
+            assert_eq!(s, $expected);
+            assert_eq!(usize::try_from(bytes_written).unwrap(), $expected.len());
+            if INSTALLED.get() {
+                assert_eq!((bytes_written, s), c_fmt!($format $(, $p)*));
+            }
+        };
+    }
+
+    /// Run `f` with glibc's `LC_NUMERIC` set to `name`. This uses
+    /// `uselocale` rather than `setlocale`, so that the other tests, which
+    /// run in parallel, aren't affected.
+    ///
+    /// If glibc doesn't have the locale, `f` still runs, but its output
+    /// isn't compared with glibc's.
+    fn with_locale(name: &CStr, f: impl FnOnce()) {
+        unsafe {
+            let locale = newlocale(LC_NUMERIC_MASK, name.as_ptr(), null_mut());
+            if locale.is_null() {
+                eprintln!("not comparing with glibc in {name:?}, which isn't installed");
+                INSTALLED.set(false);
+                f();
+                INSTALLED.set(true);
+                return;
+            }
+            let old = uselocale(locale);
+            f();
+            uselocale(old);
+            freelocale(locale);
+        }
+    }
+
+    unsafe {
+        assert_eq_locale!(Locale::C, c"%'d|%'u" , 1234567, 1234567 => "1234567|1234567");
+        #[cfg(feature = "float")]
+        assert_eq_locale!(Locale::C, c"%'.2f", 1234567.891 => "1234567.89");
+    }
+
+    with_locale(c"en_US.UTF-8", || unsafe {
+        let l = Locale::EN_US;
+        assert_eq_locale!(l, c"%'d|%'i|%'u", 1234567, -1234567, 4294967295u32 => "1,234,567|-1,234,567|4,294,967,295");
+        assert_eq_locale!(l, c"%'d|%'d|%'d|%'5d", 999, -1000, 0, 0 => "999|-1,000|0|    0");
+        assert_eq_locale!(l, c"%'ld|%'lld", c_long::MAX, c_longlong::MIN => "9,223,372,036,854,775,807|-9,223,372,036,854,775,808");
+        assert_eq_locale!(l, c"[%'15d][%'-15d][%'015d]", -1234567, 1234567, -1234567 => "[     -1,234,567][1,234,567      ][-000001,234,567]");
+        assert_eq_locale!(l, c"[%'+015d][% '015d][%'+d]", 1234567, 1234567, 1234 => "[+000001,234,567][ 000001,234,567][+1,234]");
+        assert_eq_locale!(l, c"%'hhd", 1234567 => "-121");
+        // Widths over `u16::MAX`, which `std` can't pad to.
+        let expected = format!(
+            "{}1,234,567|1,234,567{}|-{}1,234,567",
+            " ".repeat(69_991),
+            " ".repeat(69_991),
+            "0".repeat(69_990)
+        );
+        let long = *format(
+            &l,
+            c"%'70000d|%'-70000d|%'070000d".as_ptr(),
+            1234567,
+            1234567,
+            -1234567,
+        );
+        assert_eq!(long, (210_002, expected));
+        if INSTALLED.get() {
+            assert_eq!(
+                long,
+                c_fmt!(c"%'70000d|%'-70000d|%'070000d", 1234567, 1234567, -1234567)
+            );
+        }
+        // glibc groups `%'x` and `%'o` too, but POSIX only defines `'` for
+        // decimal conversions.
+        assert_eq!(
+            format(&l, c"%'x|%'o".as_ptr(), 1234567, 1234567).1,
+            "12d687|4553207"
+        );
+        #[cfg(feature = "float")]
+        assert_eq_locale!(l, c"%'f|%'.0f|%'#.0f", 1234567.891, 1234567.0, 1234567.0 => "1,234,567.891000|1,234,567|1,234,567.");
+        #[cfg(feature = "float")]
+        assert_eq_locale!(l, c"[%'015.2f][%'15.1f][%'-15.1f]", -1234567.891, 1234.5, 1234.5 => "[-001,234,567.89][        1,234.5][1,234.5        ]");
+        #[cfg(feature = "float")]
+        assert_eq_locale!(l, c"%'e|%'F|%'f|%'f", 1234567.0, 1e20, -0.5, f64::INFINITY => "1.234567e+06|100,000,000,000,000,000,000.000000|-0.500000|inf");
+    });
+
+    with_locale(c"de_DE.UTF-8", || unsafe {
+        let l = Locale::DE_DE;
+        assert_eq_locale!(l, c"%'d|%'u|%'d", -1234567, 4294967295u32, 999 => "-1.234.567|4.294.967.295|999");
+        assert_eq_locale!(l, c"[%'015d][%'-12d]", -1234567, 1234 => "[-000001.234.567][1.234       ]");
+        #[cfg(feature = "float")]
+        assert_eq_locale!(l, c"%f|%e|%E|%F", 3.125, 3.125, -3.125, 0.5 => "3,125000|3,125000e+00|-3,125000E+00|0,500000");
+        #[cfg(feature = "float")]
+        assert_eq_locale!(l, c"%.0f|%#.0f|%#.0e|%.1f", 3.0, 3.0, 3.0, f64::NAN => "3|3,|3,e+00|nan");
+        #[cfg(feature = "float")]
+        assert_eq_locale!(l, c"[%10.2f][%-10.2f][%010.2f][%+.1e]", 3.14259, 3.14259, -3.14259, 2.5 => "[      3,14][3,14      ][-000003,14][+2,5e+00]");
+        #[cfg(feature = "float")]
+        assert_eq_locale!(l, c"%'f|%'012.1f", 1234567.891, 1234.5 => "1.234.567,891000|000001.234,5");
+    });
+
+    with_locale(c"fr_FR.UTF-8", || unsafe {
+        // The narrow no-break space is 3 bytes, and the width counts bytes.
+        let l = Locale::FR_FR;
+        assert_eq_locale!(l, c"%'d|%'u", -1234567, 4294967295u32 => "-1\u{202f}234\u{202f}567|4\u{202f}294\u{202f}967\u{202f}295");
+        assert_eq_locale!(l, c"[%'15d][%'015d]", -1234567, -1234567 => "[ -1\u{202f}234\u{202f}567][-01\u{202f}234\u{202f}567]");
+        // glibc counts it as one character for the floating point
+        // conversions, though.
+        #[cfg(feature = "float")]
+        assert_eq_locale!(l, c"[%'015.2f][%'20.2f][%#.0f]", -1234567.891, 1234567.891, 3.0 => "[-001\u{202f}234\u{202f}567,89][        1\u{202f}234\u{202f}567,89][3,]");
+    });
+
+    // Pashto has a multibyte decimal point, and no built-in `Locale`.
+    #[cfg(feature = "float")]
+    with_locale(c"ps_AF.UTF-8", || unsafe {
+        let l = Locale::new("\u{66b}", "\u{66c}", &[3]);
+        assert_eq_locale!(l, c"[%10.2f][%-10.2f][%010.2f]", 3.14259, 3.14259, -3.14259 => "[      3\u{66b}14][3\u{66b}14      ][-000003\u{66b}14]");
+        assert_eq_locale!(l, c"[%'015.2f][%#.0e][%e]", -1234567.891, 3.0, 0.25 => "[-001\u{66c}234\u{66c}567\u{66b}89][3\u{66b}e+00][2\u{66b}500000e-01]");
+    });
+
+    unsafe {
+        let indian = Locale::new(".", ",", &[3, 2]);
+        assert_eq!(
+            format(&indian, c"%'d".as_ptr(), 123456789).1,
+            "12,34,56,789"
+        );
+        let once = Locale::new(".", ",", &[3, c_char::MAX as u8]);
+        assert_eq!(format(&once, c"%'d".as_ptr(), 123456789).1, "123456,789");
+        let none = Locale::new(".", ",", &[0]);
+        assert_eq!(format(&none, c"%'d".as_ptr(), 123456789).1, "123456789");
+    }
+}
+
+#[test]
+fn test_compiled_format() {
+    use printf_compat::{CompiledFormat, Config, Dialect, Error, Printf, output};
+
+    unsafe extern "C" fn format(compiled: &CompiledFormat, args: ...) -> Box<(c_int, String)> {
+        let mut s = String::new();
+        let written = unsafe { compiled.format(args, output::fmt_write(&mut s)) };
+        Box::new((written, s))
+    }
+
+    let compiled: CompiledFormat =
+        CompiledFormat::new(c"%5d|%-4s|%%|%x%c", &Config::new()).unwrap();
+    assert_eq!(compiled.format_string(), c"%5d|%-4s|%%|%x%c");
+    unsafe {
+        for i in [0, -12, 255] {
+            assert_eq!(
+                *format(&compiled, i, c"ab".as_ptr(), i, c_int::from(b'z')),
+                c_fmt!(c"%5d|%-4s|%%|%x%c", i, c"ab".as_ptr(), i, c_int::from(b'z'))
+            );
+        }
+    }
+
+    let mut s = String::new();
+    assert_eq!(
+        compiled.format_slice(&[&1, &c"a", &2u8, &'c'], output::fmt_write(&mut s)),
+        Ok(15)
+    );
+    assert_eq!(s, "    1|a   |%|2c");
+    assert_eq!(
+        compiled.format_slice(&[&1], output::fmt_write(&mut String::new())),
+        Err(Error::MissingArgument)
+    );
+
+    let musl: CompiledFormat = Printf::new().dialect(Dialect::Musl).compile(c"%p").unwrap();
+    let mut s = String::new();
+    musl.format_slice(
+        &[&null_mut::<c_void>()],
+        output::fmt_write_dialect(&mut s, Dialect::Musl),
+    )
+    .unwrap();
+    assert_eq!(s, "0");
+
+    let compile = |format| CompiledFormat::<4>::new(format, &Config::new()).err();
+    assert_eq!(compile(c"%d %d"), None);
+    assert_eq!(compile(c"%d %d %d"), Some(Error::Capacity));
+    assert_eq!(compile(c"abc%"), Some(Error::InvalidFormat));
+    assert_eq!(compile(c"%99999999999d"), Some(Error::InvalidFormat));
+    // Whether a conversion is allowed is only checked when formatting.
+    assert_eq!(compile(c"%n"), None);
+}
+
+#[test]
+fn test_record() {
+    use printf_compat::{Error, Limits, Printf, Record, output};
+
+    unsafe extern "C" fn capture(
+        printf: &Printf,
+        str: *const c_char,
+        args: ...
+    ) -> Box<Result<Record, Error>> {
+        Box::new(unsafe { printf.capture(str, args) })
+    }
+
+    let mut name = *b"world\0";
+    let expected = unsafe {
+        c_fmt!(
+            c"hello %-7s|%5x|%p|%c",
+            name.as_ptr(),
+            255,
+            0x1234usize,
+            0x21
+        )
+    };
+    let record = unsafe {
+        capture(
+            &Printf::new(),
+            c"hello %-7s|%5x|%p|%c".as_ptr(),
+            name.as_ptr(),
+            255,
+            0x1234usize,
+            0x21,
+        )
+    }
+    .unwrap();
+    // The record doesn't borrow the arguments.
+    name.fill(b'x');
+    let (written, s) = std::thread::spawn(move || {
+        let mut s = String::new();
+        (record.format(output::fmt_write(&mut s)), s)
+    })
+    .join()
+    .unwrap();
+    assert_eq!((written, s), expected);
+
+    let record =
+        unsafe { capture(&Printf::new(), c"%d%%|%s".as_ptr(), 5, null_mut::<c_char>()) }.unwrap();
+    assert_eq!(record.to_string(), "5%|(null)");
+    let mut v = Vec::new();
+    assert_eq!(record.try_format(output::io_write(&mut v)).unwrap(), 9);
+    assert_eq!(v, b"5%|(null)");
+
+    #[cfg(feature = "wide")]
+    {
+        let wide: Vec<_> = "wide"
+            .chars()
+            .map(printf_compat::argument::WChar::from)
+            .chain([0])
+            .collect();
+        let record = unsafe { capture(&Printf::new(), c"%ls!".as_ptr(), wide.as_ptr()) }.unwrap();
+        drop(wide);
+        assert_eq!(record.to_string(), "wide!");
+    }
+
+    // Nothing has been written when the arguments are captured, so `%n` is
+    // always rejected.
+    #[cfg(feature = "n-specifier")]
+    {
+        let printf = Printf::new().bytes_written(printf_compat::BytesWrittenPolicy::Write);
+        let mut n: c_int = 0;
+        assert_eq!(
+            unsafe { capture(&printf, c"%n".as_ptr(), &mut n as *mut c_int) }.unwrap_err(),
+            Error::Forbidden(b'n')
+        );
+    }
+    assert_eq!(
+        unsafe { capture(&Printf::new(), c"%d %".as_ptr(), 1) }.unwrap_err(),
+        Error::InvalidFormat
+    );
+
+    // The output limit is checked when the record is formatted.
+    let printf = Printf::new().limits(Limits::new().max_output(4));
+    let record = unsafe { capture(&printf, c"%d".as_ptr(), 12345) }.unwrap();
+    assert_eq!(
+        record.try_format(output::fmt_write(&mut String::new())),
+        Err(Error::OutputLimit)
+    );
+}
+
+#[test]
+fn test_binary_encoder() {
+    use core::fmt;
+    use printf_compat::argument::Flags;
+    use printf_compat::output::binary::{self, Encoder};
+    use printf_compat::{
+        ArgType, Config, Conversion, Error, Limits, Printf, PrintfArg, Promoted, output,
+    };
+
+    /// `%k`: an integer in brackets.
+    fn bracket(w: &mut dyn fmt::Write, value: Promoted, _: Flags, _: Option<c_int>) -> fmt::Result {
+        let Promoted::Int(x) = value else {
+            return Err(fmt::Error);
+        };
+        write!(w, "[{x}]")
+    }
+
+    const CONFIG: Config =
+        Config::new().conversions(&[Conversion::new(b'k', ArgType::Int, bracket)]);
+
+    /// Encode, decode, and check that it matches formatting directly.
+    fn roundtrip(format: &CStr, args: &[&dyn PrintfArg]) -> String {
+        let printf = Printf::with_config(CONFIG);
+        let mut buf = [0; 256];
+        let mut encoder = Encoder::new(&mut buf, 7);
+        let encoded = printf.format_slice(format, args, &mut encoder).unwrap();
+        let mut bytes = encoder.as_bytes();
+        // The ID takes one byte.
+        assert_eq!(usize::try_from(encoded).unwrap(), bytes.len() - 1);
+        let mut s = String::new();
+        let formats = |id| (id == 7).then_some(format);
+        let written = binary::decode(&mut bytes, formats, &CONFIG, output::fmt_write(&mut s));
+        assert_eq!(bytes, b"");
+        assert_eq!(written, Ok(s.len() as c_int));
+        assert_eq!(s, printf.sprintf(format, args).unwrap());
+        s
+    }
+
+    assert_eq!(roundtrip(c"no arguments", &[]), "no arguments");
+    assert_eq!(
+        roundtrip(
+            c"%d %5i|%-+4hhd|%lu %#o",
+            &[&-3, &42, &5i8, &u64::MAX, &8u32]
+        ),
+        "-3    42|+5  |18446744073709551615 010"
+    );
+    assert_eq!(
+        roundtrip(c"%x %#x %b %8.3B %zu", &[&255, &255, &5, &5, &usize::MAX]),
+        format!("ff 0xff 101      101 {}", usize::MAX)
+    );
+    assert_eq!(
+        roundtrip(
+            c"%s|%10.3s|%c|%%|%-5%|%s",
+            &[&c"abc", &c"defgh", &'x', &None::<&CStr>]
+        ),
+        "abc|       def|x|%|%|(null)"
+    );
+    assert_eq!(
+        roundtrip(c"%p %k", &[&(0x1234 as *const c_void), &-12]),
+        "0x1234 [-12]"
+    );
+    #[cfg(feature = "float")]
+    assert_eq!(
+        roundtrip(c"%f %e %E %.2f", &[&1.5, &-0.25, &1e-10, &0.5]),
+        "1.500000 -2.500000e-01 1.000000E-10 0.50"
+    );
+
+    unsafe extern "C" fn encode(
+        config: &Config,
+        buf: &mut [u8; 64],
+        str: *const c_char,
+        args: ...
+    ) -> Box<Result<usize, Error>> {
+        let mut encoder = Encoder::new(buf, 0);
+        Box::new(
+            unsafe { Printf::with_config(*config).try_format(str, args, &mut encoder) }
+                .map(|_| encoder.as_bytes().len()),
+        )
+    }
+
+    fn decode(config: &Config, format: &CStr, mut bytes: &[u8]) -> Result<String, Error> {
+        let mut s = String::new();
+        binary::decode(
+            &mut bytes,
+            |_| Some(format),
+            config,
+            output::fmt_write(&mut s),
+        )?;
+        assert_eq!(bytes, b"");
+        Ok(s)
+    }
+
+    let mut buf = [0; 64];
+    let star = c"%*d|%-*.*s|";
+    let len = *unsafe {
+        encode(
+            &CONFIG,
+            &mut buf,
+            star.as_ptr(),
+            -4,
+            1,
+            6,
+            2,
+            c"abc".as_ptr(),
+        )
+    };
+    let expected = unsafe { c_fmt!(c"%*d|%-*.*s|", -4, 1, 6, 2, c"abc".as_ptr()) };
+    assert_eq!(decode(&CONFIG, star, &buf[..len.unwrap()]), Ok(expected.1));
+
+    #[cfg(feature = "wide")]
+    {
+        let wide: Vec<_> = "wïde"
+            .chars()
+            .map(printf_compat::argument::WChar::from)
+            .chain([0])
+            .collect();
+        let len = *unsafe {
+            encode(
+                &CONFIG,
+                &mut buf,
+                c"%6ls|%lc".as_ptr(),
+                wide.as_ptr(),
+                0x263a,
+            )
+        };
+        assert_eq!(
+            decode(&CONFIG, c"%6ls|%lc", &buf[..len.unwrap()]).unwrap(),
+            "  wïde|☺"
+        );
+        let long = vec![0x61; binary::MAX_WIDE_STRING + 1]
+            .into_iter()
+            .chain([0])
+            .collect::<Vec<_>>();
+        let mut big = [0; 64];
+        assert_eq!(
+            *unsafe { encode(&CONFIG, &mut big, c"%ls".as_ptr(), long.as_ptr()) },
+            Err(Error::BufferFull)
+        );
+    }
+
+    #[cfg(feature = "pointer-ext")]
+    {
+        let config = CONFIG.pointer_extensions(true);
+        let addr = [10u8, 0, 0, 1];
+        let mac = [0u8, 0x11, 0x22, 0x33, 0x44, 0x55];
+        let format = c"%pI4 %pMR %3phC";
+        let len = *unsafe {
+            encode(
+                &config,
+                &mut buf,
+                format.as_ptr(),
+                addr.as_ptr(),
+                mac.as_ptr(),
+                mac.as_ptr(),
+            )
+        };
+        assert_eq!(
+            decode(&config, format, &buf[..len.unwrap()]).unwrap(),
+            "10.0.0.1 55:44:33:22:11:00 00:11:22"
+        );
+    }
+
+    // `%n` isn't encoded, unless it was disabled.
+    let config = CONFIG.bytes_written(printf_compat::BytesWrittenPolicy::Ignore);
+    let mut n: c_int = 0;
+    let len = *unsafe {
+        encode(
+            &config,
+            &mut buf,
+            c"a%nb%d".as_ptr(),
+            &mut n as *mut c_int,
+            5,
+        )
+    };
+    #[cfg(feature = "n-specifier")]
+    assert_eq!(
+        decode(&config, c"a%nb%d", &buf[..len.unwrap()]).unwrap(),
+        "ab5"
+    );
+    #[cfg(not(feature = "n-specifier"))]
+    assert_eq!(
+        decode(&config, c"a%nb%d", &buf[..len.unwrap()]),
+        Err(Error::Handler)
+    );
+
+    // A stream of messages.
+    let formats = [c"first %d\n", c"second %s\n"];
+    let mut stream = Vec::new();
+    for (id, args) in [
+        (1, &[&c"x" as &dyn PrintfArg][..]),
+        (0, &[&1]),
+        (1, &[&c"y"]),
+    ] {
+        let mut buf = [0; 16];
+        let mut encoder = Encoder::new(&mut buf, id);
+        printf_compat::format_slice(formats[id as usize], args, &mut encoder).unwrap();
+        stream.extend_from_slice(encoder.as_bytes());
+    }
+    let mut bytes = &stream[..];
+    let mut s = String::new();
+    while !bytes.is_empty() {
+        let formats = |id| formats.get(id as usize).copied();
+        binary::decode(
+            &mut bytes,
+            formats,
+            &Config::new(),
+            output::fmt_write(&mut s),
+        )
+        .unwrap();
+    }
+    assert_eq!(s, "second x\nfirst 1\nsecond y\n");
+
+    let mut buf = [0; 8];
+    let mut encoder = Encoder::new(&mut buf, 0);
+    printf_compat::format_slice(c"%d %d", &[&1, &300], &mut encoder).unwrap();
+    let bytes = encoder.as_bytes().to_vec();
+    assert_eq!(bytes, [0, 0, 2, 0, 0xd8, 4]);
+    assert_eq!(
+        decode(&Config::new(), c"%d %d", &bytes[..5]),
+        Err(Error::MissingArgument)
+    );
+    assert_eq!(
+        decode(&Config::new(), c"%d", &[0, 0x7f]),
+        Err(Error::ArgumentType)
+    );
+    let mut bytes = &bytes[..];
+    assert_eq!(
+        binary::decode(
+            &mut bytes,
+            |_| None,
+            &Config::new(),
+            output::fmt_write(&mut String::new())
+        ),
+        Err(Error::InvalidFormat)
+    );
+
+    // The limits are checked when decoding, even if they weren't when
+    // encoding.
+    let encode_slice = |format: &CStr, args: &[&dyn PrintfArg]| {
+        let mut buf = [0; 64];
+        let mut encoder = Encoder::new(&mut buf, 0);
+        let printf = Printf::with_config(CONFIG);
+        printf.format_slice(format, args, &mut encoder).unwrap();
+        encoder.as_bytes().to_vec()
+    };
+    let limits = Limits::new().max_width(10).max_precision(10).max_output(16);
+    let limited = Config::new().limits(limits);
+    let bytes = encode_slice(c"%5d|%.3s", &[&1, &c"abcdef"]);
+    assert_eq!(
+        decode(&limited, c"%5d|%.3s", &bytes).as_deref(),
+        Ok("    1|abc")
+    );
+    let bytes = encode_slice(c"%11d", &[&1]);
+    assert_eq!(decode(&limited, c"%11d", &bytes), Err(Error::WidthLimit));
+    let bytes = encode_slice(c"%.11d", &[&1]);
+    assert_eq!(
+        decode(&limited, c"%.11d", &bytes),
+        Err(Error::PrecisionLimit)
+    );
+    let bytes = encode_slice(c"%s|%5d", &[&c"abcdefghijk", &1]);
+    assert_eq!(decode(&limited, c"%s|%5d", &bytes), Err(Error::OutputLimit));
+    let bytes = encode_slice(c"%s", &[&c"abcdefghijklmnopq"]);
+    assert_eq!(decode(&limited, c"%s", &bytes), Err(Error::OutputLimit));
+    let no_dereference = Config::new().limits(Limits::new().allow_dereference(false));
+    let bytes = encode_slice(c"%d %s", &[&1, &c"a"]);
+    assert_eq!(
+        decode(&no_dereference, c"%d %s", &bytes),
+        Err(Error::Forbidden(b's'))
+    );
+    let bytes = encode_slice(c"%k", &[&1]);
+    assert_eq!(
+        decode(&CONFIG.limits(Limits::new()), c"%k", &bytes),
+        Ok("[1]".into())
+    );
+    // Just the ID, since `%n` isn't encoded.
+    assert_eq!(decode(&limited, c"a%nb", &[0]), Err(Error::Forbidden(b'n')));
+
+    let mut buf = [0; 3];
+    let mut encoder = Encoder::new(&mut buf, 0);
+    assert_eq!(
+        printf_compat::format_slice(c"%d %d", &[&1, &300], &mut encoder),
+        Err(Error::BufferFull)
+    );
+    let mut encoder = Encoder::new(&mut [], 0);
+    assert_eq!(
+        printf_compat::format_slice(c"text", &[], &mut encoder),
+        Err(Error::BufferFull)
+    );
+}
+
 #[test]
 fn test_errors() {
     assert_fmt_err(c"%");
     assert_fmt_err(c"%1");
+    assert_fmt_err(c"%99999999999d");
+}
+
+#[test]
+fn test_untrusted() {
+    use printf_compat::{Error, Limits, format_untrusted, output::fmt_write};
+    use std::ffi::CString;
+
+    unsafe extern "C" fn format(
+        limits: &Limits,
+        str: *const c_char,
+        args: ...
+    ) -> Box<(Result<c_int, Error>, String)> {
+        let mut s = String::new();
+        let res = unsafe { format_untrusted(str, args, limits, fmt_write(&mut s)) };
+        Box::new((res, s))
+    }
+
+    let limits = Limits::new().max_width(10).max_precision(5).max_output(20);
+    unsafe {
+        assert_eq!(
+            *format(&limits, c"%10d|%.5s".as_ptr(), 1, c"abcdef".as_ptr()),
+            (Ok(16), "         1|abcde".into())
+        );
+        assert_eq!(
+            *format(&limits, c"a%11d".as_ptr(), 1),
+            (Err(Error::WidthLimit), "a".into())
+        );
+        assert_eq!(
+            *format(&limits, c"a%*d".as_ptr(), -11, 1),
+            (Err(Error::WidthLimit), "a".into())
+        );
+        assert_eq!(
+            *format(&limits, c"a%.*d".as_ptr(), 6, 1),
+            (Err(Error::PrecisionLimit), "a".into())
+        );
+        assert_eq!(
+            *format(&limits, c"%s%10d".as_ptr(), c"abcdefghijklmno".as_ptr(), 1),
+            (Err(Error::OutputLimit), "abcdefghijklmno".into())
+        );
+        assert_eq!(
+            *format(&limits, c"%s".as_ptr(), c"abcdefghijklmnopqrstu".as_ptr()),
+            (Err(Error::OutputLimit), "".into())
+        );
+        assert_eq!(
+            *format(&limits, c"%.5s|abcdefghijklmnopq".as_ptr(), c"xyz".as_ptr()),
+            (Err(Error::OutputLimit), "xyz".into())
+        );
+
+        // Nothing that doesn't fit reaches the sink.
+        let long = CString::new(vec![b'x'; 20_000]).unwrap();
+        let small = Limits::new().max_output(16);
+        assert_eq!(
+            *format(&small, c"%s".as_ptr(), long.as_ptr()),
+            (Err(Error::OutputLimit), "".into())
+        );
+        #[cfg(feature = "float")]
+        assert_eq!(
+            *format(&small, c"%.1000f".as_ptr(), 1.0),
+            (Err(Error::OutputLimit), "".into())
+        );
+
+        // Strings longer than `std`'s widths and precisions can be are fine.
+        let long = CString::new(vec![b'x'; 70_000]).unwrap();
+        assert_eq!(
+            *format(&Limits::new(), c"%s".as_ptr(), long.as_ptr()),
+            (Err(Error::OutputLimit), "".into())
+        );
+        let unlimited = Limits::new().max_output(usize::MAX);
+        assert_eq!(
+            format(&unlimited, c"%s".as_ptr(), long.as_ptr()).0,
+            Ok(70_000)
+        );
+        let mut n = 0 as c_int;
+        assert_eq!(
+            *format(&limits, c"a%n".as_ptr(), &mut n as *mut c_int),
+            (Err(Error::Forbidden(b'n')), "a".into())
+        );
+        let limits = limits.allow_dereference(false);
+        assert_eq!(
+            *format(&limits, c"a%s".as_ptr(), c"abc".as_ptr()),
+            (Err(Error::Forbidden(b's')), "a".into())
+        );
+        assert_eq!(
+            *format(&limits, c"%q".as_ptr()),
+            (Err(Error::InvalidFormat), "".into())
+        );
+    }
+}
+
+#[test]
+fn test_slice() {
+    use printf_compat::{Error, sprintf};
+
+    macro_rules! assert_eq_sprintf {
+        ($format:literal, [$($p:expr),*], [$($c:expr),*]) => {
+            let (_, expected) = unsafe { c_fmt!($format $(, $c)*) };
+            assert_eq!(sprintf($format, &[$(&$p),*]).unwrap(), expected);
+        };
+    }
+
+    assert_eq_sprintf!(c"%d %i %d", [-5i8, 300i16, i32::MIN], [-5, 300, i32::MIN]);
+    assert_eq_sprintf!(c"%lld %ld", [i64::MIN, -1isize], [i64::MIN, -1 as c_long]);
+    assert_eq_sprintf!(
+        c"%u %x %o",
+        [200u8, 0xbeefu16, u32::MAX],
+        [200, 0xbeef, u32::MAX]
+    );
+    assert_eq_sprintf!(c"%llu %zx", [u64::MAX, usize::MAX], [u64::MAX, usize::MAX]);
+    assert_eq_sprintf!(c"%hhd %hu %hhx", [300, -1, 0x1ff], [300, -1, 0x1ff]);
+    assert_eq_sprintf!(c"%u %d", [-1, u32::MAX], [-1, u32::MAX]);
+    assert_eq_sprintf!(
+        c"%*d|%-*.*s|",
+        [5, 1, 6, 2, c"abc"],
+        [5, 1, 6, 2, c"abc".as_ptr()]
+    );
+    assert_eq_sprintf!(c"%c%c", ['a', b'b'], [b'a' as c_int, b'b' as c_int]);
+    assert_eq_sprintf!(c"%s", [None::<&CStr>], [core::ptr::null::<c_char>()]);
+    let s = c"abc";
+    assert_eq_sprintf!(
+        c"%p %p",
+        [0x1234 as *const u8, s],
+        [0x1234 as *const u8, s.as_ptr()]
+    );
+    #[cfg(feature = "float")]
+    assert_eq_sprintf!(
+        c"%.3f %.1f %e",
+        [0.1f32, 1e5, -2.5],
+        [f64::from(0.1f32), 1e5, -2.5]
+    );
+
+    assert_eq!(sprintf(c"%d %d", &[&1]), Err(Error::MissingArgument));
+    assert_eq!(sprintf(c"%*d", &[&1]), Err(Error::MissingArgument));
+    assert_eq!(sprintf(c"%d", &[&1.0]), Err(Error::ArgumentType));
+    assert_eq!(sprintf(c"%f", &[&1]), Err(Error::ArgumentType));
+    assert_eq!(
+        sprintf(c"%s", &[&null_mut::<c_char>()]),
+        Err(Error::ArgumentType)
+    );
+    #[cfg(feature = "n-specifier")]
+    assert_eq!(
+        sprintf(c"%n", &[&null_mut::<c_int>()]),
+        Err(Error::Forbidden(b'n'))
+    );
+    assert_eq!(sprintf(c"%d", &[&1, &2]).unwrap(), "1");
+}
+
+/// The `printf!` macro checks its argument types against the unpromoted C
+/// types, so it can't share arguments with `c_fmt!`. These outputs are all
+/// checked against C by the tests above.
+#[cfg(feature = "macros")]
+#[test]
+fn test_macro() {
+    use printf_compat::printf;
+
+    macro_rules! assert_eq_printf {
+        ($format:literal $(, $p:expr)* => $expected:literal) => {
+            assert_eq!(printf!($format $(, $p)*).to_string(), $expected);
+            let mut s = String::new();
+            let bytes_written = printf!($format $(, $p)*).format(printf_compat::output::fmt_write(&mut s));
+            assert_eq!((usize::try_from(bytes_written).unwrap(), s.as_str()), ($expected.len(), $expected));
+        };
+    }
+
+    assert_eq_printf!(c"Hello, %s!", c"world" => "Hello, world!");
+    assert_eq_printf!("%5d|%-5d|%+d", 12, 34, 56 => "   12|34   |+56");
+    assert_eq_printf!(c"%*d|%.*s|%.*s|", -4, 1, 2, c"abc", -1, c"abc" => "1   |ab|abc|");
+    assert_eq_printf!(
+        c"%hhd %hu %lx %llo %zu",
+        -1, 65535, 255, 8, 7 => "-1 65535 ff 10 7"
+    );
+    assert_eq_printf!(c"%c%%", b'x' as c_char => "x%");
+    assert_eq_printf!(c"%p", 0x1234 as *const c_void => "0x1234");
+    assert_eq_printf!(c"%#b %08hhB", 5, 255 => "0b101 11111111");
+    #[cfg(feature = "float")]
+    assert_eq_printf!(c"%.3f %e", 1.5, 1e10 => "1.500 1.000000e+10");
 }
 
 /// Test that specifying precision with an integral type does _not_
@@ -270,3 +2074,85 @@ fn test_int_precision() {
         assert_eq!(rust_fmt(c"%.3d".as_ptr(), 1).1, "1");
     }
 }
+
+#[cfg(feature = "log")]
+#[test]
+fn test_log() {
+    use std::sync::Mutex;
+
+    use log::{Level, LevelFilter};
+    use printf_compat::log::Logger;
+
+    static RECORDS: Mutex<Vec<(Level, String, String)>> = Mutex::new(Vec::new());
+
+    struct Capture;
+
+    impl log::Log for Capture {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= Level::Info
+        }
+
+        fn log(&self, record: &log::Record) {
+            let record = (
+                record.level(),
+                record.target().to_owned(),
+                record.args().to_string(),
+            );
+            RECORDS.lock().unwrap().push(record);
+        }
+
+        fn flush(&self) {}
+    }
+
+    const LOGGER: Logger = Logger::new("libfoo");
+    const CUSTOM: Logger = Logger::new("libbar")
+        .levels(&[(1, Level::Error), (2, Level::Warn)])
+        .default_level(Level::Trace);
+
+    unsafe extern "C" fn callback(logger: &Logger, level: c_int, str: *const c_char, args: ...) {
+        unsafe { logger.log(level, str, args) };
+    }
+
+    log::set_logger(&Capture).unwrap();
+    log::set_max_level(LevelFilter::Debug);
+    unsafe {
+        callback(
+            &LOGGER,
+            3,
+            c"open %s: %d\n".as_ptr(),
+            c"/dev/foo".as_ptr(),
+            -2,
+        );
+        callback(&LOGGER, 4, c"two lines\n\n".as_ptr());
+        callback(&LOGGER, 6, c"%s".as_ptr(), c"no newline".as_ptr());
+        callback(
+            &LOGGER,
+            5,
+            c"%s|%c".as_ptr(),
+            c"split\n".as_ptr(),
+            b'\n' as c_int,
+        );
+        // Disabled by the logger, or not in the table.
+        callback(&LOGGER, 7, c"debug\n".as_ptr());
+        callback(&CUSTOM, 42, c"trace\n".as_ptr());
+        callback(&CUSTOM, 2, c"%d%%\n".as_ptr(), 50);
+        // What was formatted before an error is still logged.
+        callback(&CUSTOM, 1, c"before %n after".as_ptr(), null_mut::<c_int>());
+    }
+
+    let records = |level, target: &str, message: &str| (level, target.into(), message.into());
+    assert_eq!(
+        *RECORDS.lock().unwrap(),
+        [
+            records(Level::Error, "libfoo", "open /dev/foo: -2"),
+            records(Level::Warn, "libfoo", "two lines\n"),
+            records(Level::Info, "libfoo", "no newline"),
+            records(Level::Info, "libfoo", "split\n|"),
+            records(Level::Warn, "libbar", "50%"),
+            records(Level::Error, "libbar", "before "),
+        ]
+    );
+    assert_eq!(LOGGER.level(0), Level::Error);
+    assert_eq!(LOGGER.level(100), Level::Info);
+    assert_eq!(CUSTOM.level(1), Level::Error);
+}